@@ -1,11 +1,22 @@
-use mqtt::client::Client;
+use mqtt::client::{Client, ClientOptions, ConnectOptions};
+use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let client = Client::new(String::from("test_client"), "0.0.0.0");
-    client.connect();
+    let options = ClientOptions::new("0.0.0.0")
+        .with_port(1883)
+        .with_nodelay(true);
+    let client = Client::with_options(String::from("test_client"), &options).unwrap();
+    client.connect(&ConnectOptions::new()).unwrap();
     let topic = "testing/topic";
     let payload = "hello world";
-    client.subscribe(topic);
-    client.publish(topic, payload, 1, false);
-    client.disconnect();
+    client
+        .subscribe_with(topic, 1, |message| {
+            println!("{}: {:?}", message.topic(), message.payload())
+        })
+        .unwrap();
+    client.publish(topic, payload.as_bytes(), 1, false).unwrap();
+    // messages arrive on the client's background thread
+    thread::sleep(Duration::from_secs(1));
+    client.disconnect().unwrap();
 }
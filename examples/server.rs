@@ -1,7 +1,16 @@
+use std::io;
+use std::thread;
+
 use mqtt::server::Server;
 
 fn main() {
-    let mut server = Server::new();
-    server.listen();
-    //TODO graceful shutdown
+    let server = Server::new();
+    let listener = {
+        let server = server.clone();
+        thread::spawn(move || server.listen())
+    };
+    println!("listening on port 1883, press enter to shut down");
+    let _ = io::stdin().read_line(&mut String::new());
+    server.shutdown();
+    listener.join().unwrap().unwrap();
 }
@@ -1,50 +1,1010 @@
-use crate::control_packet::ControlPacket;
-use std::io::Write;
+use crate::client::event_loop::Shared;
+use crate::client::subscription::Handler;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::PacketType;
+use crate::error::Error;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::topic;
+#[cfg(test)]
 use std::net::TcpStream;
+use std::sync::{mpsc, Arc};
+use std::thread;
 
+mod connect;
+mod disconnect;
+mod event_loop;
+mod keep_alive;
+mod options;
+mod reconnect;
+mod subscription;
+
+pub use crate::client::connect::{ConnectOptions, ConnectResult};
+pub use crate::client::disconnect::DisconnectOptions;
+pub use crate::client::options::ClientOptions;
+pub use crate::client::reconnect::{ConnectionEvent, ReconnectPolicy};
+pub use crate::client::subscription::{Message, Subscription};
+
+/// A handle to one connection. Clones share the connection and may be used from any thread;
+/// a single background thread reads from the server, delivers messages and completes the
+/// requests waiting for an acknowledgement.
+///
+/// Callbacks run on that background thread, so they cannot wait on anything the background
+/// thread completes: a QoS 1 or 2 publish, a subscribe or an unsubscribe from a callback
+/// fails with `Error::InvalidArgument`.
+#[derive(Clone)]
 pub struct Client {
-    client_id: String,
-    stream: TcpStream,
-    subscriptions: Vec<String>,
+    shared: Arc<Shared>,
 }
 
 impl Client {
-    pub fn new(client_id: String, host: &str) -> Self {
-        let stream = TcpStream::connect(format!("{host}:1883")).unwrap();
+    /// Opens a TCP connection to `host` on port 1883. Nothing is sent before `connect`.
+    pub fn new(client_id: String, host: &str) -> Result<Self, Error> {
+        Client::with_options(client_id, &ClientOptions::new(host))
+    }
+
+    /// Opens a TCP connection as the options say; reconnects use the same options.
+    pub fn with_options(client_id: String, options: &ClientOptions) -> Result<Self, Error> {
+        let stream = options.open()?;
+        Ok(Client {
+            shared: Arc::new(Shared::new(client_id, stream, options.clone())),
+        })
+    }
+
+    #[cfg(test)]
+    fn from_stream(client_id: String, stream: TcpStream) -> Self {
+        let peer_addr = stream.peer_addr().unwrap();
+        let options = ClientOptions::new("127.0.0.1").with_addrs(&[peer_addr]);
         Client {
-            client_id,
-            stream,
-            subscriptions: Vec::new(),
+            shared: Arc::new(Shared::new(client_id, stream, options)),
+        }
+    }
+
+    pub fn client_id(&self) -> String {
+        self.shared.client_id()
+    }
+
+    /// Sends CONNECT and waits up to the options' timeout for the CONNACK, then starts the
+    /// background thread. A CONNACK with a failure reason code is returned as
+    /// `Error::ConnectionRefused`. Messages still in flight are resent with DUP set if the
+    /// server kept the session, and dropped otherwise.
+    ///
+    /// The background thread pings the server whenever nothing was sent for the keep alive,
+    /// or for the Server Keep Alive if the CONNACK has one. If the PINGRESP does not arrive
+    /// within the options' timeout the connection is closed and waiting requests fail with
+    /// `Error::Timeout`.
+    ///
+    /// Connecting again while the background thread runs fails with
+    /// `Error::InvalidArgument`.
+    pub fn connect(&self, options: &ConnectOptions) -> Result<ConnectResult, Error> {
+        if self.shared.is_running() {
+            return Err(Error::InvalidArgument("already connected"));
+        }
+        let mut reader = self.shared.try_clone_stream()?;
+        let (result, decoder) = connect::handshake(&mut reader, options, &self.shared.client_id())?;
+        let keep_alive = self.shared.established(options, &result)?;
+        let shared = Arc::clone(&self.shared);
+        let event_loop =
+            thread::spawn(move || event_loop::run(&shared, reader, decoder, keep_alive));
+        self.shared.set_event_loop(event_loop);
+        Ok(result)
+    }
+
+    /// Connection state changes from now on, starting with the next connect. With a
+    /// reconnect policy in the connect options, a lost connection is reported and then
+    /// replaced in the background: the CONNECT is sent again with Clean Start set to 0, QoS 1
+    /// and 2 messages still in flight are resent with DUP set, and if the server no longer
+    /// has the session every topic filter is subscribed to again. Waiting publishes complete
+    /// once the new connection acknowledges them, while waiting subscribes and unsubscribes
+    /// fail.
+    pub fn connection_events(&self) -> mpsc::Receiver<ConnectionEvent> {
+        self.shared.connection_events()
+    }
+
+    /// Publishes and, for QoS 1 and 2, blocks until the server has acknowledged the message.
+    /// The reason code is the one from the PUBACK, a failed PUBREC or the PUBCOMP, so it may
+    /// report a failure such as `QuotaExceeded`; QoS 0 always reports `Success`.
+    /// A message too large to encode, or larger than the Maximum Packet Size of the server,
+    /// is refused with `Error::InvalidArgument`.
+    pub fn publish(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        qos: u8,
+        retain: bool,
+    ) -> Result<ReasonCode, Error> {
+        if qos > 2 {
+            return Err(Error::InvalidArgument("qos must be 0, 1 or 2"));
+        }
+        if !topic::is_valid_topic_name(topic) {
+            return Err(Error::InvalidArgument("invalid topic name"));
+        }
+        // topic name, packet identifier for QoS 1 and 2, no properties, then the payload
+        let packet_identifier_len = if qos > 0 { 2 } else { 0 };
+        self.shared
+            .check_packet_size(2 + topic.len() + packet_identifier_len + 1 + payload.len())?;
+        if qos == 0 {
+            let publish = Publish::new(
+                topic,
+                None,
+                Vec::from(payload),
+                qos,
+                retain,
+                Properties::default(),
+            );
+            self.shared.send(&publish)?;
+            return Ok(ReasonCode::Success);
+        }
+        self.check_not_in_callback()?;
+        let receiver = self.shared.start_publish(topic, payload, qos, retain)?;
+        receiver.recv().map_err(|_| self.shared.connection_error())
+    }
+
+    /// Subscribes to `topic_filter` and returns an iterator over the matching messages.
+    pub fn subscribe(&self, topic_filter: &str, qos: u8) -> Result<Subscription, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let granted_qos = self.send_subscribe(topic_filter, qos, Handler::Channel(sender))?;
+        Ok(Subscription::new(granted_qos, receiver))
+    }
+
+    /// Subscribes to `topic_filter` and calls `callback` with every matching message.
+    /// Returns the QoS granted by the server.
+    pub fn subscribe_with<F>(&self, topic_filter: &str, qos: u8, callback: F) -> Result<u8, Error>
+    where
+        F: FnMut(Message) + Send + 'static,
+    {
+        self.send_subscribe(topic_filter, qos, Handler::Callback(Box::new(callback)))
+    }
+
+    fn send_subscribe(&self, topic_filter: &str, qos: u8, handler: Handler) -> Result<u8, Error> {
+        if qos > 2 {
+            return Err(Error::InvalidArgument("qos must be 0, 1 or 2"));
+        }
+        if !topic::is_valid_topic_filter(topic_filter) {
+            return Err(Error::InvalidArgument("invalid topic filter"));
         }
+        self.check_not_in_callback()?;
+        let receiver = self.shared.start_subscribe(topic_filter, qos, handler)?;
+        let reason_code = single_reason_code(
+            receiver
+                .recv()
+                .map_err(|_| self.shared.connection_error())?,
+            PacketType::SUBACK,
+        )?;
+        if reason_code.is_failure() {
+            return Err(Error::SubscriptionRejected(reason_code));
+        }
+        Ok(reason_code.value())
+    }
+
+    /// Stops delivery for `topic_filter` once the server has acknowledged, and returns the
+    /// UNSUBACK reason code, e.g. `NoSubscriptionExisted`.
+    pub fn unsubscribe(&self, topic_filter: &str) -> Result<ReasonCode, Error> {
+        if !topic::is_valid_topic_filter(topic_filter) {
+            return Err(Error::InvalidArgument("invalid topic filter"));
+        }
+        self.check_not_in_callback()?;
+        let receiver = self.shared.start_unsubscribe(topic_filter)?;
+        single_reason_code(
+            receiver
+                .recv()
+                .map_err(|_| self.shared.connection_error())?,
+            PacketType::UNSUBACK,
+        )
+    }
+
+    // only the background thread reads the acknowledgement a request waits for
+    fn check_not_in_callback(&self) -> Result<(), Error> {
+        if self.shared.on_event_loop() {
+            return Err(Error::InvalidArgument(
+                "cannot wait for an acknowledgement in a callback",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Disconnects normally, so the server discards the will message.
+    pub fn disconnect(&self) -> Result<(), Error> {
+        self.disconnect_with(&DisconnectOptions::new())
+    }
+
+    /// Sends a DISCONNECT as the options say, closes the connection and stops the background
+    /// thread, waiting for it unless called from a callback. Requests still waiting fail,
+    /// and no reconnect follows. The connection is closed even if sending fails, in which
+    /// case the error is returned.
+    pub fn disconnect_with(&self, options: &DisconnectOptions) -> Result<(), Error> {
+        self.shared.disconnect(options)
+    }
+}
+
+// we only ever request one topic filter at a time
+fn single_reason_code(
+    reason_codes: Vec<ReasonCode>,
+    packet_type: PacketType,
+) -> Result<ReasonCode, Error> {
+    match reason_codes[..] {
+        [reason_code] => Ok(reason_code),
+        _ => Err(Error::ProtocolError {
+            packet_type: Some(packet_type),
+            offset: None,
+            message: "reason code count does not match the request",
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{
+        Client, ClientOptions, ConnectOptions, ConnectionEvent, DisconnectOptions, ReconnectPolicy,
+    };
+    use crate::common::{FourByteInt, TwoByteInt, UTF8String, MAX_REMAINING_LENGTH};
+    use crate::control_packet::connack::Connack;
+    use crate::control_packet::disconnect::Disconnect;
+    use crate::control_packet::pingresp::Pingresp;
+    use crate::control_packet::puback::Puback;
+    use crate::control_packet::pubcomp::Pubcomp;
+    use crate::control_packet::publish::Publish;
+    use crate::control_packet::pubrec::Pubrec;
+    use crate::control_packet::pubrel::Pubrel;
+    use crate::control_packet::suback::Suback;
+    use crate::control_packet::unsuback::Unsuback;
+    use crate::control_packet::{ControlPacket, Packet};
+    use crate::decoder::Decoder;
+    use crate::error::Error;
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{mpsc, Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // accepts one connection and hands it to `server`
+    fn serve<F>(server: F) -> TcpStream
+    where
+        F: FnOnce(TcpStream, Decoder) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            server(stream, Decoder::default());
+        });
+        TcpStream::connect(addr).unwrap()
+    }
+
+    // reads the CONNECT and answers with `connack` if there is one
+    fn serve_connack(connack: Option<Connack>) -> TcpStream {
+        serve(move |mut stream, mut decoder| {
+            let packet = decoder.read_packet(&mut stream).unwrap();
+            assert!(matches!(packet, Packet::Connect(_)));
+            match connack {
+                Some(connack) => stream.write_all(&connack.as_bytes()).unwrap(),
+                None => thread::sleep(Duration::from_millis(500)),
+            }
+        })
+    }
+
+    fn accept_connect(stream: &mut TcpStream, decoder: &mut Decoder) {
+        let packet = decoder.read_packet(stream).unwrap();
+        assert!(matches!(packet, Packet::Connect(_)));
+        let connack = Connack::new(false, ReasonCode::Success, Properties::default());
+        stream.write_all(&connack.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_connect() {
+        let properties = Properties::new(vec![Property::AssignedClientIdentifier(
+            UTF8String::new("auto-1"),
+        )]);
+        let stream = serve_connack(Some(Connack::new(true, ReasonCode::Success, properties)));
+        let client = Client::from_stream(String::new(), stream);
+        let result = client.connect(&ConnectOptions::new()).unwrap();
+        assert!(result.session_present());
+        assert_eq!(result.reason_code(), ReasonCode::Success);
+        assert_eq!(client.client_id(), "auto-1");
+    }
+
+    #[test]
+    fn test_with_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let options = ClientOptions::new("127.0.0.1").with_port(addr.port());
+        assert!(Client::with_options(String::from("foo"), &options).is_ok());
+        drop(listener);
+        // nothing listens any more, which is an error instead of a panic
+        assert!(matches!(
+            Client::with_options(String::from("foo"), &options),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_connect_invalid_options() {
+        let (client, _stream) = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (server_stream, _) = listener.accept().unwrap();
+            (
+                Client::from_stream(String::from("foo"), stream),
+                server_stream,
+            )
+        };
+        let options = ConnectOptions::new().with_receive_maximum(0);
+        assert!(matches!(
+            client.connect(&options).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+
+    #[test]
+    fn test_connect_refused() {
+        let connack = Connack::new(false, ReasonCode::NotAuthorized, Properties::default());
+        let stream = serve_connack(Some(connack));
+        let client = Client::from_stream(String::from("foo"), stream);
+        let error = client.connect(&ConnectOptions::new()).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::ConnectionRefused(ReasonCode::NotAuthorized)
+        ));
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        let stream = serve_connack(None);
+        let client = Client::from_stream(String::from("foo"), stream);
+        let options = ConnectOptions::new().with_timeout(Duration::from_millis(50));
+        assert!(matches!(
+            client.connect(&options).unwrap_err(),
+            Error::Timeout
+        ));
+    }
+
+    #[test]
+    fn test_publish_qos_1() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            let packet_identifier = match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Publish(publish) => publish.packet_identifier().unwrap(),
+                packet => panic!("unexpected {packet:?}"),
+            };
+            let puback = Puback::new(
+                packet_identifier,
+                ReasonCode::QuotaExceeded,
+                Properties::default(),
+            );
+            stream.write_all(&puback.as_bytes()).unwrap();
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let reason_code = client.publish("foo/bar", b"hello", 1, false).unwrap();
+        assert_eq!(reason_code, ReasonCode::QuotaExceeded);
+    }
+
+    #[test]
+    fn test_publish_qos_2() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            let packet_identifier = match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Publish(publish) => publish.packet_identifier().unwrap(),
+                packet => panic!("unexpected {packet:?}"),
+            };
+            let pubrec = Pubrec::new(
+                packet_identifier,
+                ReasonCode::Success,
+                Properties::default(),
+            );
+            stream.write_all(&pubrec.as_bytes()).unwrap();
+            match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Pubrel(pubrel) => assert_eq!(pubrel.packet_identifier(), packet_identifier),
+                packet => panic!("unexpected {packet:?}"),
+            }
+            let pubcomp = Pubcomp::new(
+                packet_identifier,
+                ReasonCode::Success,
+                Properties::default(),
+            );
+            stream.write_all(&pubcomp.as_bytes()).unwrap();
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let reason_code = client.publish("foo/bar", b"hello", 2, false).unwrap();
+        assert_eq!(reason_code, ReasonCode::Success);
+    }
+
+    #[test]
+    fn test_connect_resends_in_flight() {
+        let stream = serve(|mut stream, mut decoder| {
+            decoder.read_packet(&mut stream).unwrap();
+            let connack = Connack::new(true, ReasonCode::Success, Properties::default());
+            stream.write_all(&connack.as_bytes()).unwrap();
+            match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Publish(publish) => {
+                    assert!(publish.dup());
+                    assert_eq!(publish.packet_identifier(), Some(1));
+                }
+                packet => panic!("unexpected {packet:?}"),
+            }
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.shared.insert_in_flight(Publish::new(
+            "foo/bar",
+            Some(1),
+            vec![1],
+            1,
+            false,
+            Properties::default(),
+        ));
+        client.connect(&ConnectOptions::new()).unwrap();
+        assert_eq!(client.shared.in_flight_len(), 1);
+    }
+
+    // answers the SUBSCRIBE with `reason_code` and returns the requested topic filter
+    fn accept_subscribe(
+        stream: &mut TcpStream,
+        decoder: &mut Decoder,
+        reason_code: ReasonCode,
+    ) -> String {
+        let subscribe = match decoder.read_packet(stream).unwrap() {
+            Packet::Subscribe(subscribe) => subscribe,
+            packet => panic!("unexpected {packet:?}"),
+        };
+        let suback = Suback::new(
+            subscribe.packet_identifier(),
+            vec![reason_code],
+            Properties::default(),
+        );
+        stream.write_all(&suback.as_bytes()).unwrap();
+        String::from(subscribe.subscriptions()[0].topic_filter())
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            let topic_filter = accept_subscribe(&mut stream, &mut decoder, ReasonCode::GrantedQoS1);
+            assert_eq!(topic_filter, "foo/+");
+            for topic in ["foo/bar", "bar/foo", "foo/baz"] {
+                let publish =
+                    Publish::new(topic, Some(7), vec![1], 1, false, Properties::default());
+                stream.write_all(&publish.as_bytes()).unwrap();
+                match decoder.read_packet(&mut stream).unwrap() {
+                    Packet::Puback(puback) => assert_eq!(puback.packet_identifier(), 7),
+                    packet => panic!("unexpected {packet:?}"),
+                }
+            }
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let mut subscription = client.subscribe("foo/+", 2).unwrap();
+        assert_eq!(subscription.granted_qos(), 1);
+        assert_eq!(subscription.next().unwrap().topic(), "foo/bar");
+        assert_eq!(subscription.next().unwrap().topic(), "foo/baz");
+        assert!(subscription.try_next().is_none());
+    }
+
+    #[test]
+    fn test_subscribe_with_qos_2() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::GrantedQoS2);
+            let publish = Publish::new("foo", Some(3), vec![1], 2, false, Properties::default());
+            stream.write_all(&publish.as_bytes()).unwrap();
+            assert!(matches!(
+                decoder.read_packet(&mut stream).unwrap(),
+                Packet::Pubrec(_)
+            ));
+            // resent before the PUBREC arrived, must not be delivered twice
+            stream
+                .write_all(&publish.with_dup(true).as_bytes())
+                .unwrap();
+            assert!(matches!(
+                decoder.read_packet(&mut stream).unwrap(),
+                Packet::Pubrec(_)
+            ));
+            let pubrel = Pubrel::new(3, ReasonCode::Success, Properties::default());
+            stream.write_all(&pubrel.as_bytes()).unwrap();
+            match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Pubcomp(pubcomp) => assert_eq!(pubcomp.reason_code(), ReasonCode::Success),
+                packet => panic!("unexpected {packet:?}"),
+            }
+            let last = Publish::new("foo", None, vec![2], 0, false, Properties::default());
+            stream.write_all(&last.as_bytes()).unwrap();
+        });
+        let (sender, receiver) = mpsc::channel();
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let granted_qos = client
+            .subscribe_with("foo", 2, move |message| sender.send(message).unwrap())
+            .unwrap();
+        assert_eq!(granted_qos, 2);
+        let payloads: Vec<Vec<u8>> = receiver
+            .iter()
+            .take(2)
+            .map(|message| Vec::from(message.payload()))
+            .collect();
+        assert_eq!(payloads, vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_subscribe_rejected() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::NotAuthorized);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        assert!(matches!(
+            client.subscribe("foo", 1).unwrap_err(),
+            Error::SubscriptionRejected(ReasonCode::NotAuthorized)
+        ));
+        assert!(matches!(
+            client.subscribe("foo/#/bar", 1).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+
+    #[test]
+    fn test_connect_twice() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            // the second connect sends nothing, so the next packet is the DISCONNECT
+            assert!(matches!(
+                decoder.read_packet(&mut stream).unwrap(),
+                Packet::Disconnect(_)
+            ));
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        assert!(matches!(
+            client.connect(&ConnectOptions::new()).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+        client.disconnect().unwrap();
+    }
+
+    #[test]
+    fn test_unsubscribe_in_callback() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::GRANTED_QOS_0);
+            let publish = Publish::new("foo", None, vec![1], 0, false, Properties::default());
+            stream.write_all(&publish.as_bytes()).unwrap();
+            let _ = decoder.read_packet(&mut stream);
+        });
+        let (sender, receiver) = mpsc::channel();
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let in_callback = client.clone();
+        client
+            .subscribe_with("foo", 0, move |_| {
+                sender.send(in_callback.unsubscribe("foo")).unwrap();
+            })
+            .unwrap();
+        // fails right away instead of waiting for an UNSUBACK nobody reads
+        let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_publish_topic_too_long() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        // the length would not fit in the two bytes in front of the topic
+        let topic = "a".repeat(65_536);
+        assert!(matches!(
+            client.publish(&topic, &[1], 0, false).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+
+    #[test]
+    fn test_publish_too_large_to_encode() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        // with the topic name in front the remaining length needs a fifth byte
+        let payload = vec![0; MAX_REMAINING_LENGTH];
+        assert!(matches!(
+            client.publish("a", &payload, 0, false).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+
+    #[test]
+    fn test_publish_over_maximum_packet_size() {
+        let stream = serve(|mut stream, mut decoder| {
+            decoder.read_packet(&mut stream).unwrap();
+            let properties =
+                Properties::new(vec![Property::MaximumPacketSize(FourByteInt::new(20))]);
+            let connack = Connack::new(false, ReasonCode::Success, properties);
+            stream.write_all(&connack.as_bytes()).unwrap();
+            let publish = match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Publish(publish) => publish,
+                packet => panic!("unexpected {packet:?}"),
+            };
+            assert_eq!(publish.as_bytes().len(), 20);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        // 2 bytes of fixed header, 3 of topic name and 1 of property length
+        assert!(matches!(
+            client.publish("a", &[0; 15], 0, false).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+        assert_eq!(
+            client.publish("a", &[0; 14], 0, false).unwrap(),
+            ReasonCode::Success
+        );
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::GRANTED_QOS_0);
+            let unsubscribe = match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Unsubscribe(unsubscribe) => unsubscribe,
+                packet => panic!("unexpected {packet:?}"),
+            };
+            assert_eq!(unsubscribe.topic_filters(), vec!["foo"]);
+            let unsuback = Unsuback::new(
+                unsubscribe.packet_identifier(),
+                vec![ReasonCode::Success],
+                Properties::default(),
+            );
+            stream.write_all(&unsuback.as_bytes()).unwrap();
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let mut subscription = client.subscribe("foo", 0).unwrap();
+        assert_eq!(client.unsubscribe("foo").unwrap(), ReasonCode::Success);
+        // the handler is gone, so the subscription ends
+        assert!(subscription.next().is_none());
+    }
+
+    #[test]
+    fn test_subscription_ends_on_disconnect() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::GRANTED_QOS_0);
+            decoder.read_packet(&mut stream).unwrap();
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let mut subscription = client.subscribe("foo", 0).unwrap();
+        client.disconnect().unwrap();
+        assert!(subscription.next().is_none());
+    }
+
+    #[test]
+    fn test_subscription_ends_on_connection_lost() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            accept_subscribe(&mut stream, &mut decoder, ReasonCode::GRANTED_QOS_0);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        // the server closes the connection once the subscription is granted
+        let mut subscription = client.subscribe("foo", 0).unwrap();
+        assert!(subscription.next().is_none());
+    }
+
+    #[test]
+    fn test_publish_from_many_threads() {
+        const THREADS: usize = 4;
+        const MESSAGES: usize = 5;
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            for _ in 0..THREADS * MESSAGES {
+                let packet_identifier = match decoder.read_packet(&mut stream).unwrap() {
+                    Packet::Publish(publish) => publish.packet_identifier().unwrap(),
+                    packet => panic!("unexpected {packet:?}"),
+                };
+                let puback = Puback::new(
+                    packet_identifier,
+                    ReasonCode::Success,
+                    Properties::default(),
+                );
+                stream.write_all(&puback.as_bytes()).unwrap();
+            }
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&ConnectOptions::new()).unwrap();
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let publishers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let client = client.clone();
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..MESSAGES {
+                        let reason_code = client.publish("foo", b"hello", 1, false).unwrap();
+                        assert_eq!(reason_code, ReasonCode::Success);
+                    }
+                })
+            })
+            .collect();
+        for publisher in publishers {
+            publisher.join().unwrap();
+        }
+        assert_eq!(client.shared.in_flight_len(), 0);
+    }
+
+    #[test]
+    fn test_keep_alive_ping() {
+        let stream = serve(|mut stream, mut decoder| {
+            decoder.read_packet(&mut stream).unwrap();
+            let properties = Properties::new(vec![Property::ServerKeepAlive(TwoByteInt::new(1))]);
+            let connack = Connack::new(false, ReasonCode::Success, properties);
+            stream.write_all(&connack.as_bytes()).unwrap();
+            let packet = decoder.read_packet(&mut stream).unwrap();
+            assert!(matches!(packet, Packet::Pingreq(_)));
+            stream.write_all(&Pingresp::new().as_bytes()).unwrap();
+            let packet_identifier = match decoder.read_packet(&mut stream).unwrap() {
+                Packet::Publish(publish) => publish.packet_identifier().unwrap(),
+                packet => panic!("unexpected {packet:?}"),
+            };
+            let puback = Puback::new(
+                packet_identifier,
+                ReasonCode::Success,
+                Properties::default(),
+            );
+            stream.write_all(&puback.as_bytes()).unwrap();
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        // the Server Keep Alive of 1 second replaces the one asked for
+        let result = client
+            .connect(&ConnectOptions::new().with_keep_alive(60))
+            .unwrap();
+        assert_eq!(result.server_keep_alive(), Some(1));
+        thread::sleep(Duration::from_millis(1500));
+        let reason_code = client.publish("foo", b"hello", 1, false).unwrap();
+        assert_eq!(reason_code, ReasonCode::Success);
+    }
+
+    #[test]
+    fn test_keep_alive_missing_pingresp() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            while !matches!(decoder.read_packet(&mut stream), Ok(Packet::Pingreq(_))) {}
+            // never answer, neither the SUBSCRIBE nor the PINGREQ
+            thread::sleep(Duration::from_secs(2));
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        let options = ConnectOptions::new()
+            .with_keep_alive(1)
+            .with_timeout(Duration::from_millis(100));
+        client.connect(&options).unwrap();
+        assert!(matches!(
+            client.subscribe("foo", 0).unwrap_err(),
+            Error::Timeout
+        ));
+        assert!(client.publish("foo", b"hello", 0, false).is_err());
+    }
+
+    // accepts one connection after the other and hands each to its server
+    fn serve_reconnects(servers: Vec<Box<dyn FnOnce(TcpStream, Decoder) + Send>>) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for server in servers {
+                let (stream, _) = listener.accept().unwrap();
+                server(stream, Decoder::default());
+            }
+        });
+        TcpStream::connect(addr).unwrap()
+    }
+
+    fn reconnect_options() -> ConnectOptions {
+        let policy = ReconnectPolicy::new()
+            .with_initial_delay(Duration::from_millis(20))
+            .with_maximum_attempts(2);
+        ConnectOptions::new().with_reconnect(policy)
+    }
+
+    #[test]
+    fn test_reconnect_resends_in_flight() {
+        let stream = serve_reconnects(vec![
+            Box::new(|mut stream, mut decoder| {
+                accept_connect(&mut stream, &mut decoder);
+                let packet = decoder.read_packet(&mut stream).unwrap();
+                assert!(matches!(packet, Packet::Publish(_)));
+                // dropping the stream loses the connection before the PUBACK
+            }),
+            Box::new(|mut stream, mut decoder| {
+                match decoder.read_packet(&mut stream).unwrap() {
+                    Packet::Connect(connect) => assert!(!connect.clean_start()),
+                    packet => panic!("unexpected {packet:?}"),
+                }
+                let connack = Connack::new(true, ReasonCode::Success, Properties::default());
+                stream.write_all(&connack.as_bytes()).unwrap();
+                let publish = match decoder.read_packet(&mut stream).unwrap() {
+                    Packet::Publish(publish) => publish,
+                    packet => panic!("unexpected {packet:?}"),
+                };
+                assert!(publish.dup());
+                let puback = Puback::new(
+                    publish.packet_identifier().unwrap(),
+                    ReasonCode::Success,
+                    Properties::default(),
+                );
+                stream.write_all(&puback.as_bytes()).unwrap();
+                thread::sleep(Duration::from_millis(500));
+            }),
+        ]);
+        let client = Client::from_stream(String::from("foo"), stream);
+        let events = client.connection_events();
+        client.connect(&reconnect_options()).unwrap();
+        let reason_code = client.publish("foo", b"hello", 1, false).unwrap();
+        assert_eq!(reason_code, ReasonCode::Success);
+        let events: Vec<ConnectionEvent> = events.try_iter().collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[1], ConnectionEvent::ConnectionLost { .. }));
+        assert!(matches!(
+            events[2],
+            ConnectionEvent::Reconnecting { attempt: 1, .. }
+        ));
+        assert_eq!(
+            events[3],
+            ConnectionEvent::Connected {
+                session_present: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_reconnect_resubscribes() {
+        let stream = serve_reconnects(vec![
+            Box::new(|mut stream, mut decoder| {
+                accept_connect(&mut stream, &mut decoder);
+                accept_subscribe(&mut stream, &mut decoder, ReasonCode::GrantedQoS1);
+            }),
+            Box::new(|mut stream, mut decoder| {
+                // a new session, so the subscription has to be made again
+                accept_connect(&mut stream, &mut decoder);
+                accept_subscribe(&mut stream, &mut decoder, ReasonCode::GrantedQoS1);
+                let publish = Publish::new("foo", None, vec![7], 0, false, Properties::default());
+                stream.write_all(&publish.as_bytes()).unwrap();
+                thread::sleep(Duration::from_millis(500));
+            }),
+        ]);
+        let client = Client::from_stream(String::from("foo"), stream);
+        client.connect(&reconnect_options()).unwrap();
+        let subscription = client.subscribe("foo", 1).unwrap();
+        let message = subscription.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.payload(), [7]);
     }
 
-    fn send(&mut self, packet: &impl ControlPacket) {
-        self.stream.write_all(packet.as_bytes().as_slice()).unwrap();
+    #[test]
+    fn test_reconnect_gives_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server_stream, _) = listener.accept().unwrap();
+        let mut decoder = Decoder::default();
+        let client = Client::from_stream(String::from("foo"), stream);
+        let events = client.connection_events();
+        let connecting = thread::spawn(move || {
+            accept_connect(&mut server_stream, &mut decoder);
+            decoder.read_packet(&mut server_stream).unwrap();
+        });
+        client.connect(&reconnect_options()).unwrap();
+        let publisher = client.clone();
+        let publishing = thread::spawn(move || publisher.publish("foo", b"hello", 1, false));
+        connecting.join().unwrap();
+        // nothing accepts the reconnects any more
+        drop(listener);
+        assert!(publishing.join().unwrap().is_err());
+        assert_eq!(
+            events.try_iter().last(),
+            Some(ConnectionEvent::ReconnectFailed)
+        );
     }
 
-    pub fn connect(&self) {
-        //TODO send CONNECT and wait for CONNACK
+    #[test]
+    fn test_disconnect() {
+        let (sender, receiver) = mpsc::channel();
+        let stream = serve(move |mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            sender
+                .send(decoder.read_packet(&mut stream).unwrap())
+                .unwrap();
+            // the client closes the connection after the DISCONNECT
+            assert!(decoder.read_packet(&mut stream).is_err());
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        let events = client.connection_events();
+        let options = reconnect_options().with_session_expiry_interval(60);
+        client.connect(&options).unwrap();
+        let options = DisconnectOptions::new()
+            .with_reason_code(ReasonCode::DisconnectWithWillMessage)
+            .with_session_expiry_interval(0)
+            .with_reason_string("bye");
+        client.disconnect_with(&options).unwrap();
+        let disconnect = match receiver.recv().unwrap() {
+            Packet::Disconnect(disconnect) => disconnect,
+            packet => panic!("unexpected {packet:?}"),
+        };
+        assert_eq!(
+            disconnect.reason_code(),
+            ReasonCode::DisconnectWithWillMessage
+        );
+        assert_eq!(disconnect.properties().values().len(), 2);
+        assert!(client.publish("foo", b"hello", 0, false).is_err());
+        // the event loop is gone without trying to reconnect
+        let events: Vec<ConnectionEvent> = events.try_iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], ConnectionEvent::Disconnected);
     }
 
-    pub fn publish(&self, _topic: &str, _payload: &str, _qos: u8, _retain: bool) {
-        //TODO send PUBLISH and wait for PUBACK
-        //TODO handle PUBLISH with DUP if no PUBACK comes
+    #[test]
+    fn test_disconnect_after_server_session_expiry_interval() {
+        // the server cuts the 60 seconds asked for down to 0
+        let properties =
+            Properties::new(vec![Property::SessionExpiryInterval(FourByteInt::new(0))]);
+        let stream = serve_connack(Some(Connack::new(false, ReasonCode::Success, properties)));
+        let client = Client::from_stream(String::from("foo"), stream);
+        client
+            .connect(&ConnectOptions::new().with_session_expiry_interval(60))
+            .unwrap();
+        let options = DisconnectOptions::new().with_session_expiry_interval(60);
+        assert!(matches!(
+            client.disconnect_with(&options).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
     }
 
-    pub fn subscribe(&self, _topic: &str) {
-        //TODO send SUBSCRIBE and wait for SUBACK
-        //TODO spin off thread for each subscription handling incoming PUBLISH/outgoing PUBACK
-        //TODO eventually take callback here but for now just echo
+    #[test]
+    fn test_disconnect_while_reconnecting() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        let events = client.connection_events();
+        let policy = ReconnectPolicy::new().with_initial_delay(Duration::from_secs(60));
+        client
+            .connect(&ConnectOptions::new().with_reconnect(policy))
+            .unwrap();
+        while !matches!(events.recv().unwrap(), ConnectionEvent::Reconnecting { .. }) {}
+        let started = Instant::now();
+        // whether the DISCONNECT still gets written to the dead stream is up to the OS, but
+        // the reconnect stops waiting out its delay
+        let _ = client.disconnect();
+        assert!(started.elapsed() < Duration::from_secs(10));
     }
 
-    pub fn unsubscribe(&self, _topic: &str) {
-        //TODO send UNSUBSCRIBE and wait for UNSUBACK
-        //TODO kill subscription thread
+    #[test]
+    fn test_server_disconnect() {
+        let stream = serve(|mut stream, mut decoder| {
+            accept_connect(&mut stream, &mut decoder);
+            decoder.read_packet(&mut stream).unwrap();
+            let disconnect = Disconnect::new(ReasonCode::SessionTakenOver, Properties::default());
+            stream.write_all(&disconnect.as_bytes()).unwrap();
+            thread::sleep(Duration::from_millis(500));
+        });
+        let client = Client::from_stream(String::from("foo"), stream);
+        let events = client.connection_events();
+        client.connect(&reconnect_options()).unwrap();
+        assert!(matches!(
+            client.subscribe("foo", 0).unwrap_err(),
+            Error::Disconnected(ReasonCode::SessionTakenOver)
+        ));
+        thread::sleep(Duration::from_millis(100));
+        // another connection has the session now, so there is no reconnect
+        assert!(matches!(
+            events.try_iter().last(),
+            Some(ConnectionEvent::ConnectionLost { .. })
+        ));
     }
 
-    pub fn disconnect(&self) {
-        //TODO send DISCONNECT to server
-        //TODO join any SUBSCRIBE threads
-        //TODO close stream
+    #[test]
+    fn test_client_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Client>();
     }
 }
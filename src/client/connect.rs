@@ -0,0 +1,533 @@
+use crate::client::reconnect::ReconnectPolicy;
+use crate::common::{Bytes, FourByteInt, TwoByteInt, UTF8StringPair, MAX_ENCODED_LEN};
+use crate::control_packet::connack::Connack;
+use crate::control_packet::connect::Connect;
+use crate::control_packet::{ControlPacket, Packet};
+use crate::decoder::Decoder;
+use crate::error::Error;
+use crate::payload::connect::{ConnectPayload, Will};
+use crate::property::{Properties, Property};
+use crate::reason_code::ReasonCode;
+use crate::topic::is_valid_topic_name;
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::Duration;
+
+const DEFAULT_KEEP_ALIVE: u16 = 60;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the client asks for in its CONNECT packet.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    keep_alive: u16,
+    clean_start: bool,
+    session_expiry_interval: u32,
+    user_name: Option<String>,
+    password: Option<Bytes>,
+    will: Option<Will>,
+    timeout: Duration,
+    receive_maximum: Option<u16>,
+    maximum_packet_size: Option<u32>,
+    user_properties: Vec<(String, String)>,
+    reconnect: Option<ReconnectPolicy>,
+}
+
+impl ConnectOptions {
+    pub fn new() -> Self {
+        ConnectOptions {
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            clean_start: true,
+            session_expiry_interval: 0,
+            user_name: None,
+            password: None,
+            will: None,
+            timeout: DEFAULT_TIMEOUT,
+            receive_maximum: None,
+            maximum_packet_size: None,
+            user_properties: Vec::new(),
+            reconnect: None,
+        }
+    }
+
+    /// Seconds between control packets before the server may consider the connection dead;
+    /// 0 turns keep alive off.
+    pub fn with_keep_alive(mut self, keep_alive: u16) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    pub fn with_clean_start(mut self, clean_start: bool) -> Self {
+        self.clean_start = clean_start;
+        self
+    }
+
+    /// Seconds the server keeps the session after the connection closes.
+    pub fn with_session_expiry_interval(mut self, session_expiry_interval: u32) -> Self {
+        self.session_expiry_interval = session_expiry_interval;
+        self
+    }
+
+    pub fn with_user_name(mut self, user_name: &str) -> Self {
+        self.user_name = Some(String::from(user_name));
+        self
+    }
+
+    pub fn with_password(mut self, password: &[u8]) -> Self {
+        self.password = Some(Vec::from(password));
+        self
+    }
+
+    /// Message the server publishes on our behalf if the connection is lost.
+    pub fn with_will(mut self, topic: &str, payload: &[u8], qos: u8, retain: bool) -> Self {
+        let will = Will::new(
+            topic,
+            Vec::from(payload),
+            qos,
+            retain,
+            Properties::default(),
+        );
+        self.will = Some(will);
+        self
+    }
+
+    /// How long to wait for the CONNACK, and for the PINGRESP before the connection is
+    /// considered dead.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many QoS 1 and 2 messages the server may send before we acknowledge them; must
+    /// not be 0.
+    pub fn with_receive_maximum(mut self, receive_maximum: u16) -> Self {
+        self.receive_maximum = Some(receive_maximum);
+        self
+    }
+
+    /// The largest packet we accept; the server must not send larger ones, and the client
+    /// closes the connection if it does. Must not be 0.
+    pub fn with_maximum_packet_size(mut self, maximum_packet_size: u32) -> Self {
+        self.maximum_packet_size = Some(maximum_packet_size);
+        self
+    }
+
+    /// Adds a user property to the CONNECT packet; the same name may be used more than once.
+    pub fn with_user_property(mut self, name: &str, value: &str) -> Self {
+        self.user_properties
+            .push((String::from(name), String::from(value)));
+        self
+    }
+
+    /// Reconnects when the connection is lost, with Clean Start set to 0 so the server
+    /// resumes the session. Without a policy the client stays disconnected.
+    pub fn with_reconnect(mut self, reconnect: ReconnectPolicy) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
+    pub fn keep_alive(&self) -> u16 {
+        self.keep_alive
+    }
+
+    pub fn clean_start(&self) -> bool {
+        self.clean_start
+    }
+
+    pub fn session_expiry_interval(&self) -> u32 {
+        self.session_expiry_interval
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn receive_maximum(&self) -> Option<u16> {
+        self.receive_maximum
+    }
+
+    pub fn maximum_packet_size(&self) -> Option<u32> {
+        self.maximum_packet_size
+    }
+
+    pub fn user_properties(&self) -> &[(String, String)] {
+        &self.user_properties
+    }
+
+    pub fn reconnect(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect.as_ref()
+    }
+
+    /// The CONNECT packet, once every string and binary value is known to fit in it.
+    pub(crate) fn packet(&self, client_id: &str) -> Result<Connect, Error> {
+        if client_id.len() > MAX_ENCODED_LEN {
+            return Err(Error::InvalidArgument("client identifier too long"));
+        }
+        if let Some(will) = &self.will {
+            if will.qos() > 2 {
+                return Err(Error::InvalidArgument("will qos must be 0, 1 or 2"));
+            }
+            if !is_valid_topic_name(will.topic()) {
+                return Err(Error::InvalidArgument("invalid will topic name"));
+            }
+            if will.payload().len() > MAX_ENCODED_LEN {
+                return Err(Error::InvalidArgument("will payload too long"));
+            }
+        }
+        if self
+            .user_name
+            .as_ref()
+            .is_some_and(|user_name| user_name.len() > MAX_ENCODED_LEN)
+        {
+            return Err(Error::InvalidArgument("user name too long"));
+        }
+        if self
+            .password
+            .as_ref()
+            .is_some_and(|password| password.len() > MAX_ENCODED_LEN)
+        {
+            return Err(Error::InvalidArgument("password too long"));
+        }
+        if self
+            .user_properties
+            .iter()
+            .any(|(name, value)| name.len() > MAX_ENCODED_LEN || value.len() > MAX_ENCODED_LEN)
+        {
+            return Err(Error::InvalidArgument("user property too long"));
+        }
+        let mut payload = ConnectPayload::new(client_id);
+        if let Some(will) = &self.will {
+            payload = payload.with_will(will.clone());
+        }
+        if let Some(user_name) = &self.user_name {
+            payload = payload.with_user_name(user_name);
+        }
+        if let Some(password) = &self.password {
+            payload = payload.with_password(password.clone());
+        }
+        let mut properties = Properties::default();
+        if self.session_expiry_interval != 0 {
+            properties.push(Property::SessionExpiryInterval(FourByteInt::new(
+                self.session_expiry_interval,
+            )));
+        }
+        if let Some(receive_maximum) = self.receive_maximum {
+            properties.push(Property::ReceiveMaximum(TwoByteInt::new(receive_maximum)));
+        }
+        if let Some(maximum_packet_size) = self.maximum_packet_size {
+            properties.push(Property::MaximumPacketSize(FourByteInt::new(
+                maximum_packet_size,
+            )));
+        }
+        for (name, value) in &self.user_properties {
+            properties.push(Property::UserProperty(UTF8StringPair::new(name, value)));
+        }
+        Ok(Connect::new(
+            payload,
+            self.clean_start,
+            self.keep_alive,
+            properties,
+        ))
+    }
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions::new()
+    }
+}
+
+/// Sends CONNECT on `stream` and waits up to the options' timeout for the CONNACK. A CONNACK
+/// with a failure reason code is returned as `Error::ConnectionRefused`. The decoder holds
+/// whatever the server sent after the CONNACK.
+pub(crate) fn handshake(
+    stream: &mut TcpStream,
+    options: &ConnectOptions,
+    client_id: &str,
+) -> Result<(ConnectResult, Decoder), Error> {
+    if options.receive_maximum == Some(0) {
+        return Err(Error::InvalidArgument("receive maximum must not be 0"));
+    }
+    let mut decoder = match options.maximum_packet_size {
+        Some(0) => return Err(Error::InvalidArgument("maximum packet size must not be 0")),
+        Some(maximum_packet_size) => Decoder::new(maximum_packet_size as usize),
+        None => Decoder::default(),
+    };
+    stream.write_all(&options.packet(client_id)?.as_bytes())?;
+    stream.set_read_timeout(Some(options.timeout()))?;
+    let packet = decoder.read_packet(stream);
+    stream.set_read_timeout(None)?;
+    let connack = match packet? {
+        Packet::Connack(connack) => connack,
+        packet => {
+            return Err(Error::ProtocolError {
+                packet_type: Some(packet.packet_type()),
+                offset: None,
+                message: "expected CONNACK",
+            })
+        }
+    };
+    if connack.reason_code().is_failure() {
+        return Err(Error::ConnectionRefused(connack.reason_code()));
+    }
+    Ok((ConnectResult::from_connack(&connack), decoder))
+}
+
+/// The server's answer to a successful CONNECT. Properties the server left out are reported
+/// with the defaults the specification gives them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectResult {
+    session_present: bool,
+    reason_code: ReasonCode,
+    session_expiry_interval: Option<u32>,
+    assigned_client_identifier: Option<String>,
+    server_keep_alive: Option<u16>,
+    receive_maximum: u16,
+    maximum_qos: u8,
+    retain_available: bool,
+    maximum_packet_size: Option<u32>,
+    topic_alias_maximum: u16,
+    wildcard_subscription_available: bool,
+    subscription_identifiers_available: bool,
+    shared_subscription_available: bool,
+    reason_string: Option<String>,
+    response_information: Option<String>,
+    server_reference: Option<String>,
+    user_properties: Vec<(String, String)>,
+}
+
+impl ConnectResult {
+    pub(crate) fn from_connack(connack: &Connack) -> Self {
+        let mut result = ConnectResult {
+            session_present: connack.session_present(),
+            reason_code: connack.reason_code(),
+            session_expiry_interval: None,
+            assigned_client_identifier: None,
+            server_keep_alive: None,
+            receive_maximum: u16::MAX,
+            maximum_qos: 2,
+            retain_available: true,
+            maximum_packet_size: None,
+            topic_alias_maximum: 0,
+            wildcard_subscription_available: true,
+            subscription_identifiers_available: true,
+            shared_subscription_available: true,
+            reason_string: None,
+            response_information: None,
+            server_reference: None,
+            user_properties: Vec::new(),
+        };
+        for property in connack.properties().values() {
+            match property {
+                Property::SessionExpiryInterval(value) => {
+                    result.session_expiry_interval = Some(value.value())
+                }
+                Property::AssignedClientIdentifier(value) => {
+                    result.assigned_client_identifier = Some(String::from(value.value()))
+                }
+                Property::ServerKeepAlive(value) => result.server_keep_alive = Some(value.value()),
+                Property::ReceiveMaximum(value) => result.receive_maximum = value.value(),
+                Property::MaximumQoS(value) => result.maximum_qos = *value,
+                Property::RetainAvailable(value) => result.retain_available = *value == 1,
+                Property::MaximumPacketSize(value) => {
+                    result.maximum_packet_size = Some(value.value())
+                }
+                Property::TopicAliasMaximum(value) => result.topic_alias_maximum = value.value(),
+                Property::WildcardSubscriptionAvailable(value) => {
+                    result.wildcard_subscription_available = *value == 1
+                }
+                Property::SubscriptionIdentifierAvailable(value) => {
+                    result.subscription_identifiers_available = *value == 1
+                }
+                Property::SharedSubscriptionAvailable(value) => {
+                    result.shared_subscription_available = *value == 1
+                }
+                Property::ReasonString(value) => {
+                    result.reason_string = Some(String::from(value.value()))
+                }
+                Property::ResponseInformation(value) => {
+                    result.response_information = Some(String::from(value.value()))
+                }
+                Property::ServerReference(value) => {
+                    result.server_reference = Some(String::from(value.value()))
+                }
+                Property::UserProperty(value) => {
+                    let (key, value) = value.value();
+                    result
+                        .user_properties
+                        .push((String::from(key), String::from(value)));
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    pub fn session_present(&self) -> bool {
+        self.session_present
+    }
+
+    pub fn reason_code(&self) -> ReasonCode {
+        self.reason_code
+    }
+
+    /// Overrides the session expiry interval the client asked for.
+    pub fn session_expiry_interval(&self) -> Option<u32> {
+        self.session_expiry_interval
+    }
+
+    /// Set when the client connected with an empty client identifier.
+    pub fn assigned_client_identifier(&self) -> Option<&str> {
+        self.assigned_client_identifier.as_deref()
+    }
+
+    /// Overrides the keep alive the client asked for.
+    pub fn server_keep_alive(&self) -> Option<u16> {
+        self.server_keep_alive
+    }
+
+    pub fn receive_maximum(&self) -> u16 {
+        self.receive_maximum
+    }
+
+    pub fn maximum_qos(&self) -> u8 {
+        self.maximum_qos
+    }
+
+    pub fn retain_available(&self) -> bool {
+        self.retain_available
+    }
+
+    pub fn maximum_packet_size(&self) -> Option<u32> {
+        self.maximum_packet_size
+    }
+
+    pub fn topic_alias_maximum(&self) -> u16 {
+        self.topic_alias_maximum
+    }
+
+    pub fn wildcard_subscription_available(&self) -> bool {
+        self.wildcard_subscription_available
+    }
+
+    pub fn subscription_identifiers_available(&self) -> bool {
+        self.subscription_identifiers_available
+    }
+
+    pub fn shared_subscription_available(&self) -> bool {
+        self.shared_subscription_available
+    }
+
+    pub fn reason_string(&self) -> Option<&str> {
+        self.reason_string.as_deref()
+    }
+
+    pub fn response_information(&self) -> Option<&str> {
+        self.response_information.as_deref()
+    }
+
+    pub fn server_reference(&self) -> Option<&str> {
+        self.server_reference.as_deref()
+    }
+
+    pub fn user_properties(&self) -> &[(String, String)] {
+        &self.user_properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::connect::{ConnectOptions, ConnectResult};
+    use crate::common::{FourByteInt, TwoByteInt, UTF8String, UTF8StringPair};
+    use crate::control_packet::connack::Connack;
+    use crate::error::Error;
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_packet() {
+        let options = ConnectOptions::new()
+            .with_keep_alive(30)
+            .with_clean_start(false)
+            .with_session_expiry_interval(120)
+            .with_user_name("user")
+            .with_password(b"secret")
+            .with_will("foo/status", b"offline", 1, true)
+            .with_receive_maximum(10)
+            .with_maximum_packet_size(1024)
+            .with_user_property("region", "eu");
+        let packet = options.packet("foo").unwrap();
+        assert_eq!(packet.client_id(), "foo");
+        assert_eq!(packet.keep_alive(), 30);
+        assert!(!packet.clean_start());
+        assert_eq!(
+            packet.properties().values(),
+            &[
+                Property::SessionExpiryInterval(FourByteInt::new(120)),
+                Property::ReceiveMaximum(TwoByteInt::new(10)),
+                Property::MaximumPacketSize(FourByteInt::new(1024)),
+                Property::UserProperty(UTF8StringPair::new("region", "eu")),
+            ]
+        );
+        assert_eq!(packet.user_name(), Some("user"));
+        assert_eq!(packet.password(), Some(&b"secret"[..]));
+        assert_eq!(packet.will().unwrap().qos(), 1);
+    }
+
+    #[test]
+    fn test_packet_too_long() {
+        let long = "a".repeat(65_536);
+        assert!(ConnectOptions::new().packet(&long).is_err());
+        let options = ConnectOptions::new().with_user_name(&long);
+        assert!(options.packet("foo").is_err());
+        let options = ConnectOptions::new().with_password(long.as_bytes());
+        assert!(options.packet("foo").is_err());
+        let options = ConnectOptions::new().with_will(&long, b"offline", 1, false);
+        assert!(options.packet("foo").is_err());
+        let options = ConnectOptions::new().with_will("foo/status", long.as_bytes(), 1, false);
+        assert!(options.packet("foo").is_err());
+        let options = ConnectOptions::new().with_user_property("region", &long);
+        assert!(options.packet("foo").is_err());
+        let options = ConnectOptions::new().with_user_name(&long[1..]);
+        assert!(options.packet("foo").is_ok());
+    }
+
+    #[test]
+    fn test_will_qos() {
+        let options = ConnectOptions::new().with_will("foo/status", b"offline", 3, false);
+        assert!(matches!(
+            options.packet("foo"),
+            Err(Error::InvalidArgument(_))
+        ));
+        let options = ConnectOptions::new().with_will("foo/status", b"offline", 2, false);
+        assert!(options.packet("foo").is_ok());
+    }
+
+    #[test]
+    fn test_from_connack_defaults() {
+        let connack = Connack::new(false, ReasonCode::Success, Properties::default());
+        let result = ConnectResult::from_connack(&connack);
+        assert!(!result.session_present());
+        assert_eq!(result.reason_code(), ReasonCode::Success);
+        assert_eq!(result.receive_maximum(), u16::MAX);
+        assert_eq!(result.maximum_qos(), 2);
+        assert!(result.retain_available());
+        assert_eq!(result.assigned_client_identifier(), None);
+    }
+
+    #[test]
+    fn test_from_connack_properties() {
+        let properties = Properties::new(vec![
+            Property::AssignedClientIdentifier(UTF8String::new("auto-1")),
+            Property::ServerKeepAlive(TwoByteInt::new(20)),
+            Property::MaximumQoS(1),
+            Property::RetainAvailable(0),
+        ]);
+        let connack = Connack::new(true, ReasonCode::Success, properties);
+        let result = ConnectResult::from_connack(&connack);
+        assert!(result.session_present());
+        assert_eq!(result.assigned_client_identifier(), Some("auto-1"));
+        assert_eq!(result.server_keep_alive(), Some(20));
+        assert_eq!(result.maximum_qos(), 1);
+        assert!(!result.retain_available());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::common::{FourByteInt, UTF8String, MAX_ENCODED_LEN};
+use crate::control_packet::disconnect::Disconnect;
+use crate::control_packet::PacketType;
+use crate::error::Error;
+use crate::property::{Properties, Property};
+use crate::reason_code::ReasonCode;
+
+/// What the client says in its DISCONNECT packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisconnectOptions {
+    reason_code: ReasonCode,
+    session_expiry_interval: Option<u32>,
+    reason_string: Option<String>,
+}
+
+impl DisconnectOptions {
+    /// A normal disconnection, after which the server discards the will message.
+    pub fn new() -> Self {
+        DisconnectOptions {
+            reason_code: ReasonCode::NORMAL_DISCONNECTION,
+            session_expiry_interval: None,
+            reason_string: None,
+        }
+    }
+
+    /// `DisconnectWithWillMessage` asks the server to publish the will anyway.
+    pub fn with_reason_code(mut self, reason_code: ReasonCode) -> Self {
+        self.reason_code = reason_code;
+        self
+    }
+
+    /// Replaces the session expiry interval sent in the CONNECT. It cannot be set when the
+    /// CONNECT asked for 0, as the server has already discarded the session by then.
+    pub fn with_session_expiry_interval(mut self, session_expiry_interval: u32) -> Self {
+        self.session_expiry_interval = Some(session_expiry_interval);
+        self
+    }
+
+    pub fn with_reason_string(mut self, reason_string: &str) -> Self {
+        self.reason_string = Some(String::from(reason_string));
+        self
+    }
+
+    pub fn reason_code(&self) -> ReasonCode {
+        self.reason_code
+    }
+
+    pub fn session_expiry_interval(&self) -> Option<u32> {
+        self.session_expiry_interval
+    }
+
+    pub fn reason_string(&self) -> Option<&str> {
+        self.reason_string.as_deref()
+    }
+
+    /// The DISCONNECT packet; `current_session_expiry_interval` is the one in force, which is
+    /// the CONNACK's if the server replaced the one the CONNECT sent.
+    pub(crate) fn packet(&self, current_session_expiry_interval: u32) -> Result<Disconnect, Error> {
+        if !self.reason_code.is_valid_for(&PacketType::DISCONNECT) {
+            return Err(Error::InvalidArgument(
+                "reason code is not allowed in DISCONNECT",
+            ));
+        }
+        let mut properties = Properties::default();
+        if let Some(session_expiry_interval) = self.session_expiry_interval {
+            if current_session_expiry_interval == 0 && session_expiry_interval != 0 {
+                return Err(Error::InvalidArgument(
+                    "session expiry interval cannot be set after connecting with 0",
+                ));
+            }
+            properties.push(Property::SessionExpiryInterval(FourByteInt::new(
+                session_expiry_interval,
+            )));
+        }
+        if let Some(reason_string) = &self.reason_string {
+            if reason_string.len() > MAX_ENCODED_LEN {
+                return Err(Error::InvalidArgument("reason string too long"));
+            }
+            properties.push(Property::ReasonString(UTF8String::new(reason_string)));
+        }
+        Ok(Disconnect::new(self.reason_code, properties))
+    }
+}
+
+impl Default for DisconnectOptions {
+    fn default() -> Self {
+        DisconnectOptions::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::disconnect::DisconnectOptions;
+    use crate::common::{FourByteInt, UTF8String};
+    use crate::error::Error;
+    use crate::property::Property;
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_packet() {
+        let options = DisconnectOptions::new()
+            .with_reason_code(ReasonCode::DisconnectWithWillMessage)
+            .with_session_expiry_interval(0)
+            .with_reason_string("shutting down");
+        let packet = options.packet(60).unwrap();
+        assert_eq!(packet.reason_code(), ReasonCode::DisconnectWithWillMessage);
+        assert_eq!(
+            packet.properties().values(),
+            &[
+                Property::SessionExpiryInterval(FourByteInt::new(0)),
+                Property::ReasonString(UTF8String::new("shutting down")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_packet_invalid() {
+        let options = DisconnectOptions::new().with_reason_code(ReasonCode::GrantedQoS1);
+        assert!(matches!(
+            options.packet(0).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+        let options = DisconnectOptions::new().with_session_expiry_interval(60);
+        assert!(matches!(
+            options.packet(0).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+        let options = DisconnectOptions::new().with_reason_string(&"a".repeat(65_536));
+        assert!(matches!(
+            options.packet(0).unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+}
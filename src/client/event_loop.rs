@@ -0,0 +1,761 @@
+use crate::client::connect::{self, ConnectOptions, ConnectResult};
+use crate::client::disconnect::DisconnectOptions;
+use crate::client::keep_alive::{Action, KeepAlive};
+use crate::client::options::ClientOptions;
+use crate::client::reconnect::ConnectionEvent;
+use crate::client::subscription::{Handler, Handlers, Message};
+use crate::common::{encode_variable_length_int, Byte, MAX_REMAINING_LENGTH};
+use crate::control_packet::pingreq::Pingreq;
+use crate::control_packet::puback::Puback;
+use crate::control_packet::pubcomp::Pubcomp;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::pubrec::Pubrec;
+use crate::control_packet::pubrel::Pubrel;
+use crate::control_packet::subscribe::Subscribe;
+use crate::control_packet::unsubscribe::Unsubscribe;
+use crate::control_packet::{ControlPacket, Packet};
+use crate::decoder::Decoder;
+use crate::error::Error;
+use crate::payload::subscribe::{Subscription as SubscriptionRequest, SubscriptionOptions};
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::session::{Acknowledgement, Session};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle, ThreadId};
+use std::time::Instant;
+
+// a request waiting for the server's acknowledgement, keyed by packet identifier
+enum Pending {
+    Publish(Sender<ReasonCode>),
+    // the handler is installed by the event loop itself, so messages sent right after the
+    // SUBACK are not missed
+    Subscribe {
+        topic_filter: String,
+        qos: u8,
+        handler: Handler,
+        sender: Sender<Vec<ReasonCode>>,
+    },
+    Unsubscribe {
+        topic_filter: String,
+        sender: Sender<Vec<ReasonCode>>,
+    },
+    // subscriptions made again because the server started a new session
+    Resubscribe(Vec<String>),
+}
+
+struct Writer {
+    stream: TcpStream,
+    // when the last packet went out, which is what the server's keep alive timer measures
+    last_sent: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    session: Session,
+    pending: HashMap<u16, Pending>,
+}
+
+impl State {
+    // skips identifiers still used by a resumed message or a pending request
+    fn next_packet_identifier(&mut self) -> Result<u16, Error> {
+        for _ in 0..u16::MAX {
+            let packet_identifier = self
+                .session
+                .next_packet_identifier()
+                .ok_or(Error::PacketIdentifiersExhausted)?;
+            if !self.pending.contains_key(&packet_identifier) {
+                return Ok(packet_identifier);
+            }
+        }
+        Err(Error::PacketIdentifiersExhausted)
+    }
+}
+
+// Everything the client handles share with the event loop thread. Application threads
+// register what they wait for and write their packets; the event loop reads every packet
+// and completes those waits.
+pub(crate) struct Shared {
+    client_id: Mutex<String>,
+    // where to reconnect to
+    client_options: ClientOptions,
+    // the options of the last successful connect, reused to reconnect
+    options: Mutex<Option<ConnectOptions>>,
+    // the session expiry interval in force, which is the server's if its CONNACK set one
+    session_expiry_interval: Mutex<u32>,
+    // the largest packet the server accepts, if its CONNACK set a limit
+    maximum_packet_size: Mutex<Option<u32>>,
+    writer: Mutex<Writer>,
+    state: Mutex<State>,
+    // why the event loop stopped, for the requests it could not complete
+    error: Mutex<Option<Error>>,
+    // separate from the state so a callback may publish at QoS 0
+    handlers: Mutex<Handlers>,
+    event_loop: Mutex<Option<JoinHandle<()>>>,
+    // set by the event loop itself, so it is known before the first callback runs
+    event_loop_thread: Mutex<Option<ThreadId>>,
+    event_senders: Mutex<Vec<Sender<ConnectionEvent>>>,
+    // set once the application disconnects, which wakes a reconnect waiting out its delay
+    closing: Mutex<bool>,
+    closing_changed: Condvar,
+}
+
+// a panicking callback must not take the whole client down with it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Shared {
+    pub(crate) fn new(client_id: String, stream: TcpStream, client_options: ClientOptions) -> Self {
+        Shared {
+            client_id: Mutex::new(client_id),
+            client_options,
+            options: Mutex::new(None),
+            session_expiry_interval: Mutex::new(0),
+            maximum_packet_size: Mutex::new(None),
+            writer: Mutex::new(Writer {
+                stream,
+                last_sent: Instant::now(),
+            }),
+            state: Mutex::new(State::default()),
+            error: Mutex::new(None),
+            handlers: Mutex::new(Handlers::default()),
+            event_loop: Mutex::new(None),
+            event_loop_thread: Mutex::new(None),
+            event_senders: Mutex::new(Vec::new()),
+            closing: Mutex::new(false),
+            closing_changed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn client_id(&self) -> String {
+        lock(&self.client_id).clone()
+    }
+
+    pub(crate) fn set_client_id(&self, client_id: &str) {
+        *lock(&self.client_id) = String::from(client_id);
+    }
+
+    pub(crate) fn try_clone_stream(&self) -> Result<TcpStream, Error> {
+        Ok(lock(&self.writer).stream.try_clone()?)
+    }
+
+    pub(crate) fn connection_events(&self) -> Receiver<ConnectionEvent> {
+        let (sender, receiver) = mpsc::channel();
+        lock(&self.event_senders).push(sender);
+        receiver
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        // receivers the application dropped are forgotten
+        lock(&self.event_senders).retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
+    fn reconnects(&self) -> bool {
+        !self.is_closing()
+            && lock(&self.options)
+                .as_ref()
+                .is_some_and(|options| options.reconnect().is_some())
+    }
+
+    fn is_closing(&self) -> bool {
+        *lock(&self.closing)
+    }
+
+    /// Sends the DISCONNECT, closes the connection and waits for the event loop to end, so
+    /// no reconnect follows. Called from a callback, the event loop ends once the callback
+    /// returns instead.
+    pub(crate) fn disconnect(&self, options: &DisconnectOptions) -> Result<(), Error> {
+        let session_expiry_interval = *lock(&self.session_expiry_interval);
+        let disconnect = options.packet(session_expiry_interval)?;
+        *lock(&self.closing) = true;
+        self.closing_changed.notify_all();
+        let result = {
+            let mut writer = lock(&self.writer);
+            let result = writer
+                .stream
+                .write_all(&disconnect.as_bytes())
+                .and_then(|_| writer.stream.flush());
+            // wakes the event loop, which reads the end of the stream
+            let _ = writer.stream.shutdown(Shutdown::Both);
+            result
+        };
+        let event_loop = lock(&self.event_loop).take();
+        if let Some(event_loop) = event_loop {
+            if event_loop.thread().id() != thread::current().id() {
+                let _ = event_loop.join();
+            }
+        }
+        // a callback holds the handlers, the event loop closes them once it returns
+        if !self.on_event_loop() {
+            lock(&self.handlers).close_channels();
+        }
+        self.emit(ConnectionEvent::Disconnected);
+        Ok(result?)
+    }
+
+    /// Whether this is the event loop thread, which runs the callbacks.
+    pub(crate) fn on_event_loop(&self) -> bool {
+        *lock(&self.event_loop_thread) == Some(thread::current().id())
+    }
+
+    /// Whether an event loop is serving the connection, or reconnecting it.
+    pub(crate) fn is_running(&self) -> bool {
+        lock(&self.event_loop)
+            .as_ref()
+            .is_some_and(|event_loop| !event_loop.is_finished())
+    }
+
+    pub(crate) fn set_event_loop(&self, event_loop: JoinHandle<()>) {
+        *lock(&self.event_loop) = Some(event_loop);
+    }
+
+    pub(crate) fn send(&self, packet: &impl ControlPacket) -> Result<(), Error> {
+        self.send_bytes(&packet.as_bytes())
+    }
+
+    fn send_bytes(&self, bytes: &[Byte]) -> Result<(), Error> {
+        let mut writer = lock(&self.writer);
+        writer.stream.write_all(bytes)?;
+        writer.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn last_sent(&self) -> Instant {
+        lock(&self.writer).last_sent
+    }
+
+    /// The error for a request the event loop stopped before it was answered.
+    pub(crate) fn connection_error(&self) -> Error {
+        match &*lock(&self.error) {
+            Some(Error::Timeout) => Error::Timeout,
+            Some(Error::Disconnected(reason_code)) => Error::Disconnected(*reason_code),
+            Some(Error::Io(error)) => Error::Io(error.kind().into()),
+            _ => Error::Io(io::ErrorKind::ConnectionAborted.into()),
+        }
+    }
+
+    /// Takes over a connection the server accepted: resumes the session, subscribes again if
+    /// the server has no session for us, and returns the keep alive to maintain.
+    pub(crate) fn established(
+        &self,
+        options: &ConnectOptions,
+        result: &ConnectResult,
+    ) -> Result<KeepAlive, Error> {
+        *lock(&self.options) = Some(options.clone());
+        *lock(&self.session_expiry_interval) = result
+            .session_expiry_interval()
+            .unwrap_or(options.session_expiry_interval());
+        *lock(&self.maximum_packet_size) = result.maximum_packet_size();
+        if let Some(client_id) = result.assigned_client_identifier() {
+            self.set_client_id(client_id);
+        }
+        self.resume_session(result.session_present())?;
+        if !result.session_present() {
+            self.resubscribe()?;
+        }
+        self.emit(ConnectionEvent::Connected {
+            session_present: result.session_present(),
+        });
+        // the server may override the keep alive we asked for
+        Ok(KeepAlive::new(
+            result.server_keep_alive().unwrap_or(options.keep_alive()),
+            options.timeout(),
+        ))
+    }
+
+    /// Resends everything in flight if the server kept the session, or forgets it otherwise.
+    fn resume_session(&self, session_present: bool) -> Result<(), Error> {
+        let retransmissions = {
+            let mut state = lock(&self.state);
+            if !session_present {
+                state.session.clear();
+                // those messages are lost with the old session
+                state
+                    .pending
+                    .retain(|_, pending| !matches!(pending, Pending::Publish(_)));
+            }
+            state.session.retransmissions()
+        };
+        for bytes in retransmissions {
+            self.send_bytes(&bytes)?;
+        }
+        Ok(())
+    }
+
+    fn resubscribe(&self) -> Result<(), Error> {
+        let subscriptions = lock(&self.handlers).subscriptions();
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+        let subscribe = {
+            let mut state = lock(&self.state);
+            let packet_identifier = state.next_packet_identifier()?;
+            let topic_filters = subscriptions
+                .iter()
+                .map(|(topic_filter, _)| topic_filter.clone())
+                .collect();
+            state
+                .pending
+                .insert(packet_identifier, Pending::Resubscribe(topic_filters));
+            let requests = subscriptions
+                .iter()
+                .map(|(topic_filter, qos)| {
+                    SubscriptionRequest::new(topic_filter, SubscriptionOptions::new(*qos))
+                })
+                .collect();
+            Subscribe::new(packet_identifier, requests, Properties::default())
+        };
+        self.send(&subscribe)
+    }
+
+    // Tries to connect again as the policy allows, and returns the new connection for the
+    // event loop to read from, or nothing once the policy gives up.
+    fn reconnect(&self) -> Option<(TcpStream, Decoder, KeepAlive)> {
+        let options = lock(&self.options).clone()?.with_clean_start(false);
+        let policy = options.reconnect()?.clone();
+        let mut attempt = 1;
+        while policy.allows(attempt) {
+            let delay = policy.delay(attempt);
+            self.emit(ConnectionEvent::Reconnecting { attempt, delay });
+            let closing = self
+                .closing_changed
+                .wait_timeout_while(lock(&self.closing), delay, |closing| !*closing)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+            if *closing {
+                return None;
+            }
+            drop(closing);
+            match self.try_reconnect(&options) {
+                Ok(connection) => return Some(connection),
+                Err(error) => self.emit(ConnectionEvent::ConnectionLost {
+                    reason: error.to_string(),
+                }),
+            }
+            attempt += 1;
+        }
+        if !self.is_closing() {
+            self.emit(ConnectionEvent::ReconnectFailed);
+        }
+        None
+    }
+
+    fn try_reconnect(
+        &self,
+        options: &ConnectOptions,
+    ) -> Result<(TcpStream, Decoder, KeepAlive), Error> {
+        let mut stream = self.client_options.open()?;
+        let (result, decoder) = connect::handshake(&mut stream, options, &self.client_id())?;
+        let reader = stream.try_clone()?;
+        {
+            let mut writer = lock(&self.writer);
+            // checked under the writer lock, so a disconnect never writes to the old stream
+            // while the new one is taken over
+            if self.is_closing() {
+                return Err(Error::Io(io::ErrorKind::NotConnected.into()));
+            }
+            *writer = Writer {
+                stream,
+                last_sent: Instant::now(),
+            };
+        }
+        let keep_alive = self.established(options, &result)?;
+        Ok((reader, decoder, keep_alive))
+    }
+
+    /// Sends a QoS 1 or 2 publish; the receiver yields the reason code that completes it.
+    /// Fails unless a packet with this remaining length can be encoded and is no larger
+    /// than the server accepts.
+    pub(crate) fn check_packet_size(&self, remaining_length: usize) -> Result<(), Error> {
+        if remaining_length > MAX_REMAINING_LENGTH {
+            return Err(Error::InvalidArgument("packet too large to encode"));
+        }
+        let remaining_length_len = encode_variable_length_int(
+            u32::try_from(remaining_length).expect("checked against MAX_REMAINING_LENGTH"),
+        )
+        .len();
+        let packet_len = 1 + remaining_length_len + remaining_length;
+        if lock(&self.maximum_packet_size)
+            .is_some_and(|maximum_packet_size| packet_len > maximum_packet_size as usize)
+        {
+            return Err(Error::InvalidArgument(
+                "packet larger than the server's maximum packet size",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn start_publish(
+        &self,
+        topic: &str,
+        payload: &[Byte],
+        qos: u8,
+        retain: bool,
+    ) -> Result<Receiver<ReasonCode>, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let publish = {
+            let mut state = lock(&self.state);
+            let packet_identifier = state.next_packet_identifier()?;
+            let publish = Publish::new(
+                topic,
+                Some(packet_identifier),
+                Vec::from(payload),
+                qos,
+                retain,
+                Properties::default(),
+            );
+            state.session.insert(publish.clone());
+            state
+                .pending
+                .insert(packet_identifier, Pending::Publish(sender));
+            publish
+        };
+        match self.send(&publish) {
+            // it goes out again with the rest of the session once reconnected
+            Err(_) if self.reconnects() => {}
+            result => result?,
+        }
+        Ok(receiver)
+    }
+
+    /// Sends a SUBSCRIBE; the receiver yields the SUBACK reason codes.
+    pub(crate) fn start_subscribe(
+        &self,
+        topic_filter: &str,
+        qos: u8,
+        handler: Handler,
+    ) -> Result<Receiver<Vec<ReasonCode>>, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let subscribe = {
+            let mut state = lock(&self.state);
+            let packet_identifier = state.next_packet_identifier()?;
+            let request = SubscriptionRequest::new(topic_filter, SubscriptionOptions::new(qos));
+            let pending = Pending::Subscribe {
+                topic_filter: String::from(topic_filter),
+                qos,
+                handler,
+                sender,
+            };
+            state.pending.insert(packet_identifier, pending);
+            Subscribe::new(packet_identifier, vec![request], Properties::default())
+        };
+        self.send_request(&subscribe, subscribe.packet_identifier())?;
+        Ok(receiver)
+    }
+
+    /// Sends an UNSUBSCRIBE; the receiver yields the UNSUBACK reason codes.
+    pub(crate) fn start_unsubscribe(
+        &self,
+        topic_filter: &str,
+    ) -> Result<Receiver<Vec<ReasonCode>>, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let unsubscribe = {
+            let mut state = lock(&self.state);
+            let packet_identifier = state.next_packet_identifier()?;
+            let pending = Pending::Unsubscribe {
+                topic_filter: String::from(topic_filter),
+                sender,
+            };
+            state.pending.insert(packet_identifier, pending);
+            Unsubscribe::new(packet_identifier, &[topic_filter], Properties::default())
+        };
+        self.send_request(&unsubscribe, unsubscribe.packet_identifier())?;
+        Ok(receiver)
+    }
+
+    // a request that never reached the server is not waited for
+    fn send_request(
+        &self,
+        packet: &impl ControlPacket,
+        packet_identifier: u16,
+    ) -> Result<(), Error> {
+        let result = self.send(packet);
+        if result.is_err() {
+            lock(&self.state).pending.remove(&packet_identifier);
+        }
+        result
+    }
+
+    fn handle_packet(&self, packet: Packet) -> Result<(), Error> {
+        match packet {
+            Packet::Publish(publish) => self.receive_publish(publish)?,
+            Packet::Pubrel(pubrel) => {
+                let packet_identifier = pubrel.packet_identifier();
+                let reason_code = if lock(&self.state).session.release(packet_identifier) {
+                    ReasonCode::Success
+                } else {
+                    ReasonCode::PacketIdentifierNotFound
+                };
+                self.send(&Pubcomp::new(
+                    packet_identifier,
+                    reason_code,
+                    Properties::default(),
+                ))?;
+            }
+            Packet::Puback(_) | Packet::Pubrec(_) | Packet::Pubcomp(_) => {
+                let mut state = lock(&self.state);
+                match state.session.acknowledge(&packet) {
+                    Acknowledgement::Complete(packet_identifier, reason_code) => {
+                        if let Some(Pending::Publish(sender)) =
+                            state.pending.remove(&packet_identifier)
+                        {
+                            // the publisher may have stopped waiting
+                            let _ = sender.send(reason_code);
+                        }
+                    }
+                    Acknowledgement::Release(packet_identifier) => {
+                        drop(state);
+                        self.send(&Pubrel::new(
+                            packet_identifier,
+                            ReasonCode::Success,
+                            Properties::default(),
+                        ))?;
+                    }
+                    Acknowledgement::Unknown => {}
+                }
+            }
+            Packet::Suback(suback) => {
+                let pending = lock(&self.state)
+                    .pending
+                    .remove(&suback.packet_identifier());
+                match pending {
+                    Some(Pending::Subscribe {
+                        topic_filter,
+                        qos,
+                        handler,
+                        sender,
+                    }) => {
+                        let reason_codes = suback.reason_codes().to_vec();
+                        if reason_codes.iter().all(ReasonCode::is_success) {
+                            lock(&self.handlers).insert(&topic_filter, qos, handler);
+                        }
+                        let _ = sender.send(reason_codes);
+                    }
+                    // the new session refused some of them, so nothing arrives for those
+                    Some(Pending::Resubscribe(topic_filters)) => {
+                        let mut handlers = lock(&self.handlers);
+                        for (topic_filter, reason_code) in
+                            topic_filters.iter().zip(suback.reason_codes())
+                        {
+                            if reason_code.is_failure() {
+                                handlers.remove(topic_filter);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Packet::Unsuback(unsuback) => {
+                let pending = lock(&self.state)
+                    .pending
+                    .remove(&unsuback.packet_identifier());
+                if let Some(Pending::Unsubscribe {
+                    topic_filter,
+                    sender,
+                }) = pending
+                {
+                    lock(&self.handlers).remove(&topic_filter);
+                    let _ = sender.send(unsuback.reason_codes().to_vec());
+                }
+            }
+            Packet::Disconnect(disconnect) => {
+                return Err(Error::Disconnected(disconnect.reason_code()));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn receive_publish(&self, publish: Publish) -> Result<(), Error> {
+        let message = Message::from_publish(&publish);
+        match publish.packet_identifier() {
+            Some(packet_identifier) if publish.qos() == 1 => {
+                lock(&self.handlers).dispatch(&message);
+                self.send(&Puback::new(
+                    packet_identifier,
+                    ReasonCode::Success,
+                    Properties::default(),
+                ))?;
+            }
+            Some(packet_identifier) => {
+                // a resent QoS 2 message was already delivered the first time
+                if lock(&self.state).session.receive(packet_identifier) {
+                    lock(&self.handlers).dispatch(&message);
+                }
+                self.send(&Pubrec::new(
+                    packet_identifier,
+                    ReasonCode::Success,
+                    Properties::default(),
+                ))?;
+            }
+            None => lock(&self.handlers).dispatch(&message),
+        }
+        Ok(())
+    }
+
+    // Waiting requests are failed, as nothing will answer them any more. Publishes survive a
+    // reconnect, as they are resent with the session.
+    fn connection_lost(&self, error: Error, reconnecting: bool) {
+        *lock(&self.error) = Some(error);
+        let mut state = lock(&self.state);
+        if reconnecting {
+            state
+                .pending
+                .retain(|_, pending| matches!(pending, Pending::Publish(_)));
+        } else {
+            state.pending.clear();
+            drop(state);
+            lock(&self.handlers).close_channels();
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn insert_in_flight(&self, publish: Publish) {
+        lock(&self.state).session.insert(publish);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn in_flight_len(&self) -> usize {
+        lock(&self.state).session.in_flight_len()
+    }
+}
+
+/// Reads and handles packets, pinging the server when the connection is idle, until the
+/// connection fails, the server stops answering pings or disconnects. A failed connection
+/// is replaced as the reconnect policy allows, unless the application disconnected or the
+/// session was taken over by another connection.
+pub(crate) fn run(
+    shared: &Shared,
+    mut reader: TcpStream,
+    mut decoder: Decoder,
+    mut keep_alive: KeepAlive,
+) {
+    *lock(&shared.event_loop_thread) = Some(thread::current().id());
+    loop {
+        let error = serve(shared, &mut reader, &mut decoder, &mut keep_alive);
+        // taking over the session again would only take it from the other connection
+        let taken_over = matches!(error, Error::Disconnected(ReasonCode::SessionTakenOver));
+        let reconnecting = shared.reconnects() && !taken_over;
+        if !shared.is_closing() {
+            shared.emit(ConnectionEvent::ConnectionLost {
+                reason: error.to_string(),
+            });
+        }
+        shared.connection_lost(error, reconnecting);
+        if !reconnecting {
+            let _ = reader.shutdown(Shutdown::Both);
+            return;
+        }
+        match shared.reconnect() {
+            Some(connection) => (reader, decoder, keep_alive) = connection,
+            None => {
+                shared.connection_lost(Error::Io(io::ErrorKind::NotConnected.into()), false);
+                return;
+            }
+        }
+    }
+}
+
+// handles one connection until it fails
+fn serve(
+    shared: &Shared,
+    reader: &mut TcpStream,
+    decoder: &mut Decoder,
+    keep_alive: &mut KeepAlive,
+) -> Error {
+    loop {
+        let timeout = match keep_alive.poll(shared.last_sent(), Instant::now()) {
+            Action::Wait(timeout) => timeout,
+            Action::Ping => {
+                if let Err(error) = shared.send(&Pingreq::new()) {
+                    return error;
+                }
+                keep_alive.ping_sent(Instant::now());
+                continue;
+            }
+            Action::Expired => {
+                // nothing can be trusted to arrive any more, so writers fail from now on
+                let _ = reader.shutdown(Shutdown::Both);
+                return Error::Timeout;
+            }
+        };
+        if let Err(error) = reader.set_read_timeout(timeout) {
+            return error.into();
+        }
+        let result = match decoder.read_packet(reader) {
+            Ok(Packet::Pingresp(_)) => {
+                keep_alive.pingresp();
+                Ok(())
+            }
+            Ok(packet) => shared.handle_packet(packet),
+            // only the read timeout, which was set for the next keep alive action
+            Err(Error::Timeout) => Ok(()),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            return error;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::event_loop::{Pending, Shared};
+    use crate::client::options::ClientOptions;
+    use crate::client::subscription::Handler;
+    use crate::control_packet::suback::Suback;
+    use crate::control_packet::Packet;
+    use crate::error::Error;
+    use crate::property::Properties;
+    use crate::reason_code::ReasonCode;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+
+    fn shared() -> (Shared, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server_stream, _) = listener.accept().unwrap();
+        let client_options = ClientOptions::new("127.0.0.1");
+        let shared = Shared::new(String::from("foo"), stream, client_options);
+        (shared, server_stream)
+    }
+
+    #[test]
+    fn test_next_packet_identifier_skips_pending() {
+        let (shared, _server_stream) = shared();
+        let (sender, _receiver) = mpsc::channel();
+        let mut state = shared.state.lock().unwrap();
+        state.pending.insert(1, Pending::Publish(sender));
+        assert_eq!(state.next_packet_identifier().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_suback_installs_handler() {
+        let (shared, _server_stream) = shared();
+        let (handler_sender, _handler_receiver) = mpsc::channel();
+        let receiver = shared
+            .start_subscribe("foo/#", 1, Handler::Channel(handler_sender))
+            .unwrap();
+        let suback = Suback::new(1, vec![ReasonCode::GrantedQoS1], Properties::default());
+        shared.handle_packet(Packet::Suback(suback)).unwrap();
+        assert_eq!(receiver.recv().unwrap(), vec![ReasonCode::GrantedQoS1]);
+        assert_eq!(
+            shared.handlers.lock().unwrap().subscriptions(),
+            vec![(String::from("foo/#"), 1)]
+        );
+    }
+
+    #[test]
+    fn test_connection_lost_fails_pending() {
+        let (shared, _server_stream) = shared();
+        let receiver = shared.start_publish("foo", &[1], 1, false).unwrap();
+        shared.connection_lost(Error::Timeout, false);
+        assert!(receiver.recv().is_err());
+        assert!(matches!(shared.connection_error(), Error::Timeout));
+        assert_eq!(shared.in_flight_len(), 1);
+    }
+}
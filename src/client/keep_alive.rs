@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+/// What the event loop has to do to keep the connection alive.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Action {
+    /// Nothing is due for this long, or ever if keep alive is off.
+    Wait(Option<Duration>),
+    /// The connection was idle for the whole keep alive, a PINGREQ has to be sent.
+    Ping,
+    /// The server did not answer the last PINGREQ in time.
+    Expired,
+}
+
+// The client has to send a control packet at least once per keep alive, or the server
+// closes the connection. A PINGREQ is sent when nothing else went out, and a PINGRESP that
+// does not arrive within the timeout means the connection is dead.
+#[derive(Debug)]
+pub(crate) struct KeepAlive {
+    interval: Duration,
+    timeout: Duration,
+    ping_sent: Option<Instant>,
+}
+
+impl KeepAlive {
+    /// `keep_alive` is in seconds, 0 turns keep alive off.
+    pub(crate) fn new(keep_alive: u16, timeout: Duration) -> Self {
+        KeepAlive {
+            interval: Duration::from_secs(u64::from(keep_alive)),
+            timeout,
+            ping_sent: None,
+        }
+    }
+
+    /// `last_sent` is when any packet last went out, as every packet restarts the server's
+    /// keep alive timer.
+    pub(crate) fn poll(&self, last_sent: Instant, now: Instant) -> Action {
+        if let Some(ping_sent) = self.ping_sent {
+            let deadline = ping_sent + self.timeout;
+            return match deadline.checked_duration_since(now) {
+                Some(wait) if !wait.is_zero() => Action::Wait(Some(wait)),
+                _ => Action::Expired,
+            };
+        }
+        if self.interval.is_zero() {
+            return Action::Wait(None);
+        }
+        match (last_sent + self.interval).checked_duration_since(now) {
+            Some(wait) if !wait.is_zero() => Action::Wait(Some(wait)),
+            _ => Action::Ping,
+        }
+    }
+
+    pub(crate) fn ping_sent(&mut self, now: Instant) {
+        self.ping_sent = Some(now);
+    }
+
+    pub(crate) fn pingresp(&mut self) {
+        self.ping_sent = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::keep_alive::{Action, KeepAlive};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_ping_when_idle() {
+        let mut keep_alive = KeepAlive::new(10, Duration::from_secs(2));
+        let start = Instant::now();
+        assert_eq!(
+            keep_alive.poll(start, start + Duration::from_secs(4)),
+            Action::Wait(Some(Duration::from_secs(6)))
+        );
+        let now = start + Duration::from_secs(10);
+        assert_eq!(keep_alive.poll(start, now), Action::Ping);
+        keep_alive.ping_sent(now);
+        assert_eq!(
+            keep_alive.poll(now, now + Duration::from_secs(1)),
+            Action::Wait(Some(Duration::from_secs(1)))
+        );
+        keep_alive.pingresp();
+        assert_eq!(
+            keep_alive.poll(now, now + Duration::from_secs(1)),
+            Action::Wait(Some(Duration::from_secs(9)))
+        );
+    }
+
+    #[test]
+    fn test_expired() {
+        let mut keep_alive = KeepAlive::new(10, Duration::from_secs(2));
+        let now = Instant::now();
+        keep_alive.ping_sent(now);
+        assert_eq!(
+            keep_alive.poll(now, now + Duration::from_secs(2)),
+            Action::Expired
+        );
+    }
+
+    #[test]
+    fn test_disabled() {
+        let keep_alive = KeepAlive::new(0, Duration::from_secs(2));
+        let now = Instant::now();
+        assert_eq!(
+            keep_alive.poll(now, now + Duration::from_secs(3600)),
+            Action::Wait(None)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::Error;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the client connects to and how the TCP connection is set up. What goes into the
+/// CONNECT packet is in `ConnectOptions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientOptions {
+    host: String,
+    port: u16,
+    addrs: Vec<SocketAddr>,
+    connect_timeout: Duration,
+    nodelay: bool,
+}
+
+impl ClientOptions {
+    pub fn new(host: &str) -> Self {
+        ClientOptions {
+            host: String::from(host),
+            port: DEFAULT_PORT,
+            addrs: Vec::new(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            nodelay: false,
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Addresses tried in order instead of resolving the host.
+    pub fn with_addrs(mut self, addrs: &[SocketAddr]) -> Self {
+        self.addrs = Vec::from(addrs);
+        self
+    }
+
+    /// How long to wait for the TCP connection to each address.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Sets TCP_NODELAY, so small packets go out without waiting to be batched.
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// Connects to the first address that accepts, and reports the last failure if none does.
+    pub(crate) fn open(&self) -> Result<TcpStream, Error> {
+        if self.connect_timeout.is_zero() {
+            return Err(Error::InvalidArgument("connect timeout must not be zero"));
+        }
+        let addrs = if self.addrs.is_empty() {
+            (self.host.as_str(), self.port).to_socket_addrs()?.collect()
+        } else {
+            self.addrs.clone()
+        };
+        let mut last_error = Error::InvalidArgument("no address to connect to");
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_nodelay(self.nodelay)?;
+                    return Ok(stream);
+                }
+                Err(error) => last_error = error.into(),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::options::ClientOptions;
+    use crate::error::Error;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn test_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ClientOptions::new("127.0.0.1")
+            .with_port(port)
+            .with_nodelay(true);
+        let stream = options.open().unwrap();
+        assert!(stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn test_open_tries_every_addr() {
+        let refusing = TcpListener::bind("127.0.0.1:0").unwrap();
+        let refusing_addr = refusing.local_addr().unwrap();
+        drop(refusing);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = ClientOptions::new("unused")
+            .with_addrs(&[refusing_addr, listener.local_addr().unwrap()]);
+        let stream = options.open().unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+        let options = ClientOptions::new("unused").with_addrs(&[refusing_addr]);
+        assert!(matches!(options.open().unwrap_err(), Error::Io(_)));
+    }
+
+    #[test]
+    fn test_open_invalid() {
+        let options = ClientOptions::new("127.0.0.1").with_connect_timeout(Duration::ZERO);
+        assert!(matches!(
+            options.open().unwrap_err(),
+            Error::InvalidArgument(_)
+        ));
+    }
+}
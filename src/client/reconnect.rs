@@ -0,0 +1,133 @@
+use crate::random;
+use std::time::Duration;
+
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAXIMUM_DELAY: Duration = Duration::from_secs(60);
+
+/// How the client reconnects after the connection is lost: the delay before each attempt
+/// doubles from the initial delay up to the maximum, and a random part of up to half of it
+/// is taken off so that many clients do not all come back at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    initial_delay: Duration,
+    maximum_delay: Duration,
+    maximum_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Retries forever, starting after one second and waiting at most a minute.
+    pub fn new() -> Self {
+        ReconnectPolicy {
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            maximum_delay: DEFAULT_MAXIMUM_DELAY,
+            maximum_attempts: None,
+        }
+    }
+
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    pub fn with_maximum_delay(mut self, maximum_delay: Duration) -> Self {
+        self.maximum_delay = maximum_delay;
+        self
+    }
+
+    /// Gives up after this many failed attempts in a row.
+    pub fn with_maximum_attempts(mut self, maximum_attempts: u32) -> Self {
+        self.maximum_attempts = Some(maximum_attempts);
+        self
+    }
+
+    pub fn initial_delay(&self) -> Duration {
+        self.initial_delay
+    }
+
+    pub fn maximum_delay(&self) -> Duration {
+        self.maximum_delay
+    }
+
+    pub fn maximum_attempts(&self) -> Option<u32> {
+        self.maximum_attempts
+    }
+
+    /// Whether attempt number `attempt`, counting from 1, may still be made.
+    pub(crate) fn allows(&self, attempt: u32) -> bool {
+        self.maximum_attempts
+            .is_none_or(|maximum_attempts| attempt <= maximum_attempts)
+    }
+
+    /// The delay before attempt number `attempt`, counting from 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        self.delay_with_jitter(attempt, random::fraction())
+    }
+
+    // `jitter` is in [0, 1) and takes up to half of the delay off
+    fn delay_with_jitter(&self, attempt: u32, jitter: f64) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.maximum_delay);
+        delay.mul_f64(1.0 - jitter / 2.0)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy::new()
+    }
+}
+
+/// Changes of the connection state, as reported by `Client::connection_events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// The server accepted the CONNECT, and still had our session if `session_present`.
+    Connected { session_present: bool },
+    /// The connection failed for the given reason.
+    ConnectionLost { reason: String },
+    /// Attempt number `attempt`, counting from 1, starts after `delay`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// The reconnect policy ran out of attempts, the client stays disconnected.
+    ReconnectFailed,
+    /// The application disconnected.
+    Disconnected,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::reconnect::ReconnectPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_delay_backs_off() {
+        let policy = ReconnectPolicy::new()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_maximum_delay(Duration::from_millis(500));
+        assert_eq!(policy.delay_with_jitter(1, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.delay_with_jitter(3, 0.0), Duration::from_millis(400));
+        assert_eq!(policy.delay_with_jitter(4, 0.0), Duration::from_millis(500));
+        assert_eq!(
+            policy.delay_with_jitter(u32::MAX, 0.5),
+            Duration::from_millis(375)
+        );
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let policy = ReconnectPolicy::new();
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay > Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        assert!(ReconnectPolicy::new().allows(u32::MAX));
+        let policy = ReconnectPolicy::new().with_maximum_attempts(2);
+        assert!(policy.allows(2));
+        assert!(!policy.allows(3));
+    }
+}
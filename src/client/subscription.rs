@@ -0,0 +1,184 @@
+use crate::common::Bytes;
+use crate::control_packet::publish::Publish;
+use crate::topic;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+
+/// An application message received on a subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    topic: String,
+    payload: Bytes,
+    qos: u8,
+    retain: bool,
+}
+
+impl Message {
+    pub(crate) fn from_publish(publish: &Publish) -> Self {
+        Message {
+            topic: String::from(publish.topic_name()),
+            payload: Vec::from(publish.payload()),
+            qos: publish.qos(),
+            retain: publish.retain(),
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn qos(&self) -> u8 {
+        self.qos
+    }
+
+    pub fn retain(&self) -> bool {
+        self.retain
+    }
+}
+
+/// Messages matching one topic filter, as returned by `Client::subscribe`. Iterating blocks
+/// until the next message arrives and ends once the client disconnects, or loses the
+/// connection without reconnecting.
+#[derive(Debug)]
+pub struct Subscription {
+    granted_qos: u8,
+    receiver: Receiver<Message>,
+}
+
+impl Subscription {
+    pub(crate) fn new(granted_qos: u8, receiver: Receiver<Message>) -> Self {
+        Subscription {
+            granted_qos,
+            receiver,
+        }
+    }
+
+    /// The maximum QoS the server will deliver with, which may be lower than requested.
+    pub fn granted_qos(&self) -> u8 {
+        self.granted_qos
+    }
+
+    pub fn try_next(&self) -> Option<Message> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Message> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Message> {
+        self.receiver.recv().ok()
+    }
+}
+
+pub(crate) enum Handler {
+    Callback(Box<dyn FnMut(Message) + Send>),
+    Channel(Sender<Message>),
+}
+
+// the application side of every topic filter the client subscribed to, with the QoS it
+// asked for so the subscription can be made again on a new session
+#[derive(Default)]
+pub(crate) struct Handlers {
+    handlers: Vec<(String, u8, Handler)>,
+}
+
+impl Handlers {
+    /// Subscribing again to the same filter replaces its handler, as the server replaces the
+    /// subscription.
+    pub(crate) fn insert(&mut self, topic_filter: &str, qos: u8, handler: Handler) {
+        self.remove(topic_filter);
+        self.handlers
+            .push((String::from(topic_filter), qos, handler));
+    }
+
+    pub(crate) fn remove(&mut self, topic_filter: &str) {
+        self.handlers
+            .retain(|(filter, _, _)| filter != topic_filter);
+    }
+
+    /// Drops the senders of the subscriptions that deliver to a channel, which ends their
+    /// iterators. Callbacks are kept.
+    pub(crate) fn close_channels(&mut self) {
+        self.handlers
+            .retain(|(_, _, handler)| matches!(handler, Handler::Callback(_)));
+    }
+
+    /// Every topic filter with the QoS it was subscribed with.
+    pub(crate) fn subscriptions(&self) -> Vec<(String, u8)> {
+        self.handlers
+            .iter()
+            .map(|(topic_filter, qos, _)| (topic_filter.clone(), *qos))
+            .collect()
+    }
+
+    /// Hands the message to every handler whose filter matches its topic.
+    pub(crate) fn dispatch(&mut self, message: &Message) {
+        for (topic_filter, _, handler) in self.handlers.iter_mut() {
+            if !topic::matches(topic_filter, message.topic()) {
+                continue;
+            }
+            match handler {
+                Handler::Callback(callback) => callback(message.clone()),
+                // a dropped receiver only means the application stopped listening
+                Handler::Channel(sender) => {
+                    let _ = sender.send(message.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::subscription::{Handler, Handlers, Message};
+    use crate::control_packet::publish::Publish;
+    use crate::property::Properties;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+
+    fn message(topic: &str) -> Message {
+        let publish = Publish::new(topic, None, vec![1], 0, false, Properties::default());
+        Message::from_publish(&publish)
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let callback_received = Arc::clone(&received);
+        let (sender, receiver) = mpsc::channel();
+        let mut handlers = Handlers::default();
+        handlers.insert(
+            "foo/+",
+            0,
+            Handler::Callback(Box::new(move |message| {
+                callback_received.lock().unwrap().push(message)
+            })),
+        );
+        handlers.insert("foo/#", 1, Handler::Channel(sender));
+        handlers.dispatch(&message("foo/bar"));
+        handlers.dispatch(&message("foo"));
+        handlers.dispatch(&message("bar"));
+        assert_eq!(*received.lock().unwrap(), vec![message("foo/bar")]);
+        assert_eq!(receiver.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut handlers = Handlers::default();
+        handlers.insert("foo", 0, Handler::Channel(sender.clone()));
+        handlers.insert("foo", 2, Handler::Channel(sender));
+        assert_eq!(handlers.subscriptions(), vec![(String::from("foo"), 2)]);
+        handlers.remove("foo");
+        assert!(handlers.subscriptions().is_empty());
+    }
+}
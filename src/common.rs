@@ -3,82 +3,161 @@ use std::fmt;
 pub(crate) type Byte = u8;
 pub(crate) type Bytes = Vec<Byte>;
 
-#[derive(Debug, PartialEq)]
+/// Strings and binary data carry a two byte length, which caps them at this many bytes.
+pub(crate) const MAX_ENCODED_LEN: usize = u16::MAX as usize;
+
+/// The largest remaining length four bytes of variable byte integer can hold.
+pub(crate) const MAX_REMAINING_LENGTH: usize = 268_435_455;
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub(crate) struct TwoByteInt(u16);
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub(crate) struct FourByteInt(u32);
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct VariableByteInt(u32);
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub(crate) struct UTF8String(String);
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct UTF8StringPair(String, String);
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct BinaryData(Bytes);
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ParseErrorKind {
+    MalformedPacket,
+    ProtocolError,
+    UnsupportedProtocolVersion(u8),
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct ParseError(&'static str);
+pub(crate) struct ParseError {
+    kind: ParseErrorKind,
+    message: &'static str,
+    // bytes left unparsed where the error occurred, used to derive the offset within the packet
+    remaining: Option<usize>,
+}
 
 impl ParseError {
     pub(crate) fn new(message: &'static str) -> Self {
-        ParseError(message)
+        ParseError {
+            kind: ParseErrorKind::MalformedPacket,
+            message,
+            remaining: None,
+        }
+    }
+
+    pub(crate) fn protocol(message: &'static str) -> Self {
+        ParseError {
+            kind: ParseErrorKind::ProtocolError,
+            message,
+            remaining: None,
+        }
+    }
+
+    pub(crate) fn unsupported_protocol_version(version: u8) -> Self {
+        ParseError {
+            kind: ParseErrorKind::UnsupportedProtocolVersion(version),
+            message: "unsupported protocol version",
+            remaining: None,
+        }
+    }
+
+    // records where the error occurred unless a more precise location is already known
+    pub(crate) fn at(mut self, remaining: usize) -> Self {
+        self.remaining.get_or_insert(remaining);
+        self
+    }
+
+    // accounts for bytes that followed a sub-slice the error occurred in
+    pub(crate) fn followed_by(mut self, trailing: usize) -> Self {
+        self.remaining = self.remaining.map(|remaining| remaining + trailing);
+        self
+    }
+
+    pub(crate) fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    pub(crate) fn message(&self) -> &'static str {
+        self.message
+    }
+
+    pub(crate) fn remaining(&self) -> Option<usize> {
+        self.remaining
     }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Parse error: {}", self.0)
+        write!(f, "Parse error: {}", self.message)
     }
 }
 
-pub(crate) trait Parseable {
-    fn parse_byte(&self) -> Result<(Byte, &[Byte]), ParseError>;
-    fn parse_two_byte_int(&self) -> Result<(TwoByteInt, &[Byte]), ParseError>;
-    fn parse_four_byte_int(&self) -> Result<(FourByteInt, &[Byte]), ParseError>;
-    fn parse_variable_byte_int(&self) -> Result<(VariableByteInt, &[Byte]), ParseError>;
-    fn parse_utf8_string(&self) -> Result<(UTF8String, &[Byte]), ParseError>;
-    fn parse_utf8_string_pair(&self) -> Result<(UTF8StringPair, &[Byte]), ParseError>;
-    fn parse_binary_data(&self) -> Result<(BinaryData, &[Byte]), ParseError>;
+pub(crate) trait Parseable<'a> {
+    fn parse_byte(&self) -> Result<(Byte, &'a [Byte]), ParseError>;
+    fn parse_two_byte_int(&self) -> Result<(TwoByteInt, &'a [Byte]), ParseError>;
+    fn parse_four_byte_int(&self) -> Result<(FourByteInt, &'a [Byte]), ParseError>;
+    fn parse_variable_byte_int(&self) -> Result<(VariableByteInt, &'a [Byte]), ParseError>;
+    fn parse_utf8_string(&self) -> Result<(UTF8String, &'a [Byte]), ParseError>;
+    fn parse_utf8_string_pair(&self) -> Result<(UTF8StringPair, &'a [Byte]), ParseError>;
+    fn parse_binary_data(&self) -> Result<(BinaryData, &'a [Byte]), ParseError>;
+    fn parse_end(&self) -> Result<(), ParseError>;
 }
 
-impl Parseable for &[Byte] {
-    fn parse_byte(&self) -> Result<(Byte, &[Byte]), ParseError> {
-        Ok((self[0], &self[1..]))
+impl<'a> Parseable<'a> for &'a [Byte] {
+    fn parse_byte(&self) -> Result<(Byte, &'a [Byte]), ParseError> {
+        let (bytes, leftover) = split_checked(self, 1)?;
+        Ok((bytes[0], leftover))
     }
 
-    fn parse_two_byte_int(&self) -> Result<(TwoByteInt, &[Byte]), ParseError> {
-        let bytes: [u8; 2] = [self[0] as u8, self[1] as u8];
-        let val = u16::from_be_bytes(bytes);
-        Ok((TwoByteInt(val), &self[2..]))
+    fn parse_two_byte_int(&self) -> Result<(TwoByteInt, &'a [Byte]), ParseError> {
+        let (bytes, leftover) = split_checked(self, 2)?;
+        let val = u16::from_be_bytes([bytes[0], bytes[1]]);
+        Ok((TwoByteInt(val), leftover))
     }
 
-    fn parse_four_byte_int(&self) -> Result<(FourByteInt, &[Byte]), ParseError> {
-        let bytes: [u8; 4] = [self[0] as u8, self[1] as u8, self[2] as u8, self[3] as u8];
-        let val = u32::from_be_bytes(bytes);
-        Ok((FourByteInt(val), &self[4..]))
+    fn parse_four_byte_int(&self) -> Result<(FourByteInt, &'a [Byte]), ParseError> {
+        let (bytes, leftover) = split_checked(self, 4)?;
+        let val = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Ok((FourByteInt(val), leftover))
     }
 
-    fn parse_variable_byte_int(&self) -> Result<(VariableByteInt, &[Byte]), ParseError> {
-        let (val, len) = decode_variable_length_int(&self.clone())?;
+    fn parse_variable_byte_int(&self) -> Result<(VariableByteInt, &'a [Byte]), ParseError> {
+        let (val, len) = decode_variable_length_int(self)?;
         Ok((VariableByteInt(val), &self[len..]))
     }
 
-    fn parse_utf8_string(&self) -> Result<(UTF8String, &[Byte]), ParseError> {
-        let (string, leftover) = decode_utf8_string(&self)?;
+    fn parse_utf8_string(&self) -> Result<(UTF8String, &'a [Byte]), ParseError> {
+        let (string, leftover) = decode_utf8_string(self)?;
         Ok((UTF8String(string), leftover))
     }
 
-    fn parse_utf8_string_pair(&self) -> Result<(UTF8StringPair, &[Byte]), ParseError> {
-        let (key, key_leftover) = decode_utf8_string(&self)?;
+    fn parse_utf8_string_pair(&self) -> Result<(UTF8StringPair, &'a [Byte]), ParseError> {
+        let (key, key_leftover) = decode_utf8_string(self)?;
         let (val, leftover) = decode_utf8_string(key_leftover)?;
         Ok((UTF8StringPair(key, val), leftover))
     }
 
-    fn parse_binary_data(&self) -> Result<(BinaryData, &[Byte]), ParseError> {
-        let len = u16::from_be_bytes([self[0] as u8, self[1] as u8]) as usize;
-        let bytes = Vec::from(&self[2..2 + len]);
-        Ok((BinaryData(bytes), &self[2 + len..]))
+    fn parse_binary_data(&self) -> Result<(BinaryData, &'a [Byte]), ParseError> {
+        let (len, len_leftover) = self.parse_two_byte_int()?;
+        let (bytes, leftover) = split_checked(len_leftover, len.value() as usize)?;
+        Ok((BinaryData(Vec::from(bytes)), leftover))
+    }
+
+    // the last field has to end where the remaining length says the packet ends
+    fn parse_end(&self) -> Result<(), ParseError> {
+        if !self.is_empty() {
+            return Err(ParseError::new("bytes left over after the last field").at(self.len()));
+        }
+        Ok(())
+    }
+}
+
+fn split_checked(bytes: &[Byte], len: usize) -> Result<(&[Byte], &[Byte]), ParseError> {
+    if bytes.len() < len {
+        return Err(ParseError::new("unexpected end of bytes").at(bytes.len()));
     }
+    Ok(bytes.split_at(len))
 }
 
 pub(crate) trait Serializable {
@@ -135,7 +214,7 @@ impl VariableByteInt {
 
 impl Serializable for VariableByteInt {
     fn as_bytes(&self) -> Bytes {
-        encode_variable_length_int(self.0.clone())
+        encode_variable_length_int(self.0)
     }
 }
 
@@ -185,7 +264,7 @@ impl BinaryData {
 
 impl Serializable for BinaryData {
     fn as_bytes(&self) -> Bytes {
-        let mut bytes = Vec::from((self.0.len() as u16).to_be_bytes());
+        let mut bytes = Vec::from(encoded_len(self.0.len()).to_be_bytes());
         bytes.append(&mut self.0.clone());
         bytes
     }
@@ -195,9 +274,9 @@ pub(crate) fn encode_variable_length_int(mut int: u32) -> Bytes {
     let mut bytes: Bytes = vec![];
     loop {
         let mut byte: u8 = (int % 128) as u8;
-        int = int / 128;
+        int /= 128;
         if int > 0 {
-            byte = byte | 128;
+            byte |= 128;
         }
         bytes.push(byte);
         if int == 0 {
@@ -209,27 +288,40 @@ pub(crate) fn encode_variable_length_int(mut int: u32) -> Bytes {
 pub(crate) fn decode_variable_length_int(bytes: &[Byte]) -> Result<(u32, usize), ParseError> {
     let mut multiplier: u32 = 1;
     let mut value: u32 = 0;
-    for (idx, byte) in bytes.iter().enumerate() {
+    // at most four bytes, which caps the value at 268,435,455
+    for (idx, byte) in bytes.iter().take(4).enumerate() {
         let byte_val = (byte & 127) as u32;
-        value = byte_val * multiplier + value;
-        multiplier = multiplier * 128;
+        value += byte_val * multiplier;
+        multiplier *= 128;
         if byte & 128 == 0 {
             return Ok((value, idx + 1));
         }
     }
-    Err(ParseError::new("malformed variable length int"))
+    Err(ParseError::new("malformed variable length int").at(bytes.len()))
 }
 
 pub(crate) fn encode_utf8_string(string: &str) -> Bytes {
-    let mut bytes: Bytes = Vec::from((string.len() as u16).to_be_bytes());
+    let mut bytes: Bytes = Vec::from(encoded_len(string.len()).to_be_bytes());
     bytes.append(&mut Vec::from(string.as_bytes()));
     bytes
 }
 
+// whatever ends up in a packet is checked against MAX_ENCODED_LEN when the packet is built,
+// so a longer value here is a bug rather than something to truncate
+fn encoded_len(len: usize) -> u16 {
+    u16::try_from(len).expect("length checked against MAX_ENCODED_LEN")
+}
+
 pub(crate) fn decode_utf8_string(bytes: &[Byte]) -> Result<(String, &[Byte]), ParseError> {
-    let len: usize = (bytes[0] as usize * 256) + bytes[1] as usize;
-    let string = String::from_utf8(Vec::from(&bytes[2..2 + len])).unwrap(); //TODO check for error here
-    Ok((string, &bytes[2 + len..]))
+    let (len, len_leftover) = bytes.parse_two_byte_int()?;
+    let (string_bytes, leftover) = split_checked(len_leftover, len.value() as usize)?;
+    // rejects ill-formed UTF-8, including encoded surrogates U+D800 to U+DFFF
+    let string = String::from_utf8(Vec::from(string_bytes))
+        .map_err(|_| ParseError::new("malformed utf8 string").at(len_leftover.len()))?;
+    if string.contains('\u{0000}') {
+        return Err(ParseError::new("utf8 string contains null character").at(len_leftover.len()));
+    }
+    Ok((string, leftover))
 }
 
 #[cfg(test)]
@@ -381,4 +473,95 @@ mod tests {
         let (decoded, _) = decode_utf8_string(bytes).unwrap();
         assert_eq!(decoded, string);
     }
+
+    #[test]
+    fn test_parse_empty() {
+        let bytes: &[Byte] = &[];
+        assert!(bytes.parse_byte().is_err());
+        assert!(bytes.parse_variable_byte_int().is_err());
+        assert!(bytes.parse_utf8_string().is_err());
+    }
+
+    #[test]
+    fn test_parse_two_byte_int_short() {
+        let bytes: &[Byte] = &[1];
+        assert!(bytes.parse_two_byte_int().is_err());
+    }
+
+    #[test]
+    fn test_parse_four_byte_int_short() {
+        let bytes: &[Byte] = &[1, 1, 1];
+        assert!(bytes.parse_four_byte_int().is_err());
+    }
+
+    #[test]
+    fn test_parse_utf8_string_short() {
+        let bytes: &[Byte] = &[0, 6, 102, 111, 111];
+        assert!(bytes.parse_utf8_string().is_err());
+    }
+
+    #[test]
+    fn test_parse_utf8_string_pair_short() {
+        let bytes: &[Byte] = &[0, 3, 102, 111, 111, 0];
+        assert!(bytes.parse_utf8_string_pair().is_err());
+    }
+
+    #[test]
+    fn test_parse_binary_data_short() {
+        let bytes: &[Byte] = &[0, 4, 1, 1];
+        assert!(bytes.parse_binary_data().is_err());
+    }
+
+    #[test]
+    fn test_parse_end() {
+        let bytes: &[Byte] = &[];
+        assert!(bytes.parse_end().is_ok());
+        let bytes: &[Byte] = &[0];
+        assert!(bytes.parse_end().is_err());
+    }
+
+    #[test]
+    fn test_decode_utf8_string_invalid_utf8() {
+        let bytes: &[Byte] = &[0, 2, 0xC3, 0x28];
+        assert!(decode_utf8_string(bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_utf8_string_surrogate() {
+        let bytes: &[Byte] = &[0, 3, 0xED, 0xA0, 0x80];
+        assert!(decode_utf8_string(bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_utf8_string_null_character() {
+        let bytes: &[Byte] = &[0, 3, 102, 0, 111];
+        assert!(decode_utf8_string(bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_variable_length_int_max() {
+        let bytes: &[Byte] = &[0xFF, 0xFF, 0xFF, 0x7F];
+        let actual: (u32, usize) = decode_variable_length_int(bytes).unwrap();
+        assert_eq!(actual, (268_435_455, 4));
+    }
+
+    #[test]
+    fn test_decode_variable_length_int_too_long() {
+        let bytes: &[Byte] = &[0x80, 0x80, 0x80, 0x80, 0x01];
+        assert!(decode_variable_length_int(bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_variable_length_int_truncated() {
+        let bytes: &[Byte] = &[0x80, 0x80];
+        assert!(decode_variable_length_int(bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse_error_remaining() {
+        let bytes: &[Byte] = &[0, 6, 102, 111, 111];
+        let error = bytes.parse_utf8_string().unwrap_err();
+        assert_eq!(error.remaining(), Some(3));
+        assert_eq!(error.followed_by(2).remaining(), Some(5));
+    }
 }
@@ -1,12 +1,43 @@
-use crate::common::{Byte, Bytes, ParseError};
+use crate::common::{Byte, Bytes, ParseError, Parseable};
+use crate::control_packet::auth::Auth;
+use crate::control_packet::connack::Connack;
 use crate::control_packet::connect::Connect;
+use crate::control_packet::disconnect::Disconnect;
+use crate::control_packet::pingreq::Pingreq;
+use crate::control_packet::pingresp::Pingresp;
+use crate::control_packet::puback::Puback;
+use crate::control_packet::pubcomp::Pubcomp;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::pubrec::Pubrec;
+use crate::control_packet::pubrel::Pubrel;
+use crate::control_packet::suback::Suback;
+use crate::control_packet::subscribe::Subscribe;
+use crate::control_packet::unsuback::Unsuback;
+use crate::control_packet::unsubscribe::Unsubscribe;
+use crate::error::Error;
 use crate::fixed_header::FixedHeader;
 use crate::payload::Payload;
 use crate::variable_header::VariableHeader;
 
+pub(crate) mod auth;
+pub(crate) mod connack;
 pub(crate) mod connect;
+pub(crate) mod disconnect;
+pub(crate) mod pingreq;
+pub(crate) mod pingresp;
+pub(crate) mod puback;
+pub(crate) mod pubcomp;
+pub(crate) mod publish;
+pub(crate) mod pubrec;
+pub(crate) mod pubrel;
+pub(crate) mod suback;
+pub(crate) mod subscribe;
+pub(crate) mod unsuback;
+pub(crate) mod unsubscribe;
 
-pub(crate) enum PacketType {
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketType {
     CONNECT,
     CONNACK,
     PUBLISH,
@@ -24,18 +55,63 @@ pub(crate) enum PacketType {
     AUTH,
 }
 
+impl PacketType {
+    pub(crate) fn value(&self) -> u8 {
+        match self {
+            PacketType::CONNECT => 1,
+            PacketType::CONNACK => 2,
+            PacketType::PUBLISH => 3,
+            PacketType::PUBACK => 4,
+            PacketType::PUBREC => 5,
+            PacketType::PUBREL => 6,
+            PacketType::PUBCOMP => 7,
+            PacketType::SUBSCRIBE => 8,
+            PacketType::SUBACK => 9,
+            PacketType::UNSUBSCRIBE => 10,
+            PacketType::UNSUBACK => 11,
+            PacketType::PINGREQ => 12,
+            PacketType::PINGRESP => 13,
+            PacketType::DISCONNECT => 14,
+            PacketType::AUTH => 15,
+        }
+    }
+
+    pub(crate) fn from_value(value: u8) -> Result<Self, ParseError> {
+        match value {
+            1 => Ok(PacketType::CONNECT),
+            2 => Ok(PacketType::CONNACK),
+            3 => Ok(PacketType::PUBLISH),
+            4 => Ok(PacketType::PUBACK),
+            5 => Ok(PacketType::PUBREC),
+            6 => Ok(PacketType::PUBREL),
+            7 => Ok(PacketType::PUBCOMP),
+            8 => Ok(PacketType::SUBSCRIBE),
+            9 => Ok(PacketType::SUBACK),
+            10 => Ok(PacketType::UNSUBSCRIBE),
+            11 => Ok(PacketType::UNSUBACK),
+            12 => Ok(PacketType::PINGREQ),
+            13 => Ok(PacketType::PINGRESP),
+            14 => Ok(PacketType::DISCONNECT),
+            15 => Ok(PacketType::AUTH),
+            _ => Err(ParseError::new("unknown packet type")),
+        }
+    }
+}
+
 pub(crate) trait ControlPacket {
     fn get_fixed_header(&self) -> &FixedHeader;
     fn fixed_header_bytes(&self) -> Bytes {
         self.get_fixed_header().as_bytes()
     }
 
-    fn get_variable_header(&self) -> &VariableHeader;
+    type VariableHeader: VariableHeader;
+    fn get_variable_header(&self) -> &Self::VariableHeader;
     fn variable_header_bytes(&self) -> Bytes {
         self.get_variable_header().as_bytes()
     }
 
-    fn get_payload(&self) -> &Payload;
+    type Payload: Payload;
+    fn get_payload(&self) -> &Self::Payload;
     fn payload_bytes(&self) -> Bytes {
         self.get_payload().as_bytes()
     }
@@ -53,11 +129,164 @@ pub(crate) trait ControlPacket {
     }
 }
 
-pub(crate) fn parse_packet_bytes(bytes: &[Byte]) -> Result<Box<dyn ControlPacket>, &'static str> {
-    let first_byte = bytes[0];
-    match first_byte >> 4 {
-        1 => Ok(Box::new(Connect::from_bytes(bytes).unwrap())),
-        //TODO other types here
-        _ => Err("unknown package type"),
+#[derive(Debug, PartialEq)]
+pub(crate) enum Packet {
+    Connect(Connect),
+    Connack(Connack),
+    Publish(Publish),
+    Puback(Puback),
+    Pubrec(Pubrec),
+    Pubrel(Pubrel),
+    Pubcomp(Pubcomp),
+    Subscribe(Subscribe),
+    Suback(Suback),
+    Unsubscribe(Unsubscribe),
+    Unsuback(Unsuback),
+    Pingreq(Pingreq),
+    Pingresp(Pingresp),
+    Disconnect(Disconnect),
+    Auth(Auth),
+}
+
+impl Packet {
+    pub(crate) fn packet_type(&self) -> PacketType {
+        match self {
+            Packet::Connect(_) => PacketType::CONNECT,
+            Packet::Connack(_) => PacketType::CONNACK,
+            Packet::Publish(_) => PacketType::PUBLISH,
+            Packet::Puback(_) => PacketType::PUBACK,
+            Packet::Pubrec(_) => PacketType::PUBREC,
+            Packet::Pubrel(_) => PacketType::PUBREL,
+            Packet::Pubcomp(_) => PacketType::PUBCOMP,
+            Packet::Subscribe(_) => PacketType::SUBSCRIBE,
+            Packet::Suback(_) => PacketType::SUBACK,
+            Packet::Unsubscribe(_) => PacketType::UNSUBSCRIBE,
+            Packet::Unsuback(_) => PacketType::UNSUBACK,
+            Packet::Pingreq(_) => PacketType::PINGREQ,
+            Packet::Pingresp(_) => PacketType::PINGRESP,
+            Packet::Disconnect(_) => PacketType::DISCONNECT,
+            Packet::Auth(_) => PacketType::AUTH,
+        }
+    }
+}
+
+pub(crate) fn parse_packet_bytes(bytes: &[Byte]) -> Result<Packet, Error> {
+    let packet_type = bytes
+        .first()
+        .and_then(|first_byte| PacketType::from_value(first_byte >> 4).ok());
+    // errors are located relative to this packet, ignoring any bytes that follow it
+    let packet_bytes = match packet_len(bytes) {
+        Ok(len) if len <= bytes.len() => &bytes[..len],
+        _ => bytes,
+    };
+    parse_packet(packet_bytes)
+        .map_err(|error| Error::from_parse_error(error, packet_type, packet_bytes.len()))
+}
+
+fn parse_packet(bytes: &[Byte]) -> Result<Packet, ParseError> {
+    let (first_byte, _) = bytes.parse_byte()?;
+    let packet_type =
+        PacketType::from_value(first_byte >> 4).map_err(|error| error.at(bytes.len()))?;
+    let packet = match packet_type {
+        PacketType::CONNECT => Packet::Connect(Connect::from_bytes(bytes)?),
+        PacketType::CONNACK => Packet::Connack(Connack::from_bytes(bytes)?),
+        PacketType::PUBLISH => Packet::Publish(Publish::from_bytes(bytes)?),
+        PacketType::PUBACK => Packet::Puback(Puback::from_bytes(bytes)?),
+        PacketType::PUBREC => Packet::Pubrec(Pubrec::from_bytes(bytes)?),
+        PacketType::PUBREL => Packet::Pubrel(Pubrel::from_bytes(bytes)?),
+        PacketType::PUBCOMP => Packet::Pubcomp(Pubcomp::from_bytes(bytes)?),
+        PacketType::SUBSCRIBE => Packet::Subscribe(Subscribe::from_bytes(bytes)?),
+        PacketType::SUBACK => Packet::Suback(Suback::from_bytes(bytes)?),
+        PacketType::UNSUBSCRIBE => Packet::Unsubscribe(Unsubscribe::from_bytes(bytes)?),
+        PacketType::UNSUBACK => Packet::Unsuback(Unsuback::from_bytes(bytes)?),
+        PacketType::PINGREQ => Packet::Pingreq(Pingreq::from_bytes(bytes)?),
+        PacketType::PINGRESP => Packet::Pingresp(Pingresp::from_bytes(bytes)?),
+        PacketType::DISCONNECT => Packet::Disconnect(Disconnect::from_bytes(bytes)?),
+        PacketType::AUTH => Packet::Auth(Auth::from_bytes(bytes)?),
+    };
+    Ok(packet)
+}
+
+/// Total length of the packet starting at `bytes`, fixed header included.
+pub(crate) fn packet_len(bytes: &[Byte]) -> Result<usize, ParseError> {
+    let (_first_byte, fb_leftover) = bytes.parse_byte()?;
+    let (remaining_length, leftover) = fb_leftover.parse_variable_byte_int()?;
+    Ok(bytes.len() - leftover.len() + remaining_length.value() as usize)
+}
+
+/// Parses the fixed header of a packet of the expected type and returns exactly the
+/// `remaining_length` bytes that follow it.
+pub(crate) fn parse_fixed_header(
+    bytes: &[Byte],
+    packet_type: &PacketType,
+) -> Result<(FixedHeader, Bytes), ParseError> {
+    let (fixed_header, mut leftover) = FixedHeader::from_bytes(Vec::from(bytes))?;
+    if fixed_header.packet_type() != *packet_type {
+        return Err(ParseError::new("unexpected packet type").at(bytes.len()));
+    }
+    let remaining_length = fixed_header.remaining_length() as usize;
+    if leftover.len() < remaining_length {
+        return Err(ParseError::new("packet shorter than remaining length").at(0));
+    }
+    leftover.truncate(remaining_length);
+    Ok((fixed_header, leftover))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::connect::Connect;
+    use crate::control_packet::publish::Publish;
+    use crate::control_packet::{
+        packet_len, parse_packet_bytes, ControlPacket, Packet, PacketType,
+    };
+    use crate::error::Error;
+    use crate::payload::connect::{ConnectPayload, Will};
+    use crate::property::{Properties, Property};
+
+    #[test]
+    fn test_parse_packet_bytes() {
+        let packet = Publish::new("foo", Some(1), vec![1], 1, false, Properties::default());
+        let parsed_packet = parse_packet_bytes(&packet.as_bytes()).unwrap();
+        assert_eq!(parsed_packet, Packet::Publish(packet));
+    }
+
+    #[test]
+    fn test_parse_packet_bytes_truncated() {
+        let will = Will::new("foo", vec![1, 2], 1, false, Properties::default());
+        let payload = ConnectPayload::new("id")
+            .with_will(will)
+            .with_user_name("user")
+            .with_password(vec![3]);
+        let properties = Properties::new(vec![Property::RequestProblemInformation(1)]);
+        let bytes = Connect::new(payload, true, 10, properties).as_bytes();
+        for len in 0..bytes.len() {
+            assert!(parse_packet_bytes(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_packet_bytes_unknown_type() {
+        assert!(parse_packet_bytes(&[0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_parse_packet_bytes_error_location() {
+        // PUBACK with an unknown property identifier at byte 5
+        let bytes: &[u8] = &[0x40, 5, 0, 1, 0x10, 1, 0x04, 0x30];
+        let error = parse_packet_bytes(bytes).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::MalformedPacket {
+                packet_type: Some(PacketType::PUBACK),
+                offset: Some(6),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_packet_len() {
+        assert_eq!(packet_len(&[0x30, 0x80, 0x01]).unwrap(), 131);
+        assert!(packet_len(&[0x30]).is_err());
     }
 }
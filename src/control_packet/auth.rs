@@ -0,0 +1,55 @@
+use crate::common::{Byte, ParseError, Parseable};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::variable_header::reason_code::ReasonCodeVariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Auth {
+    fixed_header: FixedHeader,
+    variable_header: ReasonCodeVariableHeader,
+}
+
+impl ControlPacket for Auth {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = ReasonCodeVariableHeader;
+    fn get_variable_header(&self) -> &ReasonCodeVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) = parse_fixed_header(bytes, &PacketType::AUTH)?;
+        let (variable_header, leftover) =
+            ReasonCodeVariableHeader::from_bytes(variable_header_bytes, &PacketType::AUTH)?;
+        (&leftover[..]).parse_end()?;
+
+        Ok(Auth {
+            fixed_header,
+            variable_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::auth::{Auth, ControlPacket};
+
+    #[test]
+    fn test_auth_success_from_bytes_as_bytes() {
+        // the reason code and properties are left out when the reason is Success
+        let packet = Auth::from_bytes(&[0xF0, 0]).unwrap();
+        assert_eq!(packet.as_bytes(), vec![0xF0, 0]);
+    }
+
+    #[test]
+    fn test_auth_from_bytes_as_bytes() {
+        // Continue authentication with the reason string "foo"
+        let bytes = vec![0xF0, 8, 0x18, 6, 0x1F, 0, 3, b'f', b'o', b'o'];
+        let packet = Auth::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(packet.as_bytes(), bytes);
+    }
+}
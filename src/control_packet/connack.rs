@@ -0,0 +1,98 @@
+use crate::common::{Byte, ParseError, Parseable};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::connack::ConnackVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Connack {
+    fixed_header: FixedHeader,
+    variable_header: ConnackVariableHeader,
+}
+
+impl Connack {
+    pub(crate) fn new(
+        session_present: bool,
+        reason_code: ReasonCode,
+        properties: Properties,
+    ) -> Self {
+        let variable_header = ConnackVariableHeader::new(session_present, reason_code, properties);
+
+        let remaining_length: u32 = variable_header.len();
+        let fixed_header = FixedHeader::new(PacketType::CONNACK, remaining_length);
+
+        Connack {
+            fixed_header,
+            variable_header,
+        }
+    }
+
+    pub(crate) fn session_present(&self) -> bool {
+        self.variable_header.session_present()
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.variable_header.reason_code()
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        self.variable_header.properties()
+    }
+}
+
+impl ControlPacket for Connack {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = ConnackVariableHeader;
+    fn get_variable_header(&self) -> &ConnackVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::CONNACK)?;
+        let (variable_header, leftover) = ConnackVariableHeader::from_bytes(variable_header_bytes)?;
+        (&leftover[..]).parse_end()?;
+
+        Ok(Connack {
+            fixed_header,
+            variable_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::TwoByteInt;
+    use crate::control_packet::connack::{Connack, ControlPacket};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_connack_as_bytes() {
+        let packet = Connack::new(true, ReasonCode::Success, Properties::default());
+        assert_eq!(packet.as_bytes()[1..], vec![3, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_connack_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::ServerKeepAlive(TwoByteInt::new(30))]);
+        let packet = Connack::new(false, ReasonCode::NotAuthorized, properties);
+        let bytes = packet.as_bytes();
+        let parsed_packet = Connack::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert!(!parsed_packet.session_present());
+        assert_eq!(parsed_packet.reason_code(), ReasonCode::NotAuthorized);
+    }
+
+    #[test]
+    fn test_connack_from_bytes_left_over() {
+        assert!(Connack::from_bytes(&[0x20, 4, 0, 0, 0, 9]).is_err());
+    }
+}
@@ -1,27 +1,36 @@
-use crate::common::{Byte, ParseError, UTF8String};
-use crate::control_packet::ControlPacket;
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
 use crate::fixed_header::FixedHeader;
+use crate::payload::connect::{ConnectPayload, Will};
 use crate::payload::Payload;
+use crate::property::Properties;
+use crate::variable_header::connect::{ConnectFlags, ConnectVariableHeader};
 use crate::variable_header::VariableHeader;
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct Connect {
     fixed_header: FixedHeader,
-    variable_header: VariableHeader,
-    payload: Payload,
+    variable_header: ConnectVariableHeader,
+    payload: ConnectPayload,
 }
 
 impl Connect {
-    pub(crate) fn new(client_id: &str) -> Connect {
-        let values: Vec<UTF8String> = vec![UTF8String::new(client_id)];
-        let payload = Payload::new(values);
+    pub(crate) fn new(
+        payload: ConnectPayload,
+        clean_start: bool,
+        keep_alive: u16,
+        properties: Properties,
+    ) -> Connect {
+        let flags = ConnectFlags::new(
+            clean_start,
+            payload.will(),
+            payload.user_name().is_some(),
+            payload.password().is_some(),
+        );
+        let variable_header = ConnectVariableHeader::new(flags, keep_alive, properties);
 
-        let keep_alive = 0;
-        let variable_header = VariableHeader::new(keep_alive);
-
-        let packet_type_value = 1;
         let remaining_length: u32 = variable_header.len() + payload.len();
-        let fixed_header = FixedHeader::new(packet_type_value, remaining_length);
+        let fixed_header = FixedHeader::new(PacketType::CONNECT, remaining_length);
 
         Connect {
             fixed_header,
@@ -31,7 +40,31 @@ impl Connect {
     }
 
     pub(crate) fn client_id(&self) -> &str {
-        &self.payload.values()[0].value()
+        self.payload.client_id()
+    }
+
+    pub(crate) fn clean_start(&self) -> bool {
+        self.variable_header.flags().clean_start()
+    }
+
+    pub(crate) fn keep_alive(&self) -> u16 {
+        self.variable_header.keep_alive()
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        self.variable_header.properties()
+    }
+
+    pub(crate) fn will(&self) -> Option<&Will> {
+        self.payload.will()
+    }
+
+    pub(crate) fn user_name(&self) -> Option<&str> {
+        self.payload.user_name()
+    }
+
+    pub(crate) fn password(&self) -> Option<&[u8]> {
+        self.payload.password()
     }
 }
 
@@ -39,17 +72,20 @@ impl ControlPacket for Connect {
     fn get_fixed_header(&self) -> &FixedHeader {
         &self.fixed_header
     }
-    fn get_variable_header(&self) -> &VariableHeader {
+    type VariableHeader = ConnectVariableHeader;
+    fn get_variable_header(&self) -> &ConnectVariableHeader {
         &self.variable_header
     }
-    fn get_payload(&self) -> &Payload {
+    type Payload = ConnectPayload;
+    fn get_payload(&self) -> &ConnectPayload {
         &self.payload
     }
     fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
-        let byte_vec = Vec::from(bytes);
-        let (fixed_header, variable_header_bytes) = FixedHeader::from_bytes(byte_vec)?;
-        let (variable_header, payload_bytes) = VariableHeader::from_bytes(variable_header_bytes)?;
-        let payload = Payload::from_bytes(payload_bytes)?;
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::CONNECT)?;
+        let (variable_header, payload_bytes) =
+            ConnectVariableHeader::from_bytes(variable_header_bytes)?;
+        let payload = ConnectPayload::from_bytes(payload_bytes, variable_header.flags())?;
 
         Ok(Connect {
             fixed_header,
@@ -62,21 +98,59 @@ impl ControlPacket for Connect {
 #[cfg(test)]
 mod tests {
     use crate::control_packet::connect::{Connect, ControlPacket};
+    use crate::payload::connect::{ConnectPayload, Will};
+    use crate::property::Properties;
 
     const CLIENT_ID: &str = "foobar";
 
     #[test]
     fn test_client_id() {
-        let packet = Connect::new(CLIENT_ID);
+        let packet = Connect::new(
+            ConnectPayload::new(CLIENT_ID),
+            false,
+            0,
+            Properties::default(),
+        );
         assert_eq!(packet.client_id(), CLIENT_ID);
     }
 
     #[test]
     fn test_connect_as_bytes_from_bytes() {
-        let packet = Connect::new(CLIENT_ID);
+        let packet = Connect::new(
+            ConnectPayload::new(CLIENT_ID),
+            false,
+            0,
+            Properties::default(),
+        );
         let bytes = packet.as_bytes();
+        assert_eq!(bytes[0], 0x10);
         let byte_slice = &bytes[..];
         let parsed_packet = Connect::from_bytes(byte_slice).unwrap();
         assert_eq!(parsed_packet, packet);
     }
+
+    #[test]
+    fn test_connect_as_bytes_from_bytes_with_will_and_credentials() {
+        let will = Will::new(
+            "foo/status",
+            b"offline".to_vec(),
+            1,
+            true,
+            Properties::default(),
+        );
+        let payload = ConnectPayload::new(CLIENT_ID)
+            .with_will(will)
+            .with_user_name("user")
+            .with_password(b"secret".to_vec());
+        let packet = Connect::new(payload, true, 60, Properties::default());
+        let bytes = packet.as_bytes();
+        assert_eq!(bytes[9], 0b1110_1110);
+        let parsed_packet = Connect::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert!(parsed_packet.clean_start());
+        assert_eq!(parsed_packet.keep_alive(), 60);
+        assert_eq!(parsed_packet.will().unwrap().topic(), "foo/status");
+        assert_eq!(parsed_packet.user_name(), Some("user"));
+        assert_eq!(parsed_packet.password(), Some(&b"secret"[..]));
+    }
 }
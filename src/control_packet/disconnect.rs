@@ -0,0 +1,94 @@
+use crate::common::{Byte, ParseError, Parseable};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::reason_code::ReasonCodeVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Disconnect {
+    fixed_header: FixedHeader,
+    variable_header: ReasonCodeVariableHeader,
+}
+
+impl Disconnect {
+    pub(crate) fn new(reason_code: ReasonCode, properties: Properties) -> Self {
+        let variable_header = ReasonCodeVariableHeader::new(reason_code, properties);
+
+        let remaining_length: u32 = variable_header.len();
+        let fixed_header = FixedHeader::new(PacketType::DISCONNECT, remaining_length);
+
+        Disconnect {
+            fixed_header,
+            variable_header,
+        }
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.variable_header.reason_code()
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        self.variable_header.properties()
+    }
+}
+
+impl ControlPacket for Disconnect {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = ReasonCodeVariableHeader;
+    fn get_variable_header(&self) -> &ReasonCodeVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::DISCONNECT)?;
+        let (variable_header, leftover) =
+            ReasonCodeVariableHeader::from_bytes(variable_header_bytes, &PacketType::DISCONNECT)?;
+        (&leftover[..]).parse_end()?;
+
+        Ok(Disconnect {
+            fixed_header,
+            variable_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::UTF8String;
+    use crate::control_packet::disconnect::{ControlPacket, Disconnect};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_disconnect_as_bytes_success() {
+        let packet = Disconnect::new(ReasonCode::Success, Properties::default());
+        assert_eq!(packet.as_bytes()[1..], vec![0]);
+    }
+
+    #[test]
+    fn test_disconnect_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::ReasonString(UTF8String::new("foo"))]);
+        let packet = Disconnect::new(ReasonCode::DisconnectWithWillMessage, properties);
+        let bytes = packet.as_bytes();
+        let parsed_packet = Disconnect::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(
+            parsed_packet.reason_code(),
+            ReasonCode::DisconnectWithWillMessage
+        );
+    }
+
+    #[test]
+    fn test_disconnect_from_bytes_left_over() {
+        // an empty property length followed by a stray byte
+        assert!(Disconnect::from_bytes(&[0xE0, 3, 0x00, 0, 1]).is_err());
+    }
+}
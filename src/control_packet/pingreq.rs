@@ -0,0 +1,52 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Pingreq {
+    fixed_header: FixedHeader,
+}
+
+impl Pingreq {
+    pub(crate) fn new() -> Self {
+        let fixed_header = FixedHeader::new(PacketType::PINGREQ, 0);
+
+        Pingreq { fixed_header }
+    }
+}
+
+impl ControlPacket for Pingreq {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = ();
+    fn get_variable_header(&self) -> &() {
+        &()
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, _) = parse_fixed_header(bytes, &PacketType::PINGREQ)?;
+        if fixed_header.remaining_length() != 0 {
+            return Err(ParseError::new("pingreq with non-zero remaining length"));
+        }
+
+        Ok(Pingreq { fixed_header })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::pingreq::{ControlPacket, Pingreq};
+
+    #[test]
+    fn test_pingreq_as_bytes_from_bytes() {
+        let packet = Pingreq::new();
+        let bytes = packet.as_bytes();
+        assert_eq!(bytes.len(), 2);
+        let parsed_packet = Pingreq::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+    }
+}
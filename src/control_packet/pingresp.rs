@@ -0,0 +1,52 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Pingresp {
+    fixed_header: FixedHeader,
+}
+
+impl Pingresp {
+    pub(crate) fn new() -> Self {
+        let fixed_header = FixedHeader::new(PacketType::PINGRESP, 0);
+
+        Pingresp { fixed_header }
+    }
+}
+
+impl ControlPacket for Pingresp {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = ();
+    fn get_variable_header(&self) -> &() {
+        &()
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, _) = parse_fixed_header(bytes, &PacketType::PINGRESP)?;
+        if fixed_header.remaining_length() != 0 {
+            return Err(ParseError::new("pingresp with non-zero remaining length"));
+        }
+
+        Ok(Pingresp { fixed_header })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::pingresp::{ControlPacket, Pingresp};
+
+    #[test]
+    fn test_pingresp_as_bytes_from_bytes() {
+        let packet = Pingresp::new();
+        let bytes = packet.as_bytes();
+        assert_eq!(bytes.len(), 2);
+        let parsed_packet = Pingresp::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::common::{Byte, ParseError, Parseable};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::pub_response::PubResponseVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Puback {
+    fixed_header: FixedHeader,
+    variable_header: PubResponseVariableHeader,
+}
+
+impl Puback {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        reason_code: ReasonCode,
+        properties: Properties,
+    ) -> Self {
+        let variable_header =
+            PubResponseVariableHeader::new(packet_identifier, reason_code, properties);
+
+        let remaining_length: u32 = variable_header.len();
+        let fixed_header = FixedHeader::new(PacketType::PUBACK, remaining_length);
+
+        Puback {
+            fixed_header,
+            variable_header,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.variable_header.reason_code()
+    }
+}
+
+impl ControlPacket for Puback {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PubResponseVariableHeader;
+    fn get_variable_header(&self) -> &PubResponseVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) = parse_fixed_header(bytes, &PacketType::PUBACK)?;
+        let (variable_header, leftover) =
+            PubResponseVariableHeader::from_bytes(variable_header_bytes, &PacketType::PUBACK)?;
+        (&leftover[..]).parse_end()?;
+
+        Ok(Puback {
+            fixed_header,
+            variable_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::UTF8String;
+    use crate::control_packet::puback::{ControlPacket, Puback};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_puback_as_bytes_success() {
+        let packet = Puback::new(7, ReasonCode::Success, Properties::default());
+        assert_eq!(packet.as_bytes()[1..], vec![2, 0, 7]);
+    }
+
+    #[test]
+    fn test_puback_from_bytes_reason_code_only() {
+        let bytes: &[u8] = &[0x40, 3, 0, 7, 0x10];
+        let parsed_packet = Puback::from_bytes(bytes).unwrap();
+        assert_eq!(
+            parsed_packet,
+            Puback::new(7, ReasonCode::NoMatchingSubscribers, Properties::default())
+        );
+    }
+
+    #[test]
+    fn test_puback_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::ReasonString(UTF8String::new("foo"))]);
+        let packet = Puback::new(7, ReasonCode::NoMatchingSubscribers, properties);
+        let bytes = packet.as_bytes();
+        let parsed_packet = Puback::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(parsed_packet.packet_identifier(), 7);
+        assert_eq!(
+            parsed_packet.reason_code(),
+            ReasonCode::NoMatchingSubscribers
+        );
+    }
+
+    #[test]
+    fn test_puback_from_bytes_wrong_packet_type() {
+        let bytes: &[u8] = &[0x50, 2, 0, 7];
+        assert!(Puback::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn test_puback_from_bytes_left_over() {
+        assert!(Puback::from_bytes(&[0x40, 5, 0, 7, 0x10, 0, 0xFF]).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::common::{Byte, ParseError, Parseable};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::pub_response::PubResponseVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Pubcomp {
+    fixed_header: FixedHeader,
+    variable_header: PubResponseVariableHeader,
+}
+
+impl Pubcomp {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        reason_code: ReasonCode,
+        properties: Properties,
+    ) -> Self {
+        let variable_header =
+            PubResponseVariableHeader::new(packet_identifier, reason_code, properties);
+
+        let remaining_length: u32 = variable_header.len();
+        let fixed_header = FixedHeader::new(PacketType::PUBCOMP, remaining_length);
+
+        Pubcomp {
+            fixed_header,
+            variable_header,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.variable_header.reason_code()
+    }
+}
+
+impl ControlPacket for Pubcomp {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PubResponseVariableHeader;
+    fn get_variable_header(&self) -> &PubResponseVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ();
+    fn get_payload(&self) -> &() {
+        &()
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::PUBCOMP)?;
+        let (variable_header, leftover) =
+            PubResponseVariableHeader::from_bytes(variable_header_bytes, &PacketType::PUBCOMP)?;
+        (&leftover[..]).parse_end()?;
+
+        Ok(Pubcomp {
+            fixed_header,
+            variable_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::UTF8String;
+    use crate::control_packet::pubcomp::{ControlPacket, Pubcomp};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_pubcomp_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::ReasonString(UTF8String::new("foo"))]);
+        let packet = Pubcomp::new(7, ReasonCode::PacketIdentifierNotFound, properties);
+        let bytes = packet.as_bytes();
+        let parsed_packet = Pubcomp::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(parsed_packet.packet_identifier(), 7);
+        assert_eq!(
+            parsed_packet.reason_code(),
+            ReasonCode::PacketIdentifierNotFound
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::common::{Byte, Bytes, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::payload::publish::PublishPayload;
+use crate::payload::Payload;
+use crate::property::Properties;
+use crate::variable_header::publish::PublishVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Publish {
+    fixed_header: FixedHeader,
+    variable_header: PublishVariableHeader,
+    payload: PublishPayload,
+}
+
+impl Publish {
+    pub(crate) fn new(
+        topic_name: &str,
+        packet_identifier: Option<u16>,
+        payload: Bytes,
+        qos: u8,
+        retain: bool,
+        properties: Properties,
+    ) -> Self {
+        let variable_header = PublishVariableHeader::new(topic_name, packet_identifier, properties);
+        let payload = PublishPayload::new(payload);
+
+        let remaining_length: u32 = variable_header.len() + payload.len();
+        let fixed_header = FixedHeader::publish(false, qos, retain, remaining_length);
+
+        Publish {
+            fixed_header,
+            variable_header,
+            payload,
+        }
+    }
+
+    // set when the packet is sent again for a session that was resumed
+    pub(crate) fn with_dup(mut self, dup: bool) -> Self {
+        self.fixed_header = FixedHeader::publish(
+            dup,
+            self.qos(),
+            self.retain(),
+            self.fixed_header.remaining_length(),
+        );
+        self
+    }
+
+    // set when a stored message goes out with an identifier from the receiving session
+    pub(crate) fn with_packet_identifier(self, packet_identifier: Option<u16>) -> Self {
+        let variable_header = PublishVariableHeader::new(
+            self.topic_name(),
+            packet_identifier,
+            self.properties().clone(),
+        );
+        let remaining_length: u32 = variable_header.len() + self.payload.len();
+        let fixed_header =
+            FixedHeader::publish(self.dup(), self.qos(), self.retain(), remaining_length);
+        Publish {
+            fixed_header,
+            variable_header,
+            payload: self.payload,
+        }
+    }
+
+    pub(crate) fn topic_name(&self) -> &str {
+        self.variable_header.topic_name()
+    }
+
+    pub(crate) fn packet_identifier(&self) -> Option<u16> {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn payload(&self) -> &[Byte] {
+        self.payload.data()
+    }
+
+    pub(crate) fn qos(&self) -> u8 {
+        self.fixed_header.qos()
+    }
+
+    pub(crate) fn retain(&self) -> bool {
+        self.fixed_header.retain()
+    }
+
+    pub(crate) fn dup(&self) -> bool {
+        self.fixed_header.dup()
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        self.variable_header.properties()
+    }
+}
+
+impl ControlPacket for Publish {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PublishVariableHeader;
+    fn get_variable_header(&self) -> &PublishVariableHeader {
+        &self.variable_header
+    }
+    type Payload = PublishPayload;
+    fn get_payload(&self) -> &PublishPayload {
+        &self.payload
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::PUBLISH)?;
+        let (variable_header, payload_bytes) =
+            PublishVariableHeader::from_bytes(variable_header_bytes, fixed_header.qos())?;
+        let payload = PublishPayload::from_bytes(payload_bytes);
+
+        Ok(Publish {
+            fixed_header,
+            variable_header,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::publish::{ControlPacket, Publish};
+    use crate::property::{Properties, Property};
+
+    const TOPIC: &str = "foo/bar";
+
+    #[test]
+    fn test_publish_as_bytes() {
+        let packet = Publish::new(TOPIC, None, vec![1, 2], 0, true, Properties::default());
+        let expected = vec![0x31, 12, 0, 7, 102, 111, 111, 47, 98, 97, 114, 0, 1, 2];
+        assert_eq!(packet.as_bytes(), expected);
+    }
+
+    #[test]
+    fn test_publish_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::PayloadFormatIndicator(1)]);
+        let packet = Publish::new(TOPIC, Some(10), b"hello".to_vec(), 2, false, properties);
+        let bytes = packet.as_bytes();
+        let parsed_packet = Publish::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(parsed_packet.topic_name(), TOPIC);
+        assert_eq!(parsed_packet.packet_identifier(), Some(10));
+        assert_eq!(parsed_packet.payload(), b"hello");
+        assert_eq!(parsed_packet.qos(), 2);
+    }
+
+    #[test]
+    fn test_publish_as_bytes_from_bytes_non_utf8_payload() {
+        let payload = vec![0xC3, 0x28, 0xFF, 0x00];
+        let packet = Publish::new(
+            TOPIC,
+            Some(1),
+            payload.clone(),
+            1,
+            false,
+            Properties::default(),
+        );
+        let bytes = packet.as_bytes();
+        assert_eq!(bytes[bytes.len() - 4..], payload);
+        let parsed_packet = Publish::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet.payload(), &payload[..]);
+    }
+
+    #[test]
+    fn test_publish_with_dup() {
+        let packet = Publish::new(TOPIC, Some(1), vec![1], 1, true, Properties::default());
+        let bytes = packet.with_dup(true).as_bytes();
+        assert_eq!(bytes[0], 0x3B);
+        let parsed_packet = Publish::from_bytes(&bytes[..]).unwrap();
+        assert!(parsed_packet.dup());
+        assert!(parsed_packet.retain());
+    }
+
+    #[test]
+    fn test_publish_with_packet_identifier() {
+        let packet = Publish::new(TOPIC, None, vec![1], 1, true, Properties::default());
+        assert_eq!(
+            packet.with_packet_identifier(Some(5)),
+            Publish::new(TOPIC, Some(5), vec![1], 1, true, Properties::default())
+        );
+    }
+}
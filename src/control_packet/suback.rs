@@ -0,0 +1,103 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::payload::reason_codes::ReasonCodesPayload;
+use crate::payload::Payload;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::packet_identifier::PacketIdentifierVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Suback {
+    fixed_header: FixedHeader,
+    variable_header: PacketIdentifierVariableHeader,
+    payload: ReasonCodesPayload,
+}
+
+impl Suback {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        reason_codes: Vec<ReasonCode>,
+        properties: Properties,
+    ) -> Self {
+        let variable_header = PacketIdentifierVariableHeader::new(packet_identifier, properties);
+        let payload = ReasonCodesPayload::new(reason_codes);
+
+        let remaining_length: u32 = variable_header.len() + payload.len();
+        let fixed_header = FixedHeader::new(PacketType::SUBACK, remaining_length);
+
+        Suback {
+            fixed_header,
+            variable_header,
+            payload,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn reason_codes(&self) -> &[ReasonCode] {
+        self.payload.reason_codes()
+    }
+}
+
+impl ControlPacket for Suback {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PacketIdentifierVariableHeader;
+    fn get_variable_header(&self) -> &PacketIdentifierVariableHeader {
+        &self.variable_header
+    }
+    type Payload = ReasonCodesPayload;
+    fn get_payload(&self) -> &ReasonCodesPayload {
+        &self.payload
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) = parse_fixed_header(bytes, &PacketType::SUBACK)?;
+        let (variable_header, payload_bytes) =
+            PacketIdentifierVariableHeader::from_bytes(variable_header_bytes, &PacketType::SUBACK)?;
+        let payload = ReasonCodesPayload::from_bytes(payload_bytes, &PacketType::SUBACK)?;
+
+        Ok(Suback {
+            fixed_header,
+            variable_header,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::suback::{ControlPacket, Suback};
+    use crate::property::Properties;
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_suback_as_bytes() {
+        let packet = Suback::new(
+            3,
+            vec![ReasonCode::GrantedQoS1, ReasonCode::UnspecifiedError],
+            Properties::default(),
+        );
+        assert_eq!(packet.as_bytes()[1..], vec![5, 0, 3, 0, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn test_suback_as_bytes_from_bytes() {
+        let packet = Suback::new(
+            3,
+            vec![ReasonCode::GrantedQoS1, ReasonCode::UnspecifiedError],
+            Properties::default(),
+        );
+        let bytes = packet.as_bytes();
+        let parsed_packet = Suback::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(
+            parsed_packet.reason_codes(),
+            &[ReasonCode::GrantedQoS1, ReasonCode::UnspecifiedError]
+        );
+    }
+}
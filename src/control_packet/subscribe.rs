@@ -0,0 +1,106 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::payload::subscribe::{SubscribePayload, Subscription};
+use crate::payload::Payload;
+use crate::property::Properties;
+use crate::variable_header::packet_identifier::PacketIdentifierVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Subscribe {
+    fixed_header: FixedHeader,
+    variable_header: PacketIdentifierVariableHeader,
+    payload: SubscribePayload,
+}
+
+impl Subscribe {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        subscriptions: Vec<Subscription>,
+        properties: Properties,
+    ) -> Self {
+        let variable_header = PacketIdentifierVariableHeader::new(packet_identifier, properties);
+        let payload = SubscribePayload::new(subscriptions);
+
+        let remaining_length: u32 = variable_header.len() + payload.len();
+        let fixed_header = FixedHeader::new(PacketType::SUBSCRIBE, remaining_length);
+
+        Subscribe {
+            fixed_header,
+            variable_header,
+            payload,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn subscriptions(&self) -> &[Subscription] {
+        self.payload.subscriptions()
+    }
+}
+
+impl ControlPacket for Subscribe {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PacketIdentifierVariableHeader;
+    fn get_variable_header(&self) -> &PacketIdentifierVariableHeader {
+        &self.variable_header
+    }
+    type Payload = SubscribePayload;
+    fn get_payload(&self) -> &SubscribePayload {
+        &self.payload
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::SUBSCRIBE)?;
+        let (variable_header, payload_bytes) = PacketIdentifierVariableHeader::from_bytes(
+            variable_header_bytes,
+            &PacketType::SUBSCRIBE,
+        )?;
+        let payload = SubscribePayload::from_bytes(payload_bytes)?;
+
+        Ok(Subscribe {
+            fixed_header,
+            variable_header,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::subscribe::{ControlPacket, Subscribe};
+    use crate::payload::subscribe::{Subscription, SubscriptionOptions};
+    use crate::property::Properties;
+
+    #[test]
+    fn test_subscribe_as_bytes_from_bytes() {
+        let subscriptions = vec![
+            Subscription::new("foo/#", SubscriptionOptions::new(1)),
+            Subscription::new(
+                "bar/+",
+                SubscriptionOptions::from_byte(0b0001_0010).unwrap(),
+            ),
+        ];
+        let packet = Subscribe::new(3, subscriptions, Properties::default());
+        let bytes = packet.as_bytes();
+        let parsed_packet = Subscribe::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(parsed_packet.packet_identifier(), 3);
+        assert_eq!(parsed_packet.subscriptions()[1].topic_filter(), "bar/+");
+        assert_eq!(
+            parsed_packet.subscriptions()[1].options().retain_handling(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_subscribe_from_bytes_no_topic_filters() {
+        let bytes: &[u8] = &[0x82, 3, 0, 3, 0];
+        assert!(Subscribe::from_bytes(bytes).is_err());
+    }
+}
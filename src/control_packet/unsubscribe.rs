@@ -0,0 +1,88 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::{parse_fixed_header, ControlPacket, PacketType};
+use crate::fixed_header::FixedHeader;
+use crate::payload::unsubscribe::UnsubscribePayload;
+use crate::payload::Payload;
+use crate::property::Properties;
+use crate::variable_header::packet_identifier::PacketIdentifierVariableHeader;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Unsubscribe {
+    fixed_header: FixedHeader,
+    variable_header: PacketIdentifierVariableHeader,
+    payload: UnsubscribePayload,
+}
+
+impl Unsubscribe {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        topic_filters: &[&str],
+        properties: Properties,
+    ) -> Self {
+        let variable_header = PacketIdentifierVariableHeader::new(packet_identifier, properties);
+        let payload = UnsubscribePayload::new(topic_filters);
+
+        let remaining_length: u32 = variable_header.len() + payload.len();
+        let fixed_header = FixedHeader::new(PacketType::UNSUBSCRIBE, remaining_length);
+
+        Unsubscribe {
+            fixed_header,
+            variable_header,
+            payload,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.variable_header.packet_identifier()
+    }
+
+    pub(crate) fn topic_filters(&self) -> Vec<&str> {
+        self.payload.topic_filters()
+    }
+}
+
+impl ControlPacket for Unsubscribe {
+    fn get_fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+    type VariableHeader = PacketIdentifierVariableHeader;
+    fn get_variable_header(&self) -> &PacketIdentifierVariableHeader {
+        &self.variable_header
+    }
+    type Payload = UnsubscribePayload;
+    fn get_payload(&self) -> &UnsubscribePayload {
+        &self.payload
+    }
+    fn from_bytes(bytes: &[Byte]) -> Result<Self, ParseError> {
+        let (fixed_header, variable_header_bytes) =
+            parse_fixed_header(bytes, &PacketType::UNSUBSCRIBE)?;
+        let (variable_header, payload_bytes) = PacketIdentifierVariableHeader::from_bytes(
+            variable_header_bytes,
+            &PacketType::UNSUBSCRIBE,
+        )?;
+        let payload = UnsubscribePayload::from_bytes(payload_bytes)?;
+
+        Ok(Unsubscribe {
+            fixed_header,
+            variable_header,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::unsubscribe::{ControlPacket, Unsubscribe};
+    use crate::property::Properties;
+
+    #[test]
+    fn test_unsubscribe_as_bytes_from_bytes() {
+        let packet = Unsubscribe::new(9, &["foo/#", "bar"], Properties::default());
+        let bytes = packet.as_bytes();
+        let parsed_packet = Unsubscribe::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed_packet, packet);
+        assert_eq!(parsed_packet.packet_identifier(), 9);
+        assert_eq!(parsed_packet.topic_filters(), vec!["foo/#", "bar"]);
+    }
+}
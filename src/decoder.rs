@@ -0,0 +1,259 @@
+use crate::common::{decode_variable_length_int, Byte, Bytes};
+use crate::control_packet::{parse_packet_bytes, Packet, PacketType};
+use crate::error::Error;
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::time::Instant;
+
+/// Largest packet MQTT can express: a 268,435,455 byte remaining length behind a five byte
+/// fixed header.
+pub(crate) const MAXIMUM_PACKET_SIZE: usize = 268_435_460;
+
+const READ_CHUNK_SIZE: usize = 4096;
+
+// Reassembles packets from a byte stream, which may split a packet across several reads or
+// deliver several packets in one. An error leaves the stream position unknown, so the
+// connection should be closed afterwards.
+#[derive(Debug)]
+pub(crate) struct Decoder {
+    buffer: Bytes,
+    maximum_packet_size: usize,
+}
+
+impl Decoder {
+    pub(crate) fn new(maximum_packet_size: usize) -> Self {
+        Decoder {
+            buffer: Vec::new(),
+            maximum_packet_size,
+        }
+    }
+
+    pub(crate) fn push(&mut self, bytes: &[Byte]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete packet off the buffer, or `None` if more bytes are needed.
+    pub(crate) fn next_packet(&mut self) -> Result<Option<Packet>, Error> {
+        let packet_len = match self.packet_len()? {
+            Some(packet_len) => packet_len,
+            None => return Ok(None),
+        };
+        // checked as soon as the header is in, so an oversized packet is never buffered whole
+        if packet_len > self.maximum_packet_size {
+            return Err(Error::PacketTooLarge {
+                size: packet_len,
+                maximum: self.maximum_packet_size,
+            });
+        }
+        if self.buffer.len() < packet_len {
+            return Ok(None);
+        }
+        let leftover = self.buffer.split_off(packet_len);
+        let packet_bytes = std::mem::replace(&mut self.buffer, leftover);
+        parse_packet_bytes(&packet_bytes).map(Some)
+    }
+
+    /// Reads from `reader` until a complete packet is available.
+    pub(crate) fn read_packet<R: Read>(&mut self, reader: &mut R) -> Result<Packet, Error> {
+        self.read_packet_with(reader, |_| Ok(()))
+    }
+
+    /// Like `read_packet`, but gives up with `Error::Timeout` at `deadline`, however slowly
+    /// the packet trickles in. The stream is left with whatever read timeout was set last.
+    pub(crate) fn read_packet_before(
+        &mut self,
+        stream: &mut TcpStream,
+        deadline: Instant,
+    ) -> Result<Packet, Error> {
+        self.read_packet_with(stream, |stream| {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            stream.set_read_timeout(Some(timeout))
+        })
+    }
+
+    // `before_read` runs before every read, so a caller can adjust the reader
+    fn read_packet_with<R, F>(
+        &mut self,
+        reader: &mut R,
+        mut before_read: F,
+    ) -> Result<Packet, Error>
+    where
+        R: Read,
+        F: FnMut(&mut R) -> io::Result<()>,
+    {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            if let Some(packet) = self.next_packet()? {
+                return Ok(packet);
+            }
+            before_read(reader)?;
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+            self.push(&chunk[..read]);
+        }
+    }
+
+    // length of the packet at the front of the buffer, once its remaining length is complete
+    fn packet_len(&self) -> Result<Option<usize>, Error> {
+        let length_bytes = match self.buffer.get(1..) {
+            Some(length_bytes) => length_bytes,
+            None => return Ok(None),
+        };
+        if length_bytes.len() < 4 && length_bytes.iter().all(|byte| byte & 128 != 0) {
+            return Ok(None);
+        }
+        let (remaining_length, length_len) =
+            decode_variable_length_int(length_bytes).map_err(|error| {
+                let packet_type = PacketType::from_value(self.buffer[0] >> 4).ok();
+                Error::from_parse_error(error, packet_type, self.buffer.len())
+            })?;
+        Ok(Some(1 + length_len + remaining_length as usize))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(MAXIMUM_PACKET_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::pingreq::Pingreq;
+    use crate::control_packet::publish::Publish;
+    use crate::control_packet::{ControlPacket, Packet};
+    use crate::decoder::Decoder;
+    use crate::error::Error;
+    use crate::property::Properties;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    fn publish(payload_len: usize) -> Publish {
+        Publish::new(
+            "foo/bar",
+            Some(1),
+            vec![7; payload_len],
+            1,
+            false,
+            Properties::default(),
+        )
+    }
+
+    #[test]
+    fn test_next_packet_byte_by_byte() {
+        let packet = publish(200);
+        let bytes = packet.as_bytes();
+        let mut decoder = Decoder::default();
+        for byte in &bytes[..bytes.len() - 1] {
+            decoder.push(&[*byte]);
+            assert!(decoder.next_packet().unwrap().is_none());
+        }
+        decoder.push(&bytes[bytes.len() - 1..]);
+        assert_eq!(
+            decoder.next_packet().unwrap(),
+            Some(Packet::Publish(packet))
+        );
+        assert_eq!(decoder.buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_next_packet_coalesced() {
+        let mut bytes = publish(3).as_bytes();
+        bytes.append(&mut Pingreq::new().as_bytes());
+        bytes.append(&mut publish(5).as_bytes()[..4].to_vec());
+        let mut decoder = Decoder::default();
+        decoder.push(&bytes);
+        assert_eq!(
+            decoder.next_packet().unwrap(),
+            Some(Packet::Publish(publish(3)))
+        );
+        assert_eq!(
+            decoder.next_packet().unwrap(),
+            Some(Packet::Pingreq(Pingreq::new()))
+        );
+        assert!(decoder.next_packet().unwrap().is_none());
+        assert_eq!(decoder.buffer.len(), 4);
+    }
+
+    #[test]
+    fn test_next_packet_too_large() {
+        let mut decoder = Decoder::new(100);
+        // only the fixed header has arrived
+        decoder.push(&[0x30, 0x80, 0x01]);
+        let error = decoder.next_packet().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::PacketTooLarge {
+                size: 131,
+                maximum: 100
+            }
+        ));
+    }
+
+    #[test]
+    fn test_next_packet_malformed_remaining_length() {
+        let mut decoder = Decoder::default();
+        decoder.push(&[0x30, 0xFF, 0xFF, 0xFF]);
+        assert!(decoder.next_packet().unwrap().is_none());
+        decoder.push(&[0xFF]);
+        assert!(matches!(
+            decoder.next_packet().unwrap_err(),
+            Error::MalformedPacket {
+                offset: Some(1),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_read_packet_before() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server_stream, _) = listener.accept().unwrap();
+        let bytes = Pingreq::new().as_bytes();
+        // half a packet never completes
+        server_stream.write_all(&bytes[..1]).unwrap();
+        let mut decoder = Decoder::default();
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(matches!(
+            decoder
+                .read_packet_before(&mut stream, deadline)
+                .unwrap_err(),
+            Error::Timeout
+        ));
+        server_stream.write_all(&bytes[1..]).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(
+            decoder.read_packet_before(&mut stream, deadline).unwrap(),
+            Packet::Pingreq(Pingreq::new())
+        );
+    }
+
+    #[test]
+    fn test_read_packet() {
+        let mut bytes = publish(5000).as_bytes();
+        bytes.append(&mut Pingreq::new().as_bytes());
+        let mut reader = &bytes[..];
+        let mut decoder = Decoder::default();
+        assert_eq!(
+            decoder.read_packet(&mut reader).unwrap(),
+            Packet::Publish(publish(5000))
+        );
+        assert_eq!(
+            decoder.read_packet(&mut reader).unwrap(),
+            Packet::Pingreq(Pingreq::new())
+        );
+        assert!(matches!(
+            decoder.read_packet(&mut reader).unwrap_err(),
+            Error::Io(_)
+        ));
+    }
+}
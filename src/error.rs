@@ -0,0 +1,197 @@
+use crate::common::{ParseError, ParseErrorKind};
+use crate::control_packet::PacketType;
+use crate::reason_code::ReasonCode;
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    MalformedPacket {
+        packet_type: Option<PacketType>,
+        offset: Option<usize>,
+        message: &'static str,
+    },
+    ProtocolError {
+        packet_type: Option<PacketType>,
+        offset: Option<usize>,
+        message: &'static str,
+    },
+    UnsupportedProtocolVersion(u8),
+    PacketTooLarge {
+        size: usize,
+        maximum: usize,
+    },
+    ConnectionRefused(ReasonCode),
+    SubscriptionRejected(ReasonCode),
+    /// The server sent a DISCONNECT with this reason code.
+    Disconnected(ReasonCode),
+    InvalidArgument(&'static str),
+    PacketIdentifiersExhausted,
+    Io(io::Error),
+    Timeout,
+}
+
+impl Error {
+    // offsets are counted from the first byte of the fixed header
+    pub(crate) fn from_parse_error(
+        error: ParseError,
+        packet_type: Option<PacketType>,
+        packet_len: usize,
+    ) -> Self {
+        let offset = error
+            .remaining()
+            .map(|remaining| packet_len.saturating_sub(remaining));
+        match error.kind() {
+            ParseErrorKind::MalformedPacket => Error::MalformedPacket {
+                packet_type,
+                offset,
+                message: error.message(),
+            },
+            ParseErrorKind::ProtocolError => Error::ProtocolError {
+                packet_type,
+                offset,
+                message: error.message(),
+            },
+            ParseErrorKind::UnsupportedProtocolVersion(version) => {
+                Error::UnsupportedProtocolVersion(version)
+            }
+        }
+    }
+
+    /// The MQTT 5 reason code to send in a CONNACK or DISCONNECT before closing the connection.
+    pub fn reason_code(&self) -> ReasonCode {
+        match self {
+            Error::MalformedPacket { .. } => ReasonCode::MalformedPacket,
+            Error::ProtocolError { .. } => ReasonCode::ProtocolError,
+            Error::UnsupportedProtocolVersion(_) => ReasonCode::UnsupportedProtocolVersion,
+            Error::PacketTooLarge { .. } => ReasonCode::PacketTooLarge,
+            Error::ConnectionRefused(reason_code)
+            | Error::SubscriptionRejected(reason_code)
+            | Error::Disconnected(reason_code) => *reason_code,
+            Error::InvalidArgument(_) | Error::PacketIdentifiersExhausted | Error::Io(_) => {
+                ReasonCode::UnspecifiedError
+            }
+            Error::Timeout => ReasonCode::KeepAliveTimeout,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MalformedPacket {
+                packet_type,
+                offset,
+                message,
+            } => {
+                write!(f, "Malformed packet: {message}")?;
+                write_location(f, packet_type, offset)
+            }
+            Error::ProtocolError {
+                packet_type,
+                offset,
+                message,
+            } => {
+                write!(f, "Protocol error: {message}")?;
+                write_location(f, packet_type, offset)
+            }
+            Error::UnsupportedProtocolVersion(version) => {
+                write!(f, "Unsupported protocol version: {version}")
+            }
+            Error::PacketTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Packet too large: {size} bytes exceeds maximum of {maximum}"
+                )
+            }
+            Error::ConnectionRefused(reason_code) => {
+                write!(f, "Connection refused: {reason_code}")
+            }
+            Error::SubscriptionRejected(reason_code) => {
+                write!(f, "Subscription rejected: {reason_code}")
+            }
+            Error::Disconnected(reason_code) => {
+                write!(f, "Disconnected by the server: {reason_code}")
+            }
+            Error::InvalidArgument(message) => write!(f, "Invalid argument: {message}"),
+            Error::PacketIdentifiersExhausted => write!(f, "No packet identifier available"),
+            Error::Io(error) => write!(f, "I/O error: {error}"),
+            Error::Timeout => write!(f, "Timed out"),
+        }
+    }
+}
+
+fn write_location(
+    f: &mut fmt::Formatter,
+    packet_type: &Option<PacketType>,
+    offset: &Option<usize>,
+) -> fmt::Result {
+    if let Some(packet_type) = packet_type {
+        write!(f, " in {packet_type:?}")?;
+    }
+    if let Some(offset) = offset {
+        write!(f, " at byte {offset}")?;
+    }
+    Ok(())
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Error::Timeout,
+            _ => Error::Io(error),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::from_parse_error(error, None, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::ParseError;
+    use crate::control_packet::PacketType;
+    use crate::error::Error;
+    use crate::reason_code::ReasonCode;
+    use std::io;
+
+    #[test]
+    fn test_from_parse_error() {
+        let error = ParseError::protocol("foo").at(3);
+        let error = Error::from_parse_error(error, Some(PacketType::CONNECT), 10);
+        assert!(matches!(
+            error,
+            Error::ProtocolError {
+                packet_type: Some(PacketType::CONNECT),
+                offset: Some(7),
+                message: "foo",
+            }
+        ));
+        assert_eq!(error.reason_code(), ReasonCode::ProtocolError);
+    }
+
+    #[test]
+    fn test_from_io_error() {
+        let error = Error::from(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(error, Error::Timeout));
+        let error = Error::from(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!(error.reason_code(), ReasonCode::UnspecifiedError);
+    }
+
+    #[test]
+    fn test_display() {
+        let error = Error::from_parse_error(ParseError::new("foo").at(1), None, 4);
+        assert_eq!(error.to_string(), "Malformed packet: foo at byte 3");
+    }
+}
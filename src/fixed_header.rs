@@ -1,8 +1,15 @@
-use crate::common::{Bytes, ParseError, Parseable, Serializable, VariableByteInt};
+use crate::common::{Byte, Bytes, ParseError, Parseable, Serializable, VariableByteInt};
+use crate::control_packet::PacketType;
 
-#[derive(Debug, PartialEq)]
+const DUP: Byte = 0b1000;
+const QOS: Byte = 0b0110;
+const RETAIN: Byte = 0b0001;
+
+// dup, qos and retain only carry meaning for PUBLISH; every other packet type has a fixed
+// flags nibble
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct FixedHeader {
-    packet_type_value: u8,
+    packet_type: PacketType,
     dup: bool,
     qos: u8,
     retain: bool,
@@ -10,29 +17,56 @@ pub(crate) struct FixedHeader {
 }
 
 impl FixedHeader {
-    pub(crate) fn new(packet_type_value: u8, remaining_length: u32) -> Self {
+    pub(crate) fn new(packet_type: PacketType, remaining_length: u32) -> Self {
         FixedHeader {
-            packet_type_value,
+            packet_type,
             dup: false,
-            qos: 1,
+            qos: 0,
             retain: false,
             remaining_length,
         }
     }
 
-    fn flags_byte(&self) -> u8 {
-        let mut flags: u8 = 0;
-        if self.retain {
-            flags += 1
+    pub(crate) fn publish(dup: bool, qos: u8, retain: bool, remaining_length: u32) -> Self {
+        FixedHeader {
+            packet_type: PacketType::PUBLISH,
+            dup,
+            qos,
+            retain,
+            remaining_length,
         }
-        if self.qos == 1 {
-            flags += 2
+    }
+
+    pub(crate) fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    pub(crate) fn dup(&self) -> bool {
+        self.dup
+    }
+
+    pub(crate) fn qos(&self) -> u8 {
+        self.qos
+    }
+
+    pub(crate) fn retain(&self) -> bool {
+        self.retain
+    }
+
+    pub(crate) fn remaining_length(&self) -> u32 {
+        self.remaining_length
+    }
+
+    fn flags_byte(&self) -> Byte {
+        if self.packet_type != PacketType::PUBLISH {
+            return reserved_flags(&self.packet_type);
         }
-        if self.qos == 2 {
-            flags += 4
+        let mut flags: Byte = (self.qos << 1) & QOS;
+        if self.retain {
+            flags |= RETAIN;
         }
         if self.dup {
-            flags += 8
+            flags |= DUP;
         }
         flags
     }
@@ -44,60 +78,120 @@ impl FixedHeader {
     pub(crate) fn from_bytes(bytes: Bytes) -> Result<(Self, Bytes), ParseError> {
         let byte_slice = &bytes[..];
         let (first_byte, first_byte_leftover) = byte_slice.parse_byte()?;
-        let packet_type_value: u8 = first_byte >> 4;
-        //TODO dup/qos/retain bits
+        let packet_type =
+            PacketType::from_value(first_byte >> 4).map_err(|error| error.at(byte_slice.len()))?;
+        let flags = first_byte & 0b1111;
         let (remaining_length, leftover) = first_byte_leftover.parse_variable_byte_int()?;
-        let fixed_header = FixedHeader::new(packet_type_value, remaining_length.value());
+        let fixed_header = if packet_type == PacketType::PUBLISH {
+            let dup = flags & DUP != 0;
+            let qos = (flags & QOS) >> 1;
+            if qos > 2 {
+                return Err(ParseError::new("publish qos of 3").at(byte_slice.len()));
+            }
+            if dup && qos == 0 {
+                return Err(ParseError::protocol("dup set on a qos 0 publish").at(byte_slice.len()));
+            }
+            FixedHeader::publish(dup, qos, flags & RETAIN != 0, remaining_length.value())
+        } else {
+            if flags != reserved_flags(&packet_type) {
+                return Err(ParseError::new("invalid fixed header flags").at(byte_slice.len()));
+            }
+            FixedHeader::new(packet_type, remaining_length.value())
+        };
         Ok((fixed_header, Vec::from(leftover)))
     }
 
     pub(crate) fn as_bytes(&self) -> Bytes {
-        let mut bytes: Bytes = vec![(self.packet_type_value << 4) + self.flags_byte()];
+        let mut bytes: Bytes = vec![(self.packet_type.value() << 4) | self.flags_byte()];
         bytes.append(&mut self.remaining_length_bytes());
         bytes
     }
+}
 
-    pub(crate) fn len(&self) -> u32 {
-        self.as_bytes().len() as u32
+// the flags nibble every packet type other than PUBLISH must carry
+fn reserved_flags(packet_type: &PacketType) -> Byte {
+    match packet_type {
+        PacketType::PUBREL | PacketType::SUBSCRIBE | PacketType::UNSUBSCRIBE => 0b0010,
+        _ => 0,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::Bytes;
+    use crate::common::{Bytes, ParseErrorKind};
+    use crate::control_packet::PacketType;
     use crate::fixed_header::FixedHeader;
 
-    const PACKET_TYPE_VALUE: u8 = 1;
     const REMAINING_LENGTH: u32 = 3;
 
     #[test]
     fn test_as_bytes() {
-        let fixed_header = FixedHeader::new(PACKET_TYPE_VALUE, REMAINING_LENGTH);
+        let fixed_header = FixedHeader::new(PacketType::CONNECT, REMAINING_LENGTH);
         let bytes = fixed_header.as_bytes();
-        assert_eq!(bytes, vec![18, 3]);
+        assert_eq!(bytes, vec![0x10, 3]);
+    }
+
+    #[test]
+    fn test_as_bytes_reserved_flags() {
+        for packet_type in [
+            PacketType::PUBREL,
+            PacketType::SUBSCRIBE,
+            PacketType::UNSUBSCRIBE,
+        ] {
+            let fixed_header = FixedHeader::new(packet_type, 0);
+            assert_eq!(fixed_header.as_bytes()[0] & 0b1111, 0b0010);
+        }
     }
 
     #[test]
     fn test_from_bytes() {
-        let fixed_header = FixedHeader::new(PACKET_TYPE_VALUE, REMAINING_LENGTH);
-        let bytes: Bytes = vec![18, 3, 2, 3];
+        let fixed_header = FixedHeader::new(PacketType::CONNECT, REMAINING_LENGTH);
+        let bytes: Bytes = vec![0x10, 3, 2, 3];
         let (parsed_fixed_header, leftover) = FixedHeader::from_bytes(bytes).unwrap();
         assert_eq!(parsed_fixed_header, fixed_header);
+        assert_eq!(parsed_fixed_header.packet_type(), PacketType::CONNECT);
         assert_eq!(leftover, vec![2, 3]);
     }
 
     #[test]
     fn test_as_bytes_from_bytes() {
-        let fixed_header = FixedHeader::new(PACKET_TYPE_VALUE, REMAINING_LENGTH);
+        let fixed_header = FixedHeader::new(PacketType::SUBSCRIBE, REMAINING_LENGTH);
         let bytes = fixed_header.as_bytes();
         let (parsed_fixed_header, _leftover) = FixedHeader::from_bytes(bytes).unwrap();
         assert_eq!(parsed_fixed_header, fixed_header);
     }
 
+    #[test]
+    fn test_from_bytes_publish_flags() {
+        let bytes: Bytes = vec![0x3D, 0];
+        let (parsed_fixed_header, _leftover) = FixedHeader::from_bytes(bytes).unwrap();
+        assert_eq!(parsed_fixed_header, FixedHeader::publish(true, 2, true, 0));
+        assert_eq!(parsed_fixed_header.as_bytes(), vec![0x3D, 0]);
+    }
+
+    #[test]
+    fn test_from_bytes_publish_qos_3() {
+        let error = FixedHeader::from_bytes(vec![0x36, 0]).unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::MalformedPacket);
+    }
+
+    #[test]
+    fn test_from_bytes_publish_dup_qos_0() {
+        let error = FixedHeader::from_bytes(vec![0x38, 0]).unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::ProtocolError);
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_reserved_flags() {
+        assert!(FixedHeader::from_bytes(vec![0x12, 0]).is_err());
+        assert!(FixedHeader::from_bytes(vec![0x60, 0]).is_err());
+        assert!(FixedHeader::from_bytes(vec![0x80, 0]).is_err());
+        assert!(FixedHeader::from_bytes(vec![0x62, 0]).is_ok());
+    }
+
     #[test]
     fn test_len() {
-        let fixed_header = FixedHeader::new(PACKET_TYPE_VALUE, REMAINING_LENGTH);
-        let len = fixed_header.len();
-        assert_eq!(len, 2);
+        let fixed_header = FixedHeader::new(PacketType::CONNECT, REMAINING_LENGTH);
+        assert_eq!(fixed_header.as_bytes().len(), 2);
     }
 }
@@ -1,7 +1,18 @@
 pub mod client;
 pub(crate) mod common;
 pub(crate) mod control_packet;
+pub(crate) mod decoder;
+pub mod error;
 pub(crate) mod fixed_header;
 pub(crate) mod payload;
+pub(crate) mod property;
+pub(crate) mod random;
+pub mod reason_code;
 pub mod server;
+pub(crate) mod session;
+pub(crate) mod topic;
 pub(crate) mod variable_header;
+
+pub use crate::control_packet::PacketType;
+pub use crate::error::Error;
+pub use crate::reason_code::ReasonCode;
@@ -0,0 +1,234 @@
+use crate::common::{BinaryData, Bytes, ParseError, Parseable, Serializable, UTF8String};
+use crate::payload::Payload;
+use crate::property::Properties;
+use crate::variable_header::connect::ConnectFlags;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Will {
+    properties: Properties,
+    topic: UTF8String,
+    payload: BinaryData,
+    qos: u8,
+    retain: bool,
+}
+
+impl Will {
+    pub(crate) fn new(
+        topic: &str,
+        payload: Bytes,
+        qos: u8,
+        retain: bool,
+        properties: Properties,
+    ) -> Self {
+        Will {
+            properties,
+            topic: UTF8String::new(topic),
+            payload: BinaryData::new(payload),
+            qos,
+            retain,
+        }
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    pub(crate) fn topic(&self) -> &str {
+        self.topic.value()
+    }
+
+    pub(crate) fn payload(&self) -> &[u8] {
+        self.payload.value()
+    }
+
+    pub(crate) fn qos(&self) -> u8 {
+        self.qos
+    }
+
+    pub(crate) fn retain(&self) -> bool {
+        self.retain
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.properties.as_bytes();
+        bytes.append(&mut self.topic.as_bytes());
+        bytes.append(&mut self.payload.as_bytes());
+        bytes
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ConnectPayload {
+    client_id: UTF8String,
+    will: Option<Will>,
+    user_name: Option<UTF8String>,
+    password: Option<BinaryData>,
+}
+
+impl ConnectPayload {
+    pub(crate) fn new(client_id: &str) -> Self {
+        ConnectPayload {
+            client_id: UTF8String::new(client_id),
+            will: None,
+            user_name: None,
+            password: None,
+        }
+    }
+
+    pub(crate) fn with_will(mut self, will: Will) -> Self {
+        self.will = Some(will);
+        self
+    }
+
+    pub(crate) fn with_user_name(mut self, user_name: &str) -> Self {
+        self.user_name = Some(UTF8String::new(user_name));
+        self
+    }
+
+    pub(crate) fn with_password(mut self, password: Bytes) -> Self {
+        self.password = Some(BinaryData::new(password));
+        self
+    }
+
+    pub(crate) fn client_id(&self) -> &str {
+        self.client_id.value()
+    }
+
+    pub(crate) fn will(&self) -> Option<&Will> {
+        self.will.as_ref()
+    }
+
+    pub(crate) fn user_name(&self) -> Option<&str> {
+        self.user_name.as_ref().map(|user_name| user_name.value())
+    }
+
+    pub(crate) fn password(&self) -> Option<&[u8]> {
+        self.password.as_ref().map(|password| password.value())
+    }
+
+    // the connect flags determine which of the optional fields follow the client id
+    pub(crate) fn from_bytes(bytes: Bytes, flags: &ConnectFlags) -> Result<Self, ParseError> {
+        let (client_id, ci_leftover) = (&bytes[..]).parse_utf8_string()?;
+        let mut leftover = Vec::from(ci_leftover);
+
+        let will = if flags.will() {
+            let (properties, wp_leftover) = Properties::will_from_bytes(leftover)?;
+            let (topic, wt_leftover) = (&wp_leftover[..]).parse_utf8_string()?;
+            let (payload, wpl_leftover) = wt_leftover.parse_binary_data()?;
+            leftover = Vec::from(wpl_leftover);
+            Some(Will {
+                properties,
+                topic,
+                payload,
+                qos: flags.will_qos(),
+                retain: flags.will_retain(),
+            })
+        } else {
+            None
+        };
+
+        let user_name = if flags.user_name() {
+            let (user_name, un_leftover) = (&leftover[..]).parse_utf8_string()?;
+            leftover = Vec::from(un_leftover);
+            Some(user_name)
+        } else {
+            None
+        };
+
+        let password = if flags.password() {
+            let (password, pw_leftover) = (&leftover[..]).parse_binary_data()?;
+            leftover = Vec::from(pw_leftover);
+            Some(password)
+        } else {
+            None
+        };
+
+        (&leftover[..]).parse_end()?;
+
+        Ok(ConnectPayload {
+            client_id,
+            will,
+            user_name,
+            password,
+        })
+    }
+}
+
+impl Payload for ConnectPayload {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.client_id.as_bytes();
+        if let Some(will) = &self.will {
+            bytes.append(&mut will.as_bytes());
+        }
+        if let Some(user_name) = &self.user_name {
+            bytes.append(&mut user_name.as_bytes());
+        }
+        if let Some(password) = &self.password {
+            bytes.append(&mut password.as_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Bytes, FourByteInt};
+    use crate::payload::connect::{ConnectPayload, Will};
+    use crate::payload::Payload;
+    use crate::property::{Properties, Property};
+    use crate::variable_header::connect::ConnectFlags;
+
+    const CLIENT_ID: &str = "id1";
+
+    #[test]
+    fn test_as_bytes() {
+        let payload = ConnectPayload::new(CLIENT_ID);
+        let bytes = payload.as_bytes();
+        assert_eq!(bytes, vec![0, 3, 105, 100, 49]);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let payload = ConnectPayload::new(CLIENT_ID);
+        let bytes: Bytes = vec![0, 3, 105, 100, 49];
+        let flags = ConnectFlags::default();
+        let parsed_payload = ConnectPayload::from_bytes(bytes, &flags).unwrap();
+        assert_eq!(parsed_payload, payload);
+    }
+
+    #[test]
+    fn test_from_bytes_left_over() {
+        let bytes: Bytes = vec![0, 3, 105, 100, 49, 2, 3];
+        let flags = ConnectFlags::default();
+        assert!(ConnectPayload::from_bytes(bytes, &flags).is_err());
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::WillDelayInterval(FourByteInt::new(5))]);
+        let will = Will::new("bye", vec![1, 2], 1, true, properties);
+        let payload = ConnectPayload::new(CLIENT_ID)
+            .with_will(will)
+            .with_user_name("user")
+            .with_password(vec![3, 4]);
+        let bytes = payload.as_bytes();
+        let flags = ConnectFlags::new(false, payload.will(), true, true);
+        let parse_payload = ConnectPayload::from_bytes(bytes, &flags).unwrap();
+        assert_eq!(parse_payload, payload);
+    }
+
+    #[test]
+    fn test_as_bytes_field_order() {
+        let will = Will::new("w", vec![9], 0, false, Properties::default());
+        let payload = ConnectPayload::new("c").with_will(will).with_user_name("u");
+        let expected = vec![0, 1, 99, 0, 0, 1, 119, 0, 1, 9, 0, 1, 117];
+        assert_eq!(payload.as_bytes(), expected);
+    }
+
+    #[test]
+    fn test_len() {
+        let payload = ConnectPayload::new(CLIENT_ID);
+        let len = payload.len();
+        assert_eq!(len, 5);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::common::{Byte, Bytes};
+use crate::payload::Payload;
+
+// application message, opaque to the protocol and not length prefixed
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PublishPayload {
+    data: Bytes,
+}
+
+impl PublishPayload {
+    pub(crate) fn new(data: Bytes) -> Self {
+        PublishPayload { data }
+    }
+
+    pub(crate) fn data(&self) -> &[Byte] {
+        &self.data
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes) -> Self {
+        PublishPayload { data: bytes }
+    }
+}
+
+impl Payload for PublishPayload {
+    fn as_bytes(&self) -> Bytes {
+        self.data.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::payload::publish::PublishPayload;
+    use crate::payload::Payload;
+
+    #[test]
+    fn test_as_bytes_from_bytes_non_utf8() {
+        let payload = PublishPayload::new(vec![0xFF, 0xFE, 0x00, 0xC3]);
+        let bytes = payload.as_bytes();
+        assert_eq!(bytes, vec![0xFF, 0xFE, 0x00, 0xC3]);
+        assert_eq!(PublishPayload::from_bytes(bytes), payload);
+    }
+
+    #[test]
+    fn test_len_empty() {
+        let payload = PublishPayload::new(vec![]);
+        assert_eq!(payload.len(), 0);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::common::{Bytes, ParseError};
+use crate::control_packet::PacketType;
+use crate::payload::Payload;
+use crate::reason_code::ReasonCode;
+
+// SUBACK and UNSUBACK carry one reason code per topic filter of the request
+#[derive(Debug, PartialEq)]
+pub(crate) struct ReasonCodesPayload {
+    reason_codes: Vec<ReasonCode>,
+}
+
+impl ReasonCodesPayload {
+    pub(crate) fn new(reason_codes: Vec<ReasonCode>) -> Self {
+        ReasonCodesPayload { reason_codes }
+    }
+
+    pub(crate) fn reason_codes(&self) -> &[ReasonCode] {
+        &self.reason_codes
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes, packet_type: &PacketType) -> Result<Self, ParseError> {
+        let reason_codes = bytes
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                ReasonCode::from_value_for(value, packet_type)
+                    .map_err(|error| error.at(bytes.len() - index))
+            })
+            .collect::<Result<Vec<ReasonCode>, ParseError>>()?;
+        Ok(ReasonCodesPayload { reason_codes })
+    }
+}
+
+impl Payload for ReasonCodesPayload {
+    fn as_bytes(&self) -> Bytes {
+        self.reason_codes
+            .iter()
+            .map(|reason_code| reason_code.value())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::PacketType;
+    use crate::payload::reason_codes::ReasonCodesPayload;
+    use crate::payload::Payload;
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let payload = ReasonCodesPayload::new(vec![
+            ReasonCode::GRANTED_QOS_0,
+            ReasonCode::GrantedQoS2,
+            ReasonCode::NotAuthorized,
+        ]);
+        let bytes = payload.as_bytes();
+        assert_eq!(bytes, vec![0x00, 0x02, 0x87]);
+        assert_eq!(
+            ReasonCodesPayload::from_bytes(bytes, &PacketType::SUBACK).unwrap(),
+            payload
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_reason_code() {
+        assert!(ReasonCodesPayload::from_bytes(vec![0x02], &PacketType::UNSUBACK).is_err());
+        assert!(ReasonCodesPayload::from_bytes(vec![0x11], &PacketType::SUBACK).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::common::{Byte, Bytes, ParseError, Parseable, Serializable, UTF8String};
+use crate::payload::Payload;
+
+const QOS: Byte = 0b0000_0011;
+const NO_LOCAL: Byte = 0b0000_0100;
+const RETAIN_AS_PUBLISHED: Byte = 0b0000_1000;
+const RETAIN_HANDLING: Byte = 0b0011_0000;
+const RESERVED: Byte = 0b1100_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SubscriptionOptions {
+    qos: u8,
+    no_local: bool,
+    retain_as_published: bool,
+    retain_handling: u8,
+}
+
+impl SubscriptionOptions {
+    pub(crate) fn new(qos: u8) -> Self {
+        SubscriptionOptions {
+            qos,
+            no_local: false,
+            retain_as_published: false,
+            retain_handling: 0,
+        }
+    }
+
+    pub(crate) fn qos(&self) -> u8 {
+        self.qos
+    }
+
+    pub(crate) fn no_local(&self) -> bool {
+        self.no_local
+    }
+
+    pub(crate) fn retain_as_published(&self) -> bool {
+        self.retain_as_published
+    }
+
+    pub(crate) fn retain_handling(&self) -> u8 {
+        self.retain_handling
+    }
+
+    pub(crate) fn from_byte(byte: Byte) -> Result<Self, ParseError> {
+        if byte & RESERVED != 0 {
+            return Err(ParseError::new("reserved subscription option bits set"));
+        }
+        let qos = byte & QOS;
+        if qos > 2 {
+            return Err(ParseError::new("subscription qos of 3"));
+        }
+        let retain_handling = (byte & RETAIN_HANDLING) >> 4;
+        if retain_handling > 2 {
+            return Err(ParseError::protocol("retain handling of 3"));
+        }
+        Ok(SubscriptionOptions {
+            qos,
+            no_local: byte & NO_LOCAL != 0,
+            retain_as_published: byte & RETAIN_AS_PUBLISHED != 0,
+            retain_handling,
+        })
+    }
+
+    pub(crate) fn as_byte(&self) -> Byte {
+        let mut byte = self.qos & QOS;
+        if self.no_local {
+            byte |= NO_LOCAL;
+        }
+        if self.retain_as_published {
+            byte |= RETAIN_AS_PUBLISHED;
+        }
+        byte |= (self.retain_handling << 4) & RETAIN_HANDLING;
+        byte
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Subscription {
+    topic_filter: UTF8String,
+    options: SubscriptionOptions,
+}
+
+impl Subscription {
+    pub(crate) fn new(topic_filter: &str, options: SubscriptionOptions) -> Self {
+        Subscription {
+            topic_filter: UTF8String::new(topic_filter),
+            options,
+        }
+    }
+
+    pub(crate) fn topic_filter(&self) -> &str {
+        self.topic_filter.value()
+    }
+
+    pub(crate) fn options(&self) -> &SubscriptionOptions {
+        &self.options
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.topic_filter.as_bytes();
+        bytes.push(self.options.as_byte());
+        bytes
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct SubscribePayload {
+    subscriptions: Vec<Subscription>,
+}
+
+impl SubscribePayload {
+    pub(crate) fn new(subscriptions: Vec<Subscription>) -> Self {
+        SubscribePayload { subscriptions }
+    }
+
+    pub(crate) fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes) -> Result<Self, ParseError> {
+        let mut subscriptions = Vec::new();
+        let mut leftover = &bytes[..];
+        while !leftover.is_empty() {
+            let (topic_filter, tf_leftover) = leftover.parse_utf8_string()?;
+            let (options, options_leftover) = tf_leftover.parse_byte()?;
+            let options = SubscriptionOptions::from_byte(options)
+                .map_err(|error| error.at(tf_leftover.len()))?;
+            subscriptions.push(Subscription {
+                topic_filter,
+                options,
+            });
+            leftover = options_leftover;
+        }
+        if subscriptions.is_empty() {
+            return Err(ParseError::protocol("subscribe without topic filters"));
+        }
+        Ok(SubscribePayload { subscriptions })
+    }
+}
+
+impl Payload for SubscribePayload {
+    fn as_bytes(&self) -> Bytes {
+        self.subscriptions
+            .iter()
+            .flat_map(|subscription| subscription.as_bytes())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::payload::subscribe::{SubscribePayload, Subscription, SubscriptionOptions};
+    use crate::payload::Payload;
+
+    #[test]
+    fn test_options_as_byte_from_byte() {
+        let options = SubscriptionOptions::from_byte(0b0010_1110).unwrap();
+        assert_eq!(options.qos(), 2);
+        assert!(options.no_local());
+        assert!(options.retain_as_published());
+        assert_eq!(options.retain_handling(), 2);
+        assert_eq!(options.as_byte(), 0b0010_1110);
+        assert_eq!(SubscriptionOptions::new(1).as_byte(), 1);
+    }
+
+    #[test]
+    fn test_options_from_byte_invalid() {
+        assert!(SubscriptionOptions::from_byte(0b0000_0011).is_err());
+        assert!(SubscriptionOptions::from_byte(0b0011_0000).is_err());
+        assert!(SubscriptionOptions::from_byte(0b0100_0000).is_err());
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let payload = SubscribePayload::new(vec![
+            Subscription::new("a/+", SubscriptionOptions::new(1)),
+            // no local
+            Subscription::new("b/#", SubscriptionOptions::from_byte(0b0100).unwrap()),
+        ]);
+        let bytes = payload.as_bytes();
+        assert_eq!(bytes, vec![0, 3, 97, 47, 43, 1, 0, 3, 98, 47, 35, 4]);
+        assert_eq!(SubscribePayload::from_bytes(bytes).unwrap(), payload);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::common::{Bytes, ParseError, Parseable, Serializable, UTF8String};
+use crate::payload::Payload;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct UnsubscribePayload {
+    topic_filters: Vec<UTF8String>,
+}
+
+impl UnsubscribePayload {
+    pub(crate) fn new(topic_filters: &[&str]) -> Self {
+        UnsubscribePayload {
+            topic_filters: topic_filters
+                .iter()
+                .map(|topic_filter| UTF8String::new(topic_filter))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn topic_filters(&self) -> Vec<&str> {
+        self.topic_filters.iter().map(|tf| tf.value()).collect()
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes) -> Result<Self, ParseError> {
+        let mut topic_filters = Vec::new();
+        let mut leftover = &bytes[..];
+        while !leftover.is_empty() {
+            let (topic_filter, tf_leftover) = leftover.parse_utf8_string()?;
+            topic_filters.push(topic_filter);
+            leftover = tf_leftover;
+        }
+        if topic_filters.is_empty() {
+            return Err(ParseError::protocol("unsubscribe without topic filters"));
+        }
+        Ok(UnsubscribePayload { topic_filters })
+    }
+}
+
+impl Payload for UnsubscribePayload {
+    fn as_bytes(&self) -> Bytes {
+        self.topic_filters
+            .iter()
+            .flat_map(|tf| tf.as_bytes())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::payload::unsubscribe::UnsubscribePayload;
+    use crate::payload::Payload;
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let payload = UnsubscribePayload::new(&["a", "b/#"]);
+        let bytes = payload.as_bytes();
+        assert_eq!(bytes, vec![0, 1, 97, 0, 3, 98, 47, 35]);
+        assert_eq!(UnsubscribePayload::from_bytes(bytes).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_from_bytes_empty() {
+        assert!(UnsubscribePayload::from_bytes(vec![]).is_err());
+    }
+}
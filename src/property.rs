@@ -0,0 +1,537 @@
+use crate::common::{
+    BinaryData, Byte, Bytes, FourByteInt, ParseError, Parseable, Serializable, TwoByteInt,
+    UTF8String, UTF8StringPair, VariableByteInt,
+};
+use crate::control_packet::PacketType;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Property {
+    PayloadFormatIndicator(Byte),
+    MessageExpiryInterval(FourByteInt),
+    ContentType(UTF8String),
+    ResponseTopic(UTF8String),
+    CorrelationData(BinaryData),
+    SubscriptionIdentifier(VariableByteInt),
+    SessionExpiryInterval(FourByteInt),
+    AssignedClientIdentifier(UTF8String),
+    ServerKeepAlive(TwoByteInt),
+    AuthenticationMethod(UTF8String),
+    AuthenticationData(BinaryData),
+    RequestProblemInformation(Byte),
+    WillDelayInterval(FourByteInt),
+    RequestResponseInformation(Byte),
+    ResponseInformation(UTF8String),
+    ServerReference(UTF8String),
+    ReasonString(UTF8String),
+    ReceiveMaximum(TwoByteInt),
+    TopicAliasMaximum(TwoByteInt),
+    TopicAlias(TwoByteInt),
+    MaximumQoS(Byte),
+    RetainAvailable(Byte),
+    UserProperty(UTF8StringPair),
+    MaximumPacketSize(FourByteInt),
+    WildcardSubscriptionAvailable(Byte),
+    SubscriptionIdentifierAvailable(Byte),
+    SharedSubscriptionAvailable(Byte),
+}
+
+impl Property {
+    pub(crate) fn identifier(&self) -> u8 {
+        match self {
+            Property::PayloadFormatIndicator(_) => 0x01,
+            Property::MessageExpiryInterval(_) => 0x02,
+            Property::ContentType(_) => 0x03,
+            Property::ResponseTopic(_) => 0x08,
+            Property::CorrelationData(_) => 0x09,
+            Property::SubscriptionIdentifier(_) => 0x0B,
+            Property::SessionExpiryInterval(_) => 0x11,
+            Property::AssignedClientIdentifier(_) => 0x12,
+            Property::ServerKeepAlive(_) => 0x13,
+            Property::AuthenticationMethod(_) => 0x15,
+            Property::AuthenticationData(_) => 0x16,
+            Property::RequestProblemInformation(_) => 0x17,
+            Property::WillDelayInterval(_) => 0x18,
+            Property::RequestResponseInformation(_) => 0x19,
+            Property::ResponseInformation(_) => 0x1A,
+            Property::ServerReference(_) => 0x1C,
+            Property::ReasonString(_) => 0x1F,
+            Property::ReceiveMaximum(_) => 0x21,
+            Property::TopicAliasMaximum(_) => 0x22,
+            Property::TopicAlias(_) => 0x23,
+            Property::MaximumQoS(_) => 0x24,
+            Property::RetainAvailable(_) => 0x25,
+            Property::UserProperty(_) => 0x26,
+            Property::MaximumPacketSize(_) => 0x27,
+            Property::WildcardSubscriptionAvailable(_) => 0x28,
+            Property::SubscriptionIdentifierAvailable(_) => 0x29,
+            Property::SharedSubscriptionAvailable(_) => 0x2A,
+        }
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        match self {
+            Property::PayloadFormatIndicator(v)
+            | Property::RequestProblemInformation(v)
+            | Property::RequestResponseInformation(v)
+            | Property::MaximumQoS(v)
+            | Property::RetainAvailable(v)
+            | Property::WildcardSubscriptionAvailable(v)
+            | Property::SubscriptionIdentifierAvailable(v)
+            | Property::SharedSubscriptionAvailable(v) => v.as_bytes(),
+            Property::MessageExpiryInterval(v)
+            | Property::SessionExpiryInterval(v)
+            | Property::WillDelayInterval(v)
+            | Property::MaximumPacketSize(v) => v.as_bytes(),
+            Property::ServerKeepAlive(v)
+            | Property::ReceiveMaximum(v)
+            | Property::TopicAliasMaximum(v)
+            | Property::TopicAlias(v) => v.as_bytes(),
+            Property::ContentType(v)
+            | Property::ResponseTopic(v)
+            | Property::AssignedClientIdentifier(v)
+            | Property::AuthenticationMethod(v)
+            | Property::ResponseInformation(v)
+            | Property::ServerReference(v)
+            | Property::ReasonString(v) => v.as_bytes(),
+            Property::CorrelationData(v) | Property::AuthenticationData(v) => v.as_bytes(),
+            Property::SubscriptionIdentifier(v) => v.as_bytes(),
+            Property::UserProperty(v) => v.as_bytes(),
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[Byte]) -> Result<(Self, &[Byte]), ParseError> {
+        let (identifier, leftover) = bytes.parse_variable_byte_int()?;
+        let (property, leftover) = match identifier.value() {
+            0x01 => parse_boolean_byte(leftover, Property::PayloadFormatIndicator)?,
+            0x02 => map(
+                leftover.parse_four_byte_int()?,
+                Property::MessageExpiryInterval,
+            ),
+            0x03 => map(leftover.parse_utf8_string()?, Property::ContentType),
+            0x08 => map(leftover.parse_utf8_string()?, Property::ResponseTopic),
+            0x09 => map(leftover.parse_binary_data()?, Property::CorrelationData),
+            0x0B => {
+                let (id, leftover) = leftover.parse_variable_byte_int()?;
+                if id.value() == 0 {
+                    return Err(ParseError::protocol("subscription identifier of 0"));
+                }
+                (Property::SubscriptionIdentifier(id), leftover)
+            }
+            0x11 => map(
+                leftover.parse_four_byte_int()?,
+                Property::SessionExpiryInterval,
+            ),
+            0x12 => map(
+                leftover.parse_utf8_string()?,
+                Property::AssignedClientIdentifier,
+            ),
+            0x13 => map(leftover.parse_two_byte_int()?, Property::ServerKeepAlive),
+            0x15 => map(
+                leftover.parse_utf8_string()?,
+                Property::AuthenticationMethod,
+            ),
+            0x16 => map(leftover.parse_binary_data()?, Property::AuthenticationData),
+            0x17 => parse_boolean_byte(leftover, Property::RequestProblemInformation)?,
+            0x18 => map(leftover.parse_four_byte_int()?, Property::WillDelayInterval),
+            0x19 => parse_boolean_byte(leftover, Property::RequestResponseInformation)?,
+            0x1A => map(leftover.parse_utf8_string()?, Property::ResponseInformation),
+            0x1C => map(leftover.parse_utf8_string()?, Property::ServerReference),
+            0x1F => map(leftover.parse_utf8_string()?, Property::ReasonString),
+            0x21 => {
+                let (receive_maximum, leftover) = leftover.parse_two_byte_int()?;
+                if receive_maximum.value() == 0 {
+                    return Err(ParseError::protocol("receive maximum of 0"));
+                }
+                (Property::ReceiveMaximum(receive_maximum), leftover)
+            }
+            0x22 => map(leftover.parse_two_byte_int()?, Property::TopicAliasMaximum),
+            0x23 => {
+                let (topic_alias, leftover) = leftover.parse_two_byte_int()?;
+                if topic_alias.value() == 0 {
+                    return Err(ParseError::protocol("topic alias of 0"));
+                }
+                (Property::TopicAlias(topic_alias), leftover)
+            }
+            0x24 => parse_boolean_byte(leftover, Property::MaximumQoS)?,
+            0x25 => parse_boolean_byte(leftover, Property::RetainAvailable)?,
+            0x26 => map(leftover.parse_utf8_string_pair()?, Property::UserProperty),
+            0x27 => {
+                let (maximum_packet_size, leftover) = leftover.parse_four_byte_int()?;
+                if maximum_packet_size.value() == 0 {
+                    return Err(ParseError::protocol("maximum packet size of 0"));
+                }
+                (Property::MaximumPacketSize(maximum_packet_size), leftover)
+            }
+            0x28 => parse_boolean_byte(leftover, Property::WildcardSubscriptionAvailable)?,
+            0x29 => parse_boolean_byte(leftover, Property::SubscriptionIdentifierAvailable)?,
+            0x2A => parse_boolean_byte(leftover, Property::SharedSubscriptionAvailable)?,
+            _ => return Err(ParseError::new("unknown property identifier").at(bytes.len())),
+        };
+        Ok((property, leftover))
+    }
+
+    pub(crate) fn as_bytes(&self) -> Bytes {
+        let mut bytes = VariableByteInt::new(self.identifier() as u32).as_bytes();
+        bytes.append(&mut self.value_bytes());
+        bytes
+    }
+
+    /// Whether this property may appear in the variable header of the given packet type.
+    pub(crate) fn allowed_in(&self, packet_type: &PacketType) -> bool {
+        use PacketType::*;
+        match self {
+            Property::PayloadFormatIndicator(_)
+            | Property::MessageExpiryInterval(_)
+            | Property::ContentType(_)
+            | Property::ResponseTopic(_)
+            | Property::CorrelationData(_)
+            | Property::TopicAlias(_) => *packet_type == PUBLISH,
+            Property::SubscriptionIdentifier(_) => matches!(packet_type, PUBLISH | SUBSCRIBE),
+            Property::SessionExpiryInterval(_) => {
+                matches!(packet_type, CONNECT | CONNACK | DISCONNECT)
+            }
+            Property::AssignedClientIdentifier(_)
+            | Property::ServerKeepAlive(_)
+            | Property::ResponseInformation(_)
+            | Property::MaximumQoS(_)
+            | Property::RetainAvailable(_)
+            | Property::WildcardSubscriptionAvailable(_)
+            | Property::SubscriptionIdentifierAvailable(_)
+            | Property::SharedSubscriptionAvailable(_) => *packet_type == CONNACK,
+            Property::AuthenticationMethod(_) | Property::AuthenticationData(_) => {
+                matches!(packet_type, CONNECT | CONNACK | AUTH)
+            }
+            Property::RequestProblemInformation(_) | Property::RequestResponseInformation(_) => {
+                *packet_type == CONNECT
+            }
+            Property::WillDelayInterval(_) => false,
+            Property::ServerReference(_) => matches!(packet_type, CONNACK | DISCONNECT),
+            Property::ReasonString(_) => matches!(
+                packet_type,
+                CONNACK
+                    | PUBACK
+                    | PUBREC
+                    | PUBREL
+                    | PUBCOMP
+                    | SUBACK
+                    | UNSUBACK
+                    | DISCONNECT
+                    | AUTH
+            ),
+            Property::ReceiveMaximum(_)
+            | Property::TopicAliasMaximum(_)
+            | Property::MaximumPacketSize(_) => matches!(packet_type, CONNECT | CONNACK),
+            Property::UserProperty(_) => !matches!(packet_type, PINGREQ | PINGRESP),
+        }
+    }
+
+    /// Whether this property may appear in the Will Properties of a CONNECT payload.
+    pub(crate) fn allowed_in_will(&self) -> bool {
+        matches!(
+            self,
+            Property::PayloadFormatIndicator(_)
+                | Property::MessageExpiryInterval(_)
+                | Property::ContentType(_)
+                | Property::ResponseTopic(_)
+                | Property::CorrelationData(_)
+                | Property::WillDelayInterval(_)
+                | Property::UserProperty(_)
+        )
+    }
+
+    /// Whether this property may be included more than once in the given packet type.
+    pub(crate) fn may_repeat(&self, packet_type: &PacketType) -> bool {
+        match self {
+            Property::UserProperty(_) => true,
+            Property::SubscriptionIdentifier(_) => *packet_type == PacketType::PUBLISH,
+            _ => false,
+        }
+    }
+}
+
+fn map<T>((value, leftover): (T, &[Byte]), f: fn(T) -> Property) -> (Property, &[Byte]) {
+    (f(value), leftover)
+}
+
+fn parse_boolean_byte(
+    bytes: &[Byte],
+    f: fn(Byte) -> Property,
+) -> Result<(Property, &[Byte]), ParseError> {
+    let (byte, leftover) = bytes.parse_byte()?;
+    if byte > 1 {
+        return Err(ParseError::protocol("property value must be 0 or 1"));
+    }
+    Ok((f(byte), leftover))
+}
+
+// A type that property values come in, so a property can be looked up by its variant.
+pub(crate) trait PropertyValue: Default {
+    fn of(property: &Property) -> Option<&Self>;
+}
+
+impl PropertyValue for TwoByteInt {
+    fn of(property: &Property) -> Option<&Self> {
+        match property {
+            Property::ServerKeepAlive(v)
+            | Property::ReceiveMaximum(v)
+            | Property::TopicAliasMaximum(v)
+            | Property::TopicAlias(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl PropertyValue for FourByteInt {
+    fn of(property: &Property) -> Option<&Self> {
+        match property {
+            Property::MessageExpiryInterval(v)
+            | Property::SessionExpiryInterval(v)
+            | Property::WillDelayInterval(v)
+            | Property::MaximumPacketSize(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl PropertyValue for UTF8String {
+    fn of(property: &Property) -> Option<&Self> {
+        match property {
+            Property::ContentType(v)
+            | Property::ResponseTopic(v)
+            | Property::AssignedClientIdentifier(v)
+            | Property::AuthenticationMethod(v)
+            | Property::ResponseInformation(v)
+            | Property::ServerReference(v)
+            | Property::ReasonString(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Properties {
+    values: Vec<Property>,
+}
+
+impl Properties {
+    pub(crate) fn new(values: Vec<Property>) -> Self {
+        Properties { values }
+    }
+
+    pub(crate) fn from_bytes(
+        bytes: Bytes,
+        packet_type: &PacketType,
+    ) -> Result<(Self, Bytes), ParseError> {
+        let (properties, leftover) = Properties::parse(bytes)?;
+        properties.validate(
+            |property| property.allowed_in(packet_type),
+            |property| property.may_repeat(packet_type),
+        )?;
+        Ok((properties, leftover))
+    }
+
+    pub(crate) fn will_from_bytes(bytes: Bytes) -> Result<(Self, Bytes), ParseError> {
+        let (properties, leftover) = Properties::parse(bytes)?;
+        properties.validate(Property::allowed_in_will, |property| {
+            matches!(property, Property::UserProperty(_))
+        })?;
+        Ok((properties, leftover))
+    }
+
+    fn parse(bytes: Bytes) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (prop_len, prop_len_leftover) = byte_slice.parse_variable_byte_int()?;
+        let prop_len = prop_len.value() as usize;
+        if prop_len > prop_len_leftover.len() {
+            return Err(ParseError::new("property length exceeds remaining bytes")
+                .at(prop_len_leftover.len()));
+        }
+        let (mut prop_bytes, leftover) = prop_len_leftover.split_at(prop_len);
+        let mut values = Vec::new();
+        while !prop_bytes.is_empty() {
+            let (property, prop_leftover) = Property::from_bytes(prop_bytes)
+                .map_err(|error| error.followed_by(leftover.len()))?;
+            values.push(property);
+            prop_bytes = prop_leftover;
+        }
+        Ok((Properties { values }, Vec::from(leftover)))
+    }
+
+    fn validate(
+        &self,
+        allowed: impl Fn(&Property) -> bool,
+        may_repeat: impl Fn(&Property) -> bool,
+    ) -> Result<(), ParseError> {
+        for (idx, property) in self.values.iter().enumerate() {
+            if !allowed(property) {
+                return Err(ParseError::protocol("property not allowed in this packet"));
+            }
+            let repeated = self.values[..idx]
+                .iter()
+                .any(|other| other.identifier() == property.identifier());
+            if repeated && !may_repeat(property) {
+                return Err(ParseError::protocol("property included more than once"));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn values(&self) -> &[Property] {
+        &self.values
+    }
+
+    /// The value of the first property of the variant, as in
+    /// `properties.get(Property::SessionExpiryInterval)`.
+    pub(crate) fn get<T: PropertyValue>(&self, property: fn(T) -> Property) -> Option<&T> {
+        let identifier = property(T::default()).identifier();
+        self.values
+            .iter()
+            .filter(|value| value.identifier() == identifier)
+            .find_map(T::of)
+    }
+
+    pub(crate) fn push(&mut self, property: Property) {
+        self.values.push(property);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub(crate) fn as_bytes(&self) -> Bytes {
+        let mut prop_bytes: Bytes = self.values.iter().flat_map(|p| p.as_bytes()).collect();
+        let mut bytes = VariableByteInt::new(prop_bytes.len() as u32).as_bytes();
+        bytes.append(&mut prop_bytes);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{
+        BinaryData, Bytes, FourByteInt, TwoByteInt, UTF8String, UTF8StringPair, VariableByteInt,
+    };
+    use crate::control_packet::PacketType;
+    use crate::property::{Properties, Property};
+
+    #[test]
+    fn test_property_as_bytes() {
+        let property = Property::SessionExpiryInterval(FourByteInt::new(60));
+        assert_eq!(property.as_bytes(), vec![0x11, 0, 0, 0, 60]);
+        let property = Property::UserProperty(UTF8StringPair::new("a", "b"));
+        assert_eq!(property.as_bytes(), vec![0x26, 0, 1, 97, 0, 1, 98]);
+    }
+
+    #[test]
+    fn test_property_from_bytes() {
+        let bytes: &[u8] = &[0x21, 0, 10, 2, 3];
+        let (property, leftover) = Property::from_bytes(bytes).unwrap();
+        assert_eq!(property, Property::ReceiveMaximum(TwoByteInt::new(10)));
+        assert_eq!(leftover, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_property_from_bytes_unknown_identifier() {
+        let bytes: &[u8] = &[0x04, 0];
+        assert!(Property::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn test_property_from_bytes_invalid_value() {
+        let bytes: &[u8] = &[0x24, 2];
+        assert!(Property::from_bytes(bytes).is_err());
+        let bytes: &[u8] = &[0x21, 0, 0];
+        assert!(Property::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn test_properties_as_bytes() {
+        let properties = Properties::new(vec![
+            Property::SessionExpiryInterval(FourByteInt::new(60)),
+            Property::TopicAliasMaximum(TwoByteInt::new(5)),
+        ]);
+        let bytes = properties.as_bytes();
+        assert_eq!(bytes, vec![8, 0x11, 0, 0, 0, 60, 0x22, 0, 5]);
+    }
+
+    #[test]
+    fn test_properties_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![
+            Property::PayloadFormatIndicator(1),
+            Property::MessageExpiryInterval(FourByteInt::new(3600)),
+            Property::ContentType(UTF8String::new("text/plain")),
+            Property::ResponseTopic(UTF8String::new("foo/bar")),
+            Property::CorrelationData(BinaryData::new(vec![1, 2, 3])),
+            Property::SubscriptionIdentifier(VariableByteInt::new(300)),
+            Property::SubscriptionIdentifier(VariableByteInt::new(301)),
+            Property::TopicAlias(TwoByteInt::new(4)),
+            Property::UserProperty(UTF8StringPair::new("foo", "bar")),
+            Property::UserProperty(UTF8StringPair::new("foo", "baz")),
+        ]);
+        let bytes: Bytes = [properties.as_bytes(), vec![2, 3]].concat();
+        let (parsed, leftover) = Properties::from_bytes(bytes, &PacketType::PUBLISH).unwrap();
+        assert_eq!(parsed, properties);
+        assert_eq!(leftover, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_properties_from_bytes_empty() {
+        let (parsed, leftover) = Properties::from_bytes(vec![0, 2], &PacketType::PUBACK).unwrap();
+        assert!(parsed.is_empty());
+        assert_eq!(leftover, vec![2]);
+    }
+
+    #[test]
+    fn test_properties_from_bytes_not_allowed() {
+        let properties = Properties::new(vec![Property::TopicAlias(TwoByteInt::new(4))]);
+        assert!(Properties::from_bytes(properties.as_bytes(), &PacketType::CONNECT).is_err());
+        let properties = Properties::new(vec![Property::WillDelayInterval(FourByteInt::new(4))]);
+        assert!(Properties::from_bytes(properties.as_bytes(), &PacketType::CONNECT).is_err());
+        assert!(Properties::will_from_bytes(properties.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_properties_from_bytes_repeated() {
+        let properties = Properties::new(vec![
+            Property::SubscriptionIdentifier(VariableByteInt::new(1)),
+            Property::SubscriptionIdentifier(VariableByteInt::new(2)),
+        ]);
+        assert!(Properties::from_bytes(properties.as_bytes(), &PacketType::SUBSCRIBE).is_err());
+        let properties = Properties::new(vec![
+            Property::ReceiveMaximum(TwoByteInt::new(1)),
+            Property::ReceiveMaximum(TwoByteInt::new(2)),
+        ]);
+        assert!(Properties::from_bytes(properties.as_bytes(), &PacketType::CONNECT).is_err());
+    }
+
+    #[test]
+    fn test_properties_from_bytes_truncated() {
+        assert!(Properties::from_bytes(vec![5, 0x11, 0, 0], &PacketType::CONNECT).is_err());
+    }
+
+    #[test]
+    fn test_get() {
+        let properties = Properties::new(vec![
+            Property::MaximumPacketSize(FourByteInt::new(1024)),
+            Property::SessionExpiryInterval(FourByteInt::new(60)),
+        ]);
+        assert_eq!(
+            properties.get(Property::SessionExpiryInterval),
+            Some(&FourByteInt::new(60))
+        );
+        assert_eq!(properties.get(Property::MessageExpiryInterval), None);
+        let properties = Properties::new(vec![Property::ReasonString(UTF8String::new("foo"))]);
+        assert_eq!(properties.get(Property::ContentType), None);
+        assert_eq!(
+            properties.get(Property::ReasonString),
+            Some(&UTF8String::new("foo"))
+        );
+        let properties = Properties::new(vec![Property::ReceiveMaximum(TwoByteInt::new(10))]);
+        assert_eq!(
+            properties.get(Property::ReceiveMaximum),
+            Some(&TwoByteInt::new(10))
+        );
+        assert_eq!(properties.get(Property::TopicAlias), None);
+    }
+}
@@ -0,0 +1,40 @@
+// Random numbers from the randomly keyed std hasher, mixed with the current time. Not fit
+// for secrets, but enough for salts and for spreading out reconnect delays without a
+// dependency.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Hashes the current time with a new RandomState. Each thread seeds its keys randomly
+/// once and adds one to them for every RandomState it makes, so two calls on the same
+/// thread differ even within the same nanosecond.
+pub(crate) fn next_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// A fraction in [0, 1).
+pub(crate) fn fraction() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random::{fraction, next_u64};
+
+    #[test]
+    fn test_next_u64() {
+        assert_ne!(next_u64(), next_u64());
+    }
+
+    #[test]
+    fn test_fraction() {
+        for _ in 0..100 {
+            assert!((0.0..1.0).contains(&fraction()));
+        }
+    }
+}
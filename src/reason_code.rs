@@ -0,0 +1,351 @@
+use crate::common::{Byte, ParseError};
+use crate::control_packet::PacketType;
+use std::fmt;
+
+/// MQTT 5 reason codes as carried by CONNACK, PUBACK, PUBREC, PUBREL, PUBCOMP, SUBACK,
+/// UNSUBACK, DISCONNECT and AUTH. Values below 0x80 indicate success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReasonCode {
+    Success,
+    GrantedQoS1,
+    GrantedQoS2,
+    DisconnectWithWillMessage,
+    NoMatchingSubscribers,
+    NoSubscriptionExisted,
+    ContinueAuthentication,
+    ReAuthenticate,
+    UnspecifiedError,
+    MalformedPacket,
+    ProtocolError,
+    ImplementationSpecificError,
+    UnsupportedProtocolVersion,
+    ClientIdentifierNotValid,
+    BadUserNameOrPassword,
+    NotAuthorized,
+    ServerUnavailable,
+    ServerBusy,
+    Banned,
+    ServerShuttingDown,
+    BadAuthenticationMethod,
+    KeepAliveTimeout,
+    SessionTakenOver,
+    TopicFilterInvalid,
+    TopicNameInvalid,
+    PacketIdentifierInUse,
+    PacketIdentifierNotFound,
+    ReceiveMaximumExceeded,
+    TopicAliasInvalid,
+    PacketTooLarge,
+    MessageRateTooHigh,
+    QuotaExceeded,
+    AdministrativeAction,
+    PayloadFormatInvalid,
+    RetainNotSupported,
+    QoSNotSupported,
+    UseAnotherServer,
+    ServerMoved,
+    SharedSubscriptionsNotSupported,
+    ConnectionRateExceeded,
+    MaximumConnectTime,
+    SubscriptionIdentifiersNotSupported,
+    WildcardSubscriptionsNotSupported,
+}
+
+impl ReasonCode {
+    /// 0x00 as sent in DISCONNECT.
+    pub const NORMAL_DISCONNECTION: ReasonCode = ReasonCode::Success;
+    /// 0x00 as sent in SUBACK.
+    pub const GRANTED_QOS_0: ReasonCode = ReasonCode::Success;
+
+    pub fn value(&self) -> Byte {
+        match self {
+            ReasonCode::Success => 0x00,
+            ReasonCode::GrantedQoS1 => 0x01,
+            ReasonCode::GrantedQoS2 => 0x02,
+            ReasonCode::DisconnectWithWillMessage => 0x04,
+            ReasonCode::NoMatchingSubscribers => 0x10,
+            ReasonCode::NoSubscriptionExisted => 0x11,
+            ReasonCode::ContinueAuthentication => 0x18,
+            ReasonCode::ReAuthenticate => 0x19,
+            ReasonCode::UnspecifiedError => 0x80,
+            ReasonCode::MalformedPacket => 0x81,
+            ReasonCode::ProtocolError => 0x82,
+            ReasonCode::ImplementationSpecificError => 0x83,
+            ReasonCode::UnsupportedProtocolVersion => 0x84,
+            ReasonCode::ClientIdentifierNotValid => 0x85,
+            ReasonCode::BadUserNameOrPassword => 0x86,
+            ReasonCode::NotAuthorized => 0x87,
+            ReasonCode::ServerUnavailable => 0x88,
+            ReasonCode::ServerBusy => 0x89,
+            ReasonCode::Banned => 0x8A,
+            ReasonCode::ServerShuttingDown => 0x8B,
+            ReasonCode::BadAuthenticationMethod => 0x8C,
+            ReasonCode::KeepAliveTimeout => 0x8D,
+            ReasonCode::SessionTakenOver => 0x8E,
+            ReasonCode::TopicFilterInvalid => 0x8F,
+            ReasonCode::TopicNameInvalid => 0x90,
+            ReasonCode::PacketIdentifierInUse => 0x91,
+            ReasonCode::PacketIdentifierNotFound => 0x92,
+            ReasonCode::ReceiveMaximumExceeded => 0x93,
+            ReasonCode::TopicAliasInvalid => 0x94,
+            ReasonCode::PacketTooLarge => 0x95,
+            ReasonCode::MessageRateTooHigh => 0x96,
+            ReasonCode::QuotaExceeded => 0x97,
+            ReasonCode::AdministrativeAction => 0x98,
+            ReasonCode::PayloadFormatInvalid => 0x99,
+            ReasonCode::RetainNotSupported => 0x9A,
+            ReasonCode::QoSNotSupported => 0x9B,
+            ReasonCode::UseAnotherServer => 0x9C,
+            ReasonCode::ServerMoved => 0x9D,
+            ReasonCode::SharedSubscriptionsNotSupported => 0x9E,
+            ReasonCode::ConnectionRateExceeded => 0x9F,
+            ReasonCode::MaximumConnectTime => 0xA0,
+            ReasonCode::SubscriptionIdentifiersNotSupported => 0xA1,
+            ReasonCode::WildcardSubscriptionsNotSupported => 0xA2,
+        }
+    }
+
+    pub(crate) fn from_value(value: Byte) -> Result<Self, ParseError> {
+        match value {
+            0x00 => Ok(ReasonCode::Success),
+            0x01 => Ok(ReasonCode::GrantedQoS1),
+            0x02 => Ok(ReasonCode::GrantedQoS2),
+            0x04 => Ok(ReasonCode::DisconnectWithWillMessage),
+            0x10 => Ok(ReasonCode::NoMatchingSubscribers),
+            0x11 => Ok(ReasonCode::NoSubscriptionExisted),
+            0x18 => Ok(ReasonCode::ContinueAuthentication),
+            0x19 => Ok(ReasonCode::ReAuthenticate),
+            0x80 => Ok(ReasonCode::UnspecifiedError),
+            0x81 => Ok(ReasonCode::MalformedPacket),
+            0x82 => Ok(ReasonCode::ProtocolError),
+            0x83 => Ok(ReasonCode::ImplementationSpecificError),
+            0x84 => Ok(ReasonCode::UnsupportedProtocolVersion),
+            0x85 => Ok(ReasonCode::ClientIdentifierNotValid),
+            0x86 => Ok(ReasonCode::BadUserNameOrPassword),
+            0x87 => Ok(ReasonCode::NotAuthorized),
+            0x88 => Ok(ReasonCode::ServerUnavailable),
+            0x89 => Ok(ReasonCode::ServerBusy),
+            0x8A => Ok(ReasonCode::Banned),
+            0x8B => Ok(ReasonCode::ServerShuttingDown),
+            0x8C => Ok(ReasonCode::BadAuthenticationMethod),
+            0x8D => Ok(ReasonCode::KeepAliveTimeout),
+            0x8E => Ok(ReasonCode::SessionTakenOver),
+            0x8F => Ok(ReasonCode::TopicFilterInvalid),
+            0x90 => Ok(ReasonCode::TopicNameInvalid),
+            0x91 => Ok(ReasonCode::PacketIdentifierInUse),
+            0x92 => Ok(ReasonCode::PacketIdentifierNotFound),
+            0x93 => Ok(ReasonCode::ReceiveMaximumExceeded),
+            0x94 => Ok(ReasonCode::TopicAliasInvalid),
+            0x95 => Ok(ReasonCode::PacketTooLarge),
+            0x96 => Ok(ReasonCode::MessageRateTooHigh),
+            0x97 => Ok(ReasonCode::QuotaExceeded),
+            0x98 => Ok(ReasonCode::AdministrativeAction),
+            0x99 => Ok(ReasonCode::PayloadFormatInvalid),
+            0x9A => Ok(ReasonCode::RetainNotSupported),
+            0x9B => Ok(ReasonCode::QoSNotSupported),
+            0x9C => Ok(ReasonCode::UseAnotherServer),
+            0x9D => Ok(ReasonCode::ServerMoved),
+            0x9E => Ok(ReasonCode::SharedSubscriptionsNotSupported),
+            0x9F => Ok(ReasonCode::ConnectionRateExceeded),
+            0xA0 => Ok(ReasonCode::MaximumConnectTime),
+            0xA1 => Ok(ReasonCode::SubscriptionIdentifiersNotSupported),
+            0xA2 => Ok(ReasonCode::WildcardSubscriptionsNotSupported),
+            _ => Err(ParseError::new("unknown reason code")),
+        }
+    }
+
+    // as for `from_value` but also rejecting codes the packet type does not allow
+    pub(crate) fn from_value_for(
+        value: Byte,
+        packet_type: &PacketType,
+    ) -> Result<Self, ParseError> {
+        let reason_code = ReasonCode::from_value(value)?;
+        if !reason_code.is_valid_for(packet_type) {
+            return Err(ParseError::new("reason code not allowed in this packet"));
+        }
+        Ok(reason_code)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ReasonCode::Success => "Success",
+            ReasonCode::GrantedQoS1 => "Granted QoS 1",
+            ReasonCode::GrantedQoS2 => "Granted QoS 2",
+            ReasonCode::DisconnectWithWillMessage => "Disconnect with Will Message",
+            ReasonCode::NoMatchingSubscribers => "No matching subscribers",
+            ReasonCode::NoSubscriptionExisted => "No subscription existed",
+            ReasonCode::ContinueAuthentication => "Continue authentication",
+            ReasonCode::ReAuthenticate => "Re-authenticate",
+            ReasonCode::UnspecifiedError => "Unspecified error",
+            ReasonCode::MalformedPacket => "Malformed Packet",
+            ReasonCode::ProtocolError => "Protocol Error",
+            ReasonCode::ImplementationSpecificError => "Implementation specific error",
+            ReasonCode::UnsupportedProtocolVersion => "Unsupported Protocol Version",
+            ReasonCode::ClientIdentifierNotValid => "Client Identifier not valid",
+            ReasonCode::BadUserNameOrPassword => "Bad User Name or Password",
+            ReasonCode::NotAuthorized => "Not authorized",
+            ReasonCode::ServerUnavailable => "Server unavailable",
+            ReasonCode::ServerBusy => "Server busy",
+            ReasonCode::Banned => "Banned",
+            ReasonCode::ServerShuttingDown => "Server shutting down",
+            ReasonCode::BadAuthenticationMethod => "Bad authentication method",
+            ReasonCode::KeepAliveTimeout => "Keep Alive timeout",
+            ReasonCode::SessionTakenOver => "Session taken over",
+            ReasonCode::TopicFilterInvalid => "Topic Filter invalid",
+            ReasonCode::TopicNameInvalid => "Topic Name invalid",
+            ReasonCode::PacketIdentifierInUse => "Packet Identifier in use",
+            ReasonCode::PacketIdentifierNotFound => "Packet Identifier not found",
+            ReasonCode::ReceiveMaximumExceeded => "Receive Maximum exceeded",
+            ReasonCode::TopicAliasInvalid => "Topic Alias invalid",
+            ReasonCode::PacketTooLarge => "Packet too large",
+            ReasonCode::MessageRateTooHigh => "Message rate too high",
+            ReasonCode::QuotaExceeded => "Quota exceeded",
+            ReasonCode::AdministrativeAction => "Administrative action",
+            ReasonCode::PayloadFormatInvalid => "Payload format invalid",
+            ReasonCode::RetainNotSupported => "Retain not supported",
+            ReasonCode::QoSNotSupported => "QoS not supported",
+            ReasonCode::UseAnotherServer => "Use another server",
+            ReasonCode::ServerMoved => "Server moved",
+            ReasonCode::SharedSubscriptionsNotSupported => "Shared Subscriptions not supported",
+            ReasonCode::ConnectionRateExceeded => "Connection rate exceeded",
+            ReasonCode::MaximumConnectTime => "Maximum connect time",
+            ReasonCode::SubscriptionIdentifiersNotSupported => {
+                "Subscription Identifiers not supported"
+            }
+            ReasonCode::WildcardSubscriptionsNotSupported => "Wildcard Subscriptions not supported",
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.value() < 0x80
+    }
+
+    pub fn is_failure(&self) -> bool {
+        !self.is_success()
+    }
+
+    /// Whether this reason code may be sent in a packet of the given type.
+    pub fn is_valid_for(&self, packet_type: &PacketType) -> bool {
+        use PacketType::*;
+        match self {
+            ReasonCode::Success => matches!(
+                packet_type,
+                CONNACK
+                    | PUBACK
+                    | PUBREC
+                    | PUBREL
+                    | PUBCOMP
+                    | SUBACK
+                    | UNSUBACK
+                    | DISCONNECT
+                    | AUTH
+            ),
+            ReasonCode::GrantedQoS1 | ReasonCode::GrantedQoS2 => *packet_type == SUBACK,
+            ReasonCode::DisconnectWithWillMessage
+            | ReasonCode::ServerShuttingDown
+            | ReasonCode::KeepAliveTimeout
+            | ReasonCode::SessionTakenOver
+            | ReasonCode::ReceiveMaximumExceeded
+            | ReasonCode::TopicAliasInvalid
+            | ReasonCode::MessageRateTooHigh
+            | ReasonCode::AdministrativeAction
+            | ReasonCode::MaximumConnectTime => *packet_type == DISCONNECT,
+            ReasonCode::NoMatchingSubscribers => matches!(packet_type, PUBACK | PUBREC),
+            ReasonCode::NoSubscriptionExisted => *packet_type == UNSUBACK,
+            ReasonCode::ContinueAuthentication | ReasonCode::ReAuthenticate => *packet_type == AUTH,
+            ReasonCode::UnspecifiedError
+            | ReasonCode::ImplementationSpecificError
+            | ReasonCode::NotAuthorized => matches!(
+                packet_type,
+                CONNACK | PUBACK | PUBREC | SUBACK | UNSUBACK | DISCONNECT
+            ),
+            ReasonCode::MalformedPacket
+            | ReasonCode::ProtocolError
+            | ReasonCode::ServerBusy
+            | ReasonCode::BadAuthenticationMethod
+            | ReasonCode::PacketTooLarge
+            | ReasonCode::RetainNotSupported
+            | ReasonCode::QoSNotSupported
+            | ReasonCode::UseAnotherServer
+            | ReasonCode::ServerMoved
+            | ReasonCode::ConnectionRateExceeded => matches!(packet_type, CONNACK | DISCONNECT),
+            ReasonCode::UnsupportedProtocolVersion
+            | ReasonCode::ClientIdentifierNotValid
+            | ReasonCode::BadUserNameOrPassword
+            | ReasonCode::ServerUnavailable
+            | ReasonCode::Banned => *packet_type == CONNACK,
+            ReasonCode::TopicFilterInvalid => matches!(packet_type, SUBACK | UNSUBACK | DISCONNECT),
+            ReasonCode::TopicNameInvalid | ReasonCode::PayloadFormatInvalid => {
+                matches!(packet_type, CONNACK | PUBACK | PUBREC | DISCONNECT)
+            }
+            ReasonCode::PacketIdentifierInUse => {
+                matches!(packet_type, PUBACK | PUBREC | SUBACK | UNSUBACK)
+            }
+            ReasonCode::PacketIdentifierNotFound => matches!(packet_type, PUBREL | PUBCOMP),
+            ReasonCode::QuotaExceeded => {
+                matches!(packet_type, CONNACK | PUBACK | PUBREC | SUBACK | DISCONNECT)
+            }
+            ReasonCode::SharedSubscriptionsNotSupported
+            | ReasonCode::SubscriptionIdentifiersNotSupported
+            | ReasonCode::WildcardSubscriptionsNotSupported => {
+                matches!(packet_type, SUBACK | DISCONNECT)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (0x{:02X})", self.description(), self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::PacketType;
+    use crate::reason_code::ReasonCode;
+
+    #[test]
+    fn test_value_from_value() {
+        for value in 0..=255 {
+            if let Ok(reason_code) = ReasonCode::from_value(value) {
+                assert_eq!(reason_code.value(), value);
+            }
+        }
+        assert_eq!(
+            ReasonCode::from_value(0x97).unwrap(),
+            ReasonCode::QuotaExceeded
+        );
+        assert!(ReasonCode::from_value(0x03).is_err());
+    }
+
+    #[test]
+    fn test_is_valid_for() {
+        assert!(ReasonCode::NotAuthorized.is_valid_for(&PacketType::CONNACK));
+        assert!(ReasonCode::NotAuthorized.is_valid_for(&PacketType::PUBACK));
+        assert!(!ReasonCode::NotAuthorized.is_valid_for(&PacketType::PUBCOMP));
+        assert!(ReasonCode::Success.is_valid_for(&PacketType::PUBCOMP));
+        assert!(!ReasonCode::Success.is_valid_for(&PacketType::PINGREQ));
+    }
+
+    #[test]
+    fn test_from_value_for() {
+        assert!(ReasonCode::from_value_for(0x92, &PacketType::PUBREL).is_ok());
+        assert!(ReasonCode::from_value_for(0x92, &PacketType::PUBACK).is_err());
+    }
+
+    #[test]
+    fn test_is_success() {
+        assert!(ReasonCode::GrantedQoS2.is_success());
+        assert!(ReasonCode::NoMatchingSubscribers.is_success());
+        assert!(ReasonCode::TopicNameInvalid.is_failure());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ReasonCode::QuotaExceeded.to_string(),
+            "Quota exceeded (0x97)"
+        );
+    }
+}
@@ -1,39 +1,682 @@
-use std::io::BufReader;
-use std::net::{TcpListener, TcpStream};
+use crate::control_packet::disconnect::Disconnect;
+use crate::error::Error;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::server::broker::{lock, Broker};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-struct Connection {
-    client_id: String,
-    subscriptions: Vec<String>,
-}
+mod auth;
+mod broker;
+mod connection;
+mod options;
+mod retained;
+mod stored;
+mod topic_tree;
+mod will;
+
+pub use crate::server::auth::password_file::PasswordFile;
+pub use crate::server::auth::{Authentication, Authenticator};
+pub use crate::server::options::ServerOptions;
+
+const DEFAULT_ADDR: &str = "0.0.0.0:1883";
 
+/// An MQTT 5 broker. Every accepted connection is served on its own thread, so an idle
+/// client does not hold up the others. Clones share the same state.
+#[derive(Clone)]
 pub struct Server {
-    connections: Vec<Connection>,
+    broker: Arc<Broker>,
+    options: ServerOptions,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    // where each accept loop can be woken up to see the server shut down
+    listeners: Arc<Mutex<Vec<SocketAddr>>>,
 }
 
 impl Server {
     pub fn new() -> Self {
+        Server::with_options(ServerOptions::new())
+    }
+
+    pub fn with_options(options: ServerOptions) -> Self {
         Server {
-            connections: Vec::new(),
+            broker: Arc::new(Broker::new(options.max_queued_messages())),
+            options,
+            authenticator: None,
+            listeners: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
-    pub fn listen(&mut self) {
-        let listener = TcpListener::bind("0.0.0.0:1883").unwrap();
+    /// Asks `authenticator` about every CONNECT; without one every client is accepted.
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Serves port 1883 on every interface until the server shuts down.
+    pub fn listen(&self) -> Result<(), Error> {
+        self.serve(TcpListener::bind(DEFAULT_ADDR)?);
+        Ok(())
+    }
+
+    /// Serves the connections accepted by `listener` until the server shuts down.
+    pub fn serve(&self, listener: TcpListener) {
+        if let Ok(addr) = listener.local_addr() {
+            lock(&self.listeners).push(wake_addr(addr));
+        }
+        // checked after the address is known, so a shutdown either sees it or is seen here
+        if self.broker.is_shut_down() {
+            return;
+        }
+        self.broker.start_will_timer();
         for stream in listener.incoming() {
-            let stream = stream.unwrap();
-            self.handle_connection(stream);
+            if self.broker.is_shut_down() {
+                break;
+            }
+            // a client giving up before it was accepted is no reason to stop
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let broker = Arc::clone(&self.broker);
+            let options = self.options.clone();
+            let authenticator = self.authenticator.clone();
+            thread::spawn(move || {
+                connection::run(&broker, &options, authenticator.as_deref(), stream)
+            });
         }
     }
 
-    fn handle_connection(&mut self, mut stream: TcpStream) {
-        //TODO give client 30s to send CONNECT otherwise close
-        //TODO if anything but a CONNECT comes first then close
-        let _buf_reader = BufReader::new(&mut stream);
-        //TODO handle CONNECT packet then store the connection
+    /// Client identifiers of the connected clients.
+    pub fn client_ids(&self) -> Vec<String> {
+        self.broker.client_ids()
     }
 
+    /// Stops accepting connections and closes those open with DISCONNECT Server shutting
+    /// down. Their sessions and wills are dealt with as for any connection the server
+    /// closes.
     pub fn shutdown(&self) {
-        //TODO close connections
-        //TODO close listen threads
+        let disconnect = Disconnect::new(ReasonCode::ServerShuttingDown, Properties::default());
+        for writer in self.broker.shutdown() {
+            let _ = writer.send(&disconnect);
+            writer.close();
+        }
+        // each accept loop sees the shutdown with the next connection it accepts
+        for addr in lock(&self.listeners).drain(..) {
+            let _ = TcpStream::connect(addr);
+        }
+    }
+}
+
+// a listener bound to every interface is reached on the loopback one
+fn wake_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Client, ClientOptions, ConnectOptions, ConnectionEvent};
+    use crate::common::{FourByteInt, UTF8String};
+    use crate::control_packet::connack::Connack;
+    use crate::control_packet::connect::Connect;
+    use crate::control_packet::disconnect::Disconnect;
+    use crate::control_packet::pingreq::Pingreq;
+    use crate::control_packet::pingresp::Pingresp;
+    use crate::control_packet::subscribe::Subscribe;
+    use crate::control_packet::{ControlPacket, Packet};
+    use crate::decoder::Decoder;
+    use crate::error::Error;
+    use crate::payload::connect::{ConnectPayload, Will};
+    use crate::payload::subscribe::{Subscription, SubscriptionOptions};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+    use crate::server::{Authentication, PasswordFile, Server, ServerOptions};
+    use std::io::Write;
+    use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // starts a server on a free port
+    fn start(options: ServerOptions) -> (Server, SocketAddr) {
+        serve(Server::with_options(options))
+    }
+
+    fn serve(server: Server) -> (Server, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+        (server, addr)
+    }
+
+    fn client(addr: SocketAddr, client_id: &str) -> Client {
+        let options = ClientOptions::new("127.0.0.1").with_addrs(&[addr]);
+        Client::with_options(String::from(client_id), &options).unwrap()
+    }
+
+    #[test]
+    fn test_connect() {
+        let (server, addr) = start(ServerOptions::new());
+        let client = client(addr, "foo");
+        let result = client.connect(&ConnectOptions::new()).unwrap();
+        assert_eq!(result.reason_code(), ReasonCode::Success);
+        assert_eq!(server.client_ids(), vec![String::from("foo")]);
+        client.disconnect().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(server.client_ids().is_empty());
+    }
+
+    #[test]
+    fn test_connect_assigns_client_id() {
+        let (server, addr) = start(ServerOptions::new());
+        let client = client(addr, "");
+        let result = client.connect(&ConnectOptions::new()).unwrap();
+        let client_id = result.assigned_client_identifier().unwrap();
+        assert_eq!(client.client_id(), client_id);
+        assert_eq!(server.client_ids(), vec![String::from(client_id)]);
+    }
+
+    #[test]
+    fn test_idle_connection_does_not_block() {
+        let (_server, addr) = start(ServerOptions::new());
+        let _idle = TcpStream::connect(addr).unwrap();
+        let client = client(addr, "foo");
+        assert!(client.connect(&ConnectOptions::new()).is_ok());
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        let options = ServerOptions::new().with_connect_timeout(Duration::from_millis(50));
+        let (_server, addr) = start(options);
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        // closed without an answer
+        assert!(Decoder::default().read_packet(&mut stream).is_err());
+    }
+
+    #[test]
+    fn test_first_packet_must_be_connect() {
+        let (server, addr) = start(ServerOptions::new());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(&Pingreq::new().as_bytes()).unwrap();
+        assert!(Decoder::default().read_packet(&mut stream).is_err());
+        assert!(server.client_ids().is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_protocol_version() {
+        let (_server, addr) = start(ServerOptions::new());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // an MQTT 3.1.1 CONNECT
+        stream
+            .write_all(&[0x10, 12, 0, 4, b'M', b'Q', b'T', b'T', 4, 2, 0, 60, 0, 0])
+            .unwrap();
+        let connack = Connack::new(
+            false,
+            ReasonCode::UnsupportedProtocolVersion,
+            Properties::default(),
+        );
+        assert_eq!(
+            Decoder::default().read_packet(&mut stream).unwrap(),
+            Packet::Connack(connack)
+        );
+    }
+
+    #[test]
+    fn test_same_client_id_replaces_connection() {
+        let (server, addr) = start(ServerOptions::new());
+        let first = client(addr, "foo");
+        let events = first.connection_events();
+        first.connect(&ConnectOptions::new()).unwrap();
+        let second = client(addr, "foo");
+        second.connect(&ConnectOptions::new()).unwrap();
+        // the first connection is told why it is closed
+        let reason = Error::Disconnected(ReasonCode::SessionTakenOver).to_string();
+        assert!(events.iter().any(|event| event
+            == ConnectionEvent::ConnectionLost {
+                reason: reason.clone()
+            }));
+        assert_eq!(server.client_ids(), vec![String::from("foo")]);
+    }
+
+    #[test]
+    fn test_routes_publish() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let subscription = subscriber.subscribe("sport/+/player1", 2).unwrap();
+        assert_eq!(subscription.granted_qos(), 2);
+        let publisher = client(addr, "publisher");
+        publisher.connect(&ConnectOptions::new()).unwrap();
+        for qos in 0..=2 {
+            let reason_code = publisher
+                .publish("sport/tennis/player1", &[qos], qos, false)
+                .unwrap();
+            assert_eq!(reason_code, ReasonCode::Success);
+            let message = subscription.next_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(message.topic(), "sport/tennis/player1");
+            assert_eq!(message.payload(), &[qos]);
+            assert_eq!(message.qos(), qos);
+        }
+        assert_eq!(
+            publisher.publish("sport/tennis", &[], 1, false).unwrap(),
+            ReasonCode::NoMatchingSubscribers
+        );
+    }
+
+    #[test]
+    fn test_routes_once_at_highest_qos() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let all = subscriber.subscribe("#", 0).unwrap();
+        let sport = subscriber.subscribe("sport/#", 1).unwrap();
+        subscriber.publish("sport", &[1, 2, 3], 2, false).unwrap();
+        let message = all.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.qos(), 1);
+        // the client hands the one message to both of its matching subscriptions
+        assert_eq!(sport.next_timeout(Duration::from_secs(5)).unwrap(), message);
+        assert!(all.next_timeout(Duration::from_millis(100)).is_none());
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let (_server, addr) = start(ServerOptions::new());
+        let client = client(addr, "foo");
+        client.connect(&ConnectOptions::new()).unwrap();
+        let subscription = client.subscribe("foo", 1).unwrap();
+        assert_eq!(client.unsubscribe("foo").unwrap(), ReasonCode::Success);
+        assert_eq!(
+            client.unsubscribe("foo").unwrap(),
+            ReasonCode::NoSubscriptionExisted
+        );
+        assert_eq!(
+            client.publish("foo", &[], 1, false).unwrap(),
+            ReasonCode::NoMatchingSubscribers
+        );
+        assert!(subscription.try_next().is_none());
+    }
+
+    #[test]
+    fn test_dollar_topics_not_matched_by_wildcards() {
+        let (_server, addr) = start(ServerOptions::new());
+        let client = client(addr, "foo");
+        client.connect(&ConnectOptions::new()).unwrap();
+        let all = client.subscribe("#", 0).unwrap();
+        assert_eq!(
+            client.publish("$internal/uptime", &[], 1, false).unwrap(),
+            ReasonCode::NoMatchingSubscribers
+        );
+        assert!(all.try_next().is_none());
+    }
+
+    #[test]
+    fn test_shared_subscriptions_not_supported() {
+        let (_server, addr) = start(ServerOptions::new());
+        let client = client(addr, "foo");
+        let result = client.connect(&ConnectOptions::new()).unwrap();
+        assert!(!result.shared_subscription_available());
+        assert!(!result.subscription_identifiers_available());
+        assert!(matches!(
+            client.subscribe("$share/group/foo", 0),
+            Err(Error::SubscriptionRejected(
+                ReasonCode::SharedSubscriptionsNotSupported
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_subscriptions_end_with_connection() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        subscriber.subscribe("foo", 0).unwrap();
+        subscriber.disconnect().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let publisher = client(addr, "publisher");
+        publisher.connect(&ConnectOptions::new()).unwrap();
+        assert_eq!(
+            publisher.publish("foo", &[], 1, false).unwrap(),
+            ReasonCode::NoMatchingSubscribers
+        );
+    }
+
+    #[test]
+    fn test_retained_message() {
+        let (_server, addr) = start(ServerOptions::new());
+        let publisher = client(addr, "publisher");
+        publisher.connect(&ConnectOptions::new()).unwrap();
+        publisher.publish("sport/tennis", &[1], 1, true).unwrap();
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let subscription = subscriber.subscribe("sport/+", 1).unwrap();
+        let message = subscription.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.topic(), "sport/tennis");
+        assert_eq!(message.payload(), &[1]);
+        assert!(message.retain());
+        // a message published while subscribed is not flagged as retained
+        publisher.publish("sport/tennis", &[2], 1, true).unwrap();
+        let message = subscription.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.payload(), &[2]);
+        assert!(!message.retain());
+        publisher.publish("sport/tennis", &[], 0, true).unwrap();
+        subscription.next_timeout(Duration::from_secs(5)).unwrap();
+        let late = subscriber.subscribe("sport/#", 1).unwrap();
+        assert!(late.next_timeout(Duration::from_millis(100)).is_none());
+    }
+
+    #[test]
+    fn test_session_resumed() {
+        let (_server, addr) = start(ServerOptions::new());
+        let options = ConnectOptions::new()
+            .with_clean_start(false)
+            .with_session_expiry_interval(60);
+        let subscriber = client(addr, "subscriber");
+        let result = subscriber.connect(&options).unwrap();
+        assert!(!result.session_present());
+        subscriber.subscribe("foo", 1).unwrap();
+        subscriber.disconnect().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let publisher = client(addr, "publisher");
+        publisher.connect(&ConnectOptions::new()).unwrap();
+        // queued for the subscriber while it is away
+        assert_eq!(
+            publisher.publish("foo", &[1], 1, false).unwrap(),
+            ReasonCode::Success
+        );
+        // read without a client, which would drop messages for subscriptions it has not made
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let properties =
+            Properties::new(vec![Property::SessionExpiryInterval(FourByteInt::new(60))]);
+        let connect = Connect::new(ConnectPayload::new("subscriber"), false, 0, properties);
+        stream.write_all(&connect.as_bytes()).unwrap();
+        let mut decoder = Decoder::default();
+        match decoder.read_packet(&mut stream).unwrap() {
+            Packet::Connack(connack) => assert!(connack.session_present()),
+            packet => panic!("unexpected {packet:?}"),
+        }
+        match decoder.read_packet(&mut stream).unwrap() {
+            Packet::Publish(publish) => {
+                assert_eq!(publish.topic_name(), "foo");
+                assert_eq!(publish.payload(), &[1]);
+                assert_eq!(publish.qos(), 1);
+            }
+            packet => panic!("unexpected {packet:?}"),
+        }
+    }
+
+    #[test]
+    fn test_session_ends_with_connection() {
+        let (_server, addr) = start(ServerOptions::new());
+        let options = ConnectOptions::new().with_clean_start(false);
+        let first = client(addr, "foo");
+        first.connect(&options).unwrap();
+        first.disconnect().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let second = client(addr, "foo");
+        assert!(!second.connect(&options).unwrap().session_present());
+    }
+
+    fn connect_with_will(addr: SocketAddr, client_id: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let will = Will::new("status", b"gone".to_vec(), 1, false, Properties::default());
+        let payload = ConnectPayload::new(client_id).with_will(will);
+        let connect = Connect::new(payload, true, 0, Properties::default());
+        stream.write_all(&connect.as_bytes()).unwrap();
+        Decoder::default().read_packet(&mut stream).unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_will_published_on_connection_loss() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let status = subscriber.subscribe("status", 1).unwrap();
+        let stream = connect_with_will(addr, "foo");
+        stream.shutdown(Shutdown::Both).unwrap();
+        let message = status.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.payload(), b"gone");
+        assert_eq!(message.qos(), 1);
+    }
+
+    #[test]
+    fn test_will_on_disconnect() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let status = subscriber.subscribe("status", 1).unwrap();
+        for (reason_code, published) in [
+            (ReasonCode::NORMAL_DISCONNECTION, false),
+            (ReasonCode::DisconnectWithWillMessage, true),
+        ] {
+            let mut stream = connect_with_will(addr, "foo");
+            let disconnect = Disconnect::new(reason_code, Properties::default());
+            stream.write_all(&disconnect.as_bytes()).unwrap();
+            let message = status.next_timeout(Duration::from_millis(500));
+            assert_eq!(message.is_some(), published);
+        }
+    }
+
+    #[test]
+    fn test_will_delay() {
+        let (_server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let status = subscriber.subscribe("status", 1).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let properties = Properties::new(vec![Property::WillDelayInterval(FourByteInt::new(1))]);
+        let will = Will::new("status", b"gone".to_vec(), 1, false, properties);
+        let payload = ConnectPayload::new("foo").with_will(will);
+        let properties =
+            Properties::new(vec![Property::SessionExpiryInterval(FourByteInt::new(60))]);
+        let connect = Connect::new(payload, true, 0, properties);
+        stream.write_all(&connect.as_bytes()).unwrap();
+        Decoder::default().read_packet(&mut stream).unwrap();
+        let start = Instant::now();
+        stream.shutdown(Shutdown::Both).unwrap();
+        let message = status.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.payload(), b"gone");
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    fn connect_with_keep_alive(addr: SocketAddr, keep_alive: u16) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let will = Will::new("status", b"gone".to_vec(), 0, false, Properties::default());
+        let payload = ConnectPayload::new("foo").with_will(will);
+        let connect = Connect::new(payload, true, keep_alive, Properties::default());
+        stream.write_all(&connect.as_bytes()).unwrap();
+        Decoder::default().read_packet(&mut stream).unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_keep_alive_timeout() {
+        let (server, addr) = start(ServerOptions::new());
+        let subscriber = client(addr, "subscriber");
+        subscriber.connect(&ConnectOptions::new()).unwrap();
+        let status = subscriber.subscribe("status", 0).unwrap();
+        let mut stream = connect_with_keep_alive(addr, 1);
+        let start = Instant::now();
+        let disconnect = Disconnect::new(ReasonCode::KeepAliveTimeout, Properties::default());
+        assert_eq!(
+            Decoder::default().read_packet(&mut stream).unwrap(),
+            Packet::Disconnect(disconnect)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(1500));
+        // the will goes out as for any other connection that was not closed normally
+        let message = status.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.payload(), b"gone");
+        assert_eq!(server.client_ids(), vec![String::from("subscriber")]);
+    }
+
+    #[test]
+    fn test_pingreq_keeps_connection_alive() {
+        let (server, addr) = start(ServerOptions::new());
+        let mut stream = connect_with_keep_alive(addr, 1);
+        let mut decoder = Decoder::default();
+        for _ in 0..4 {
+            thread::sleep(Duration::from_millis(500));
+            stream.write_all(&Pingreq::new().as_bytes()).unwrap();
+            assert_eq!(
+                decoder.read_packet(&mut stream).unwrap(),
+                Packet::Pingresp(Pingresp::new())
+            );
+        }
+        assert_eq!(server.client_ids(), vec![String::from("foo")]);
+    }
+
+    #[test]
+    fn test_server_keep_alive() {
+        let (server, addr) = start(ServerOptions::new().with_server_keep_alive(1));
+        let client = client(addr, "bar");
+        let result = client
+            .connect(&ConnectOptions::new().with_keep_alive(60))
+            .unwrap();
+        assert_eq!(result.server_keep_alive(), Some(1));
+        // the client pings often enough
+        thread::sleep(Duration::from_millis(2500));
+        assert_eq!(server.client_ids(), vec![String::from("bar")]);
+        // and a client that does not is disconnected
+        let mut stream = connect_with_keep_alive(addr, 60);
+        let disconnect = Disconnect::new(ReasonCode::KeepAliveTimeout, Properties::default());
+        assert_eq!(
+            Decoder::default().read_packet(&mut stream).unwrap(),
+            Packet::Disconnect(disconnect)
+        );
+    }
+
+    #[test]
+    fn test_authenticator() {
+        let authenticator = |client_id: &str,
+                             user_name: Option<&str>,
+                             password: Option<&[u8]>,
+                             peer_addr: SocketAddr| {
+            if !peer_addr.ip().is_loopback() {
+                Authentication::NotAuthorized
+            } else if client_id == "foo" && user_name == Some("user") && password == Some(b"pw") {
+                Authentication::Accepted
+            } else {
+                Authentication::BadUserNameOrPassword
+            }
+        };
+        let (server, addr) = serve(Server::new().with_authenticator(authenticator));
+        let options = ConnectOptions::new().with_user_name("user");
+        assert!(matches!(
+            client(addr, "foo").connect(&options.clone().with_password(b"wrong")),
+            Err(Error::ConnectionRefused(ReasonCode::BadUserNameOrPassword))
+        ));
+        assert!(server.client_ids().is_empty());
+        let client = client(addr, "foo");
+        let result = client.connect(&options.with_password(b"pw")).unwrap();
+        assert_eq!(result.reason_code(), ReasonCode::Success);
+    }
+
+    #[test]
+    fn test_password_file() {
+        let mut passwords = PasswordFile::new().with_iterations(1000);
+        passwords.set_password("alice", b"secret").unwrap();
+        let (_server, addr) = serve(Server::new().with_authenticator(passwords));
+        assert!(matches!(
+            client(addr, "foo").connect(&ConnectOptions::new()),
+            Err(Error::ConnectionRefused(ReasonCode::NotAuthorized))
+        ));
+        let options = ConnectOptions::new()
+            .with_user_name("alice")
+            .with_password(b"secret");
+        assert!(client(addr, "foo").connect(&options).is_ok());
+    }
+
+    #[test]
+    fn test_authentication_method_not_supported() {
+        let (server, addr) = start(ServerOptions::new());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let properties = Properties::new(vec![Property::AuthenticationMethod(UTF8String::new(
+            "SCRAM-SHA-1",
+        ))]);
+        let connect = Connect::new(ConnectPayload::new("foo"), true, 0, properties);
+        stream.write_all(&connect.as_bytes()).unwrap();
+        let connack = Connack::new(
+            false,
+            ReasonCode::BadAuthenticationMethod,
+            Properties::default(),
+        );
+        assert_eq!(
+            Decoder::default().read_packet(&mut stream).unwrap(),
+            Packet::Connack(connack)
+        );
+        assert!(server.client_ids().is_empty());
+    }
+
+    #[test]
+    fn test_slow_subscriber_disconnected() {
+        let options = ServerOptions::new().with_write_timeout(Duration::from_millis(100));
+        let (server, addr) = start(options);
+        // subscribes and then never reads
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let connect = Connect::new(ConnectPayload::new("slow"), true, 0, Properties::default());
+        stream.write_all(&connect.as_bytes()).unwrap();
+        let mut decoder = Decoder::default();
+        decoder.read_packet(&mut stream).unwrap();
+        let subscription = Subscription::new("foo", SubscriptionOptions::new(0));
+        let subscribe = Subscribe::new(1, vec![subscription], Properties::default());
+        stream.write_all(&subscribe.as_bytes()).unwrap();
+        decoder.read_packet(&mut stream).unwrap();
+        let publisher = client(addr, "publisher");
+        publisher.connect(&ConnectOptions::new()).unwrap();
+        let payload = vec![0; 64 * 1024];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while server.client_ids().contains(&String::from("slow")) {
+            assert!(Instant::now() < deadline);
+            publisher.publish("foo", &payload, 0, false).unwrap();
+        }
+        // the publisher is not held up any longer
+        assert_eq!(
+            publisher.publish("foo", &payload, 1, false).unwrap(),
+            ReasonCode::NoMatchingSubscribers
+        );
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let (server, addr) = start(ServerOptions::new());
+        let client = client(addr, "foo");
+        let events = client.connection_events();
+        client.connect(&ConnectOptions::new()).unwrap();
+        server.shutdown();
+        let reason = Error::Disconnected(ReasonCode::ServerShuttingDown).to_string();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !events
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .is_ok_and(|event| {
+                event
+                    == ConnectionEvent::ConnectionLost {
+                        reason: reason.clone(),
+                    }
+            })
+        {
+            assert!(Instant::now() < deadline);
+        }
+        // the listener closes once the accept loop has seen the shutdown
+        while TcpStream::connect(addr).is_ok() || !server.client_ids().is_empty() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(10));
+        }
     }
 }
@@ -0,0 +1,56 @@
+use crate::reason_code::ReasonCode;
+use std::net::SocketAddr;
+
+pub(crate) mod password_file;
+mod pbkdf2;
+mod sha256;
+
+/// What an [`Authenticator`] makes of a CONNECT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Authentication {
+    Accepted,
+    BadUserNameOrPassword,
+    NotAuthorized,
+    BadAuthenticationMethod,
+}
+
+impl Authentication {
+    /// The reason code of the CONNACK that answers the CONNECT.
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        match self {
+            Authentication::Accepted => ReasonCode::Success,
+            Authentication::BadUserNameOrPassword => ReasonCode::BadUserNameOrPassword,
+            Authentication::NotAuthorized => ReasonCode::NotAuthorized,
+            Authentication::BadAuthenticationMethod => ReasonCode::BadAuthenticationMethod,
+        }
+    }
+}
+
+/// Decides whether a client may connect. The server asks on every CONNECT, before the
+/// session is touched, and refuses the connection with the matching CONNACK reason code
+/// unless the answer is [`Authentication::Accepted`].
+pub trait Authenticator: Send + Sync {
+    /// `client_id` is empty if the client left it to the server to assign one.
+    fn authenticate(
+        &self,
+        client_id: &str,
+        user_name: Option<&str>,
+        password: Option<&[u8]>,
+        peer_addr: SocketAddr,
+    ) -> Authentication;
+}
+
+impl<F> Authenticator for F
+where
+    F: Fn(&str, Option<&str>, Option<&[u8]>, SocketAddr) -> Authentication + Send + Sync,
+{
+    fn authenticate(
+        &self,
+        client_id: &str,
+        user_name: Option<&str>,
+        password: Option<&[u8]>,
+        peer_addr: SocketAddr,
+    ) -> Authentication {
+        self(client_id, user_name, password, peer_addr)
+    }
+}
@@ -0,0 +1,343 @@
+use crate::error::Error;
+use crate::random;
+use crate::server::auth::pbkdf2;
+use crate::server::auth::{Authentication, Authenticator};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::hint;
+use std::net::SocketAddr;
+use std::path::Path;
+
+const SALT_LEN: usize = 16;
+// for new passwords, each line keeps the count its hash was derived with
+const DEFAULT_ITERATIONS: u32 = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    iterations: u32,
+    salt: Vec<u8>,
+    hash: [u8; 32],
+}
+
+impl Entry {
+    fn new(password: &[u8], iterations: u32) -> Self {
+        let salt = Vec::from(generate_salt());
+        let hash = pbkdf2::derive(password, &salt, iterations);
+        Entry {
+            iterations,
+            salt,
+            hash,
+        }
+    }
+
+    // compares every byte, so the time taken does not tell how much of the hash matched
+    fn verify(&self, password: &[u8]) -> bool {
+        pbkdf2::derive(password, &self.salt, self.iterations)
+            .iter()
+            .zip(self.hash)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+    }
+}
+
+/// Accepts clients whose user name and password are in a password file. Each line of
+/// the file holds a user name, an iteration count, a salt and the PBKDF2-HMAC-SHA256 hash
+/// of the password, separated by colons, with the salt and hash in hex:
+///
+/// ```text
+/// user_name:iterations:salt:hash
+/// ```
+///
+/// Empty lines and lines starting with `#` are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordFile {
+    entries: HashMap<String, Entry>,
+    iterations: u32,
+}
+
+impl PasswordFile {
+    pub fn new() -> Self {
+        PasswordFile {
+            entries: HashMap::new(),
+            iterations: DEFAULT_ITERATIONS,
+        }
+    }
+
+    /// The PBKDF2 iteration count for the passwords set from now on, at least 1. More
+    /// iterations make each guess at a password cost more, for an attacker and for every
+    /// CONNECT alike.
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        parse(&fs::read_to_string(path)?)
+    }
+
+    /// Writes the users sorted by user name, so saving the same users gives the same file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut user_names: Vec<&String> = self.entries.keys().collect();
+        user_names.sort();
+        let mut contents = String::new();
+        for user_name in user_names {
+            let entry = &self.entries[user_name];
+            let _ = writeln!(
+                contents,
+                "{user_name}:{}:{}:{}",
+                entry.iterations,
+                to_hex(&entry.salt),
+                to_hex(&entry.hash)
+            );
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Adds the user, or changes their password, with a new salt. A user name may not start
+    /// with `#`, as the line would be read back as a comment.
+    pub fn set_password(&mut self, user_name: &str, password: &[u8]) -> Result<(), Error> {
+        if user_name.is_empty() || user_name.starts_with('#') || user_name.contains(['\n', '\r']) {
+            return Err(Error::InvalidArgument("invalid user name"));
+        }
+        self.entries.insert(
+            String::from(user_name),
+            Entry::new(password, self.iterations),
+        );
+        Ok(())
+    }
+
+    /// Returns whether the user was there to remove.
+    pub fn remove(&mut self, user_name: &str) -> bool {
+        self.entries.remove(user_name).is_some()
+    }
+
+    pub fn verify(&self, user_name: &str, password: &[u8]) -> bool {
+        match self.entries.get(user_name) {
+            Some(entry) => entry.verify(password),
+            None => {
+                let _ = hint::black_box(self.dummy().verify(password));
+                false
+            }
+        }
+    }
+
+    // An unknown user is checked against this, so it costs as much as a wrong password and
+    // the time taken does not tell which user names are in the file. It takes as many
+    // iterations as the costliest entry, or as a new one in an empty file.
+    fn dummy(&self) -> Entry {
+        let iterations = self
+            .entries
+            .values()
+            .map(|entry| entry.iterations)
+            .max()
+            .unwrap_or(self.iterations);
+        Entry {
+            iterations,
+            salt: vec![0; SALT_LEN],
+            hash: [0; 32],
+        }
+    }
+}
+
+impl Default for PasswordFile {
+    fn default() -> Self {
+        PasswordFile::new()
+    }
+}
+
+impl Authenticator for PasswordFile {
+    fn authenticate(
+        &self,
+        _client_id: &str,
+        user_name: Option<&str>,
+        password: Option<&[u8]>,
+        _peer_addr: SocketAddr,
+    ) -> Authentication {
+        match (user_name, password) {
+            (None, _) => Authentication::NotAuthorized,
+            (Some(user_name), Some(password)) if self.verify(user_name, password) => {
+                Authentication::Accepted
+            }
+            _ => Authentication::BadUserNameOrPassword,
+        }
+    }
+}
+
+fn parse(contents: &str) -> Result<PasswordFile, Error> {
+    let mut entries = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // the user name comes first, so it is the one part that may hold a colon
+        let mut parts = line.rsplitn(4, ':');
+        let (hash, salt, iterations, user_name) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(hash), Some(salt), Some(iterations), Some(user_name))
+                    if !user_name.is_empty() =>
+                {
+                    (hash, salt, iterations, user_name)
+                }
+                _ => return Err(Error::InvalidArgument("malformed password file")),
+            };
+        let iterations = iterations
+            .parse()
+            .ok()
+            .filter(|iterations| *iterations > 0)
+            .ok_or(Error::InvalidArgument("malformed password file"))?;
+        let salt = from_hex(salt)?;
+        let hash = from_hex(hash)?
+            .try_into()
+            .map_err(|_| Error::InvalidArgument("malformed password file"))?;
+        entries.insert(
+            String::from(user_name),
+            Entry {
+                iterations,
+                salt,
+                hash,
+            },
+        );
+    }
+    Ok(PasswordFile {
+        entries,
+        ..PasswordFile::new()
+    })
+}
+
+fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0; SALT_LEN];
+    for chunk in salt.chunks_exact_mut(8) {
+        chunk.copy_from_slice(&random::next_u64().to_be_bytes());
+    }
+    salt
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(Error::InvalidArgument("malformed password file"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| Error::InvalidArgument("malformed password file"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::server::auth::password_file::{parse, PasswordFile};
+    use crate::server::auth::{Authentication, Authenticator};
+    use std::net::SocketAddr;
+    use std::{env, fs, process};
+
+    // few iterations, or the tests take seconds each without optimizations
+    fn password_file() -> PasswordFile {
+        PasswordFile::new().with_iterations(1000)
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut passwords = password_file();
+        passwords.set_password("alice", b"secret").unwrap();
+        assert!(passwords.verify("alice", b"secret"));
+        assert!(!passwords.verify("alice", b"secreT"));
+        assert!(!passwords.verify("bob", b"secret"));
+        passwords.set_password("alice", b"changed").unwrap();
+        assert!(!passwords.verify("alice", b"secret"));
+        assert!(passwords.verify("alice", b"changed"));
+        assert!(passwords.remove("alice"));
+        assert!(!passwords.verify("alice", b"changed"));
+        assert!(matches!(
+            passwords.set_password("", b"secret"),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_salted() {
+        let mut passwords = password_file();
+        passwords.set_password("alice", b"secret").unwrap();
+        passwords.set_password("bob", b"secret").unwrap();
+        let alice = &passwords.entries["alice"];
+        let bob = &passwords.entries["bob"];
+        assert_ne!(alice.salt, bob.salt);
+        assert_ne!(alice.hash, bob.hash);
+    }
+
+    #[test]
+    fn test_parse() {
+        // 1000 iterations, the salt "salt" and the password "secret"
+        let contents = "# users\n\
+            \n\
+            a:b:1000:73616c74:a8df899f3c4f204d967e0ad63c092987c10055ebb017b3d9d28add218d4f7aad\n";
+        let passwords = parse(contents).unwrap();
+        assert_eq!(passwords.entries.len(), 1);
+        assert_eq!(passwords.entries["a:b"].iterations, 1000);
+        assert!(passwords.verify("a:b", b"secret"));
+        assert!(!passwords.verify("a:b", b"secreT"));
+        assert!(parse("alice:1000:73616c74:00").is_err());
+        assert!(parse("alice:1000:zz:00").is_err());
+        assert!(parse("alice:x:73616c74:00").is_err());
+        let hash = "a8df899f3c4f204d967e0ad63c092987c10055ebb017b3d9d28add218d4f7aad";
+        assert!(parse(&format!("alice:0:73616c74:{hash}")).is_err());
+        assert!(parse(&format!("alice:73616c74:{hash}")).is_err());
+        assert!(parse("alice").is_err());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join(format!("mqtt-passwords-{}", process::id()));
+        let mut passwords = password_file();
+        passwords.set_password("alice", b"secret").unwrap();
+        passwords.set_password("bob", b"hunter2").unwrap();
+        passwords.set_password("carol#1:x", b"pw").unwrap();
+        assert!(matches!(
+            passwords.set_password("#dave", b"pw"),
+            Err(Error::InvalidArgument(_))
+        ));
+        passwords.save(&path).unwrap();
+        let loaded = PasswordFile::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.entries, passwords.entries);
+        assert!(loaded.verify("bob", b"hunter2"));
+        // unknown users cost what the loaded users do
+        assert_eq!(loaded.dummy().iterations, 1000);
+        assert_eq!(PasswordFile::new().dummy().iterations, 100_000);
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let mut passwords = password_file();
+        passwords.set_password("alice", b"secret").unwrap();
+        let addr: SocketAddr = "127.0.0.1:1883".parse().unwrap();
+        let authenticate =
+            |user_name, password| passwords.authenticate("foo", user_name, password, addr);
+        assert_eq!(
+            authenticate(Some("alice"), Some(b"secret")),
+            Authentication::Accepted
+        );
+        assert_eq!(
+            authenticate(Some("alice"), Some(b"wrong")),
+            Authentication::BadUserNameOrPassword
+        );
+        assert_eq!(
+            authenticate(Some("alice"), None),
+            Authentication::BadUserNameOrPassword
+        );
+        assert_eq!(
+            authenticate(None, Some(b"secret")),
+            Authentication::NotAuthorized
+        );
+    }
+}
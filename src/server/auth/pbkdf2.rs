@@ -0,0 +1,84 @@
+// PBKDF2 with HMAC-SHA256 as specified in RFC 8018 and RFC 2104, to make every guess at a
+// password from a stolen password file cost as many hashes as the iteration count.
+
+use crate::server::auth::sha256::{self, Sha256};
+
+const BLOCK_LEN: usize = 64;
+
+// HMAC-SHA256 with the padded key blocks hashed once for all the messages to come
+struct Hmac {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl Hmac {
+    fn new(key: &[u8]) -> Self {
+        let mut block = [0; BLOCK_LEN];
+        if key.len() > BLOCK_LEN {
+            block[..32].copy_from_slice(&sha256::digest(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Sha256::new();
+        inner.update_blocks(&block.map(|byte| byte ^ 0x36));
+        let mut outer = Sha256::new();
+        outer.update_blocks(&block.map(|byte| byte ^ 0x5c));
+        Hmac { inner, outer }
+    }
+
+    fn mac(&self, message: &[u8]) -> [u8; 32] {
+        let inner = self.inner.clone().finish(message);
+        self.outer.clone().finish(&inner)
+    }
+}
+
+/// The first 32 byte block of PBKDF2-HMAC-SHA256, all a password hash needs.
+pub(crate) fn derive(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let hmac = Hmac::new(password);
+    let mut message = Vec::from(salt);
+    message.extend_from_slice(&1u32.to_be_bytes());
+    let mut block = hmac.mac(&message);
+    let mut derived = block;
+    for _ in 1..iterations {
+        block = hmac.mac(&block);
+        for (byte, value) in derived.iter_mut().zip(block) {
+            *byte ^= value;
+        }
+    }
+    derived
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::auth::pbkdf2::{derive, Hmac};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231 test cases 2 and 6
+        assert_eq!(
+            hex(&Hmac::new(b"Jefe").mac(b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&Hmac::new(&[0xaa; 131])
+                .mac(b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_eq!(
+            hex(&derive(b"password", b"salt", 1)),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        assert_eq!(
+            hex(&derive(b"password", b"salt", 4096)),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+    }
+}
@@ -0,0 +1,1136 @@
+use crate::common::Bytes;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::{ControlPacket, Packet, PacketType};
+use crate::error::Error;
+use crate::payload::connect::Will;
+use crate::payload::subscribe::{Subscription, SubscriptionOptions};
+use crate::property::{Properties, Property};
+use crate::reason_code::ReasonCode;
+use crate::server::connection::Writer;
+use crate::server::options::ServerOptions;
+use crate::server::retained::RetainedMessages;
+use crate::server::stored::StoredMessage;
+use crate::server::topic_tree::TopicTree;
+use crate::server::will;
+use crate::session::{Acknowledgement, Session};
+use crate::topic::{is_valid_topic_filter, is_valid_topic_name};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SHARED_SUBSCRIPTION_PREFIX: &str = "$share/";
+// a session with this expiry interval never expires
+const NEVER_EXPIRES: u32 = u32::MAX;
+
+// the network connection a session is attached to
+struct Connection {
+    // tells this connection apart from a later one with the same client identifier
+    id: u64,
+    writer: Arc<Writer>,
+    // how many QoS 1 and 2 messages the client takes in flight at once
+    receive_maximum: u16,
+    will: Option<Will>,
+}
+
+// Everything the broker keeps for a client identifier. It outlives the connection by the
+// session expiry interval, so a client that comes back without clean start gets its
+// subscriptions, its unacknowledged messages and what was published while it was away.
+struct ClientSession {
+    connection: Option<Connection>,
+    // the topic filters this client has in the subscription tree
+    subscriptions: Vec<String>,
+    session: Session,
+    // QoS 1 and 2 messages waiting for the client to connect or to take more messages,
+    // oldest first and no more than `max_queued_messages`
+    queued: VecDeque<StoredMessage>,
+    max_queued_messages: usize,
+    expiry_interval: u32,
+    // when the session ends if no connection attaches to it before
+    expires_at: Option<Instant>,
+}
+
+impl ClientSession {
+    fn new(connection: Connection, max_queued_messages: usize, expiry_interval: u32) -> Self {
+        ClientSession {
+            connection: Some(connection),
+            subscriptions: Vec::new(),
+            session: Session::default(),
+            queued: VecDeque::new(),
+            max_queued_messages,
+            expiry_interval,
+            expires_at: None,
+        }
+    }
+
+    fn is_attached_to(&self, id: u64) -> bool {
+        self.connection
+            .as_ref()
+            .is_some_and(|connection| connection.id == id)
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.connection.is_none() && self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    // Hands a message without packet identifier to the connection, with an identifier from
+    // the session for QoS 1 and 2. QoS 1 and 2 messages are queued while the client is
+    // offline or has its Receive Maximum in flight, QoS 0 messages are dropped.
+    fn deliver(&mut self, publish: Publish, now: Instant) -> Option<Delivery> {
+        let (writer, receive_maximum) = match &self.connection {
+            Some(connection) => (
+                Arc::clone(&connection.writer),
+                usize::from(connection.receive_maximum),
+            ),
+            None => {
+                if publish.qos() > 0 {
+                    self.queue(&publish, now);
+                }
+                return None;
+            }
+        };
+        if publish.qos() == 0 {
+            return Some(Delivery { writer, publish });
+        }
+        // queued messages go first, so the client gets them in the order they were published
+        if self.queued.is_empty() && self.session.in_flight_len() < receive_maximum {
+            if let Some(packet_identifier) = self.session.next_packet_identifier() {
+                let publish = publish.with_packet_identifier(Some(packet_identifier));
+                self.session.insert(publish.clone());
+                return Some(Delivery { writer, publish });
+            }
+        }
+        self.queue(&publish, now);
+        None
+    }
+
+    // keeps a message for later, making room by dropping the oldest one
+    fn queue(&mut self, publish: &Publish, now: Instant) {
+        self.queued.push_back(StoredMessage::new(publish, now));
+        while self.queued.len() > self.max_queued_messages {
+            self.queued.pop_front();
+        }
+    }
+
+    // Takes the queued messages that have not expired off the queue, with a packet
+    // identifier each, for as long as the connected client takes more and identifiers last.
+    fn dequeue(&mut self, now: Instant) -> Vec<Publish> {
+        let receive_maximum = match &self.connection {
+            Some(connection) => usize::from(connection.receive_maximum),
+            None => return Vec::new(),
+        };
+        let mut dequeued = Vec::new();
+        while self.session.in_flight_len() < receive_maximum {
+            let message = match self.queued.pop_front() {
+                Some(message) => message,
+                None => break,
+            };
+            if message.is_expired(now) {
+                continue;
+            }
+            let packet_identifier = match self.session.next_packet_identifier() {
+                Some(packet_identifier) => packet_identifier,
+                None => {
+                    self.queued.push_front(message);
+                    break;
+                }
+            };
+            let publish = message
+                .publish(now)
+                .with_packet_identifier(Some(packet_identifier));
+            self.session.insert(publish.clone());
+            dequeued.push(publish);
+        }
+        dequeued
+    }
+
+    // What a resumed session sends after the CONNACK: everything still in flight, then the
+    // queued messages the client has room for.
+    fn resume(&mut self, now: Instant) -> Vec<Bytes> {
+        let mut resend = self.session.retransmissions();
+        resend.extend(self.dequeue(now).iter().map(Publish::as_bytes));
+        resend
+    }
+}
+
+/// A PUBLISH routed to a subscriber, to be sent once the broker is unlocked.
+pub(crate) struct Delivery {
+    writer: Arc<Writer>,
+    publish: Publish,
+}
+
+impl Delivery {
+    /// Sends the message; a subscriber that does not take it within the write timeout is
+    /// disconnected.
+    pub(crate) fn send(&self) {
+        let _ = self.writer.send(&self.publish);
+    }
+}
+
+/// The outcome of attaching a new connection to the session of its client identifier.
+pub(crate) struct Connected {
+    session_present: bool,
+    replaced: Option<Arc<Writer>>,
+    resend: Vec<Bytes>,
+    deliveries: Vec<Delivery>,
+}
+
+impl Connected {
+    pub(crate) fn session_present(&self) -> bool {
+        self.session_present
+    }
+
+    /// The connection that had the session before, which has to be closed.
+    pub(crate) fn replaced(&self) -> Option<&Writer> {
+        self.replaced.as_deref()
+    }
+
+    /// Packets of the resumed session to send right after the CONNACK.
+    pub(crate) fn resend(&self) -> &[Bytes] {
+        &self.resend
+    }
+
+    /// Wills published because the previous connection or session ended, to send once the
+    /// CONNACK is out.
+    pub(crate) fn deliveries(&self) -> &[Delivery] {
+        &self.deliveries
+    }
+}
+
+// State shared by the threads serving the connections. The sessions are always locked
+// before the wills and the subscriptions, and the subscriptions before the retained
+// messages.
+pub(crate) struct Broker {
+    sessions: Mutex<HashMap<String, ClientSession>>,
+    // how many messages each session queues at most
+    max_queued_messages: usize,
+    // wills waiting for their delay, with the time they are due
+    wills: Mutex<HashMap<String, (Instant, Will)>>,
+    // wakes the will timer when a will is added or the broker shuts down
+    wills_changed: Condvar,
+    will_timer_started: AtomicBool,
+    subscriptions: Mutex<TopicTree<SubscriptionOptions>>,
+    retained: Mutex<RetainedMessages>,
+    last_id: AtomicU64,
+    // set with the sessions locked, so no connection is attached once it is
+    shut_down: AtomicBool,
+}
+
+// a panicking connection thread must not take the whole broker down with it
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// the session of `client_id`, if connection `id` is still the one attached to it
+fn attached<'a>(
+    sessions: &'a mut HashMap<String, ClientSession>,
+    client_id: &str,
+    id: u64,
+) -> Option<&'a mut ClientSession> {
+    sessions
+        .get_mut(client_id)
+        .filter(|session| session.is_attached_to(id))
+}
+
+impl Default for Broker {
+    fn default() -> Self {
+        Broker::new(ServerOptions::new().max_queued_messages())
+    }
+}
+
+impl Broker {
+    pub(crate) fn new(max_queued_messages: usize) -> Self {
+        Broker {
+            sessions: Mutex::default(),
+            max_queued_messages,
+            wills: Mutex::default(),
+            wills_changed: Condvar::new(),
+            will_timer_started: AtomicBool::default(),
+            subscriptions: Mutex::default(),
+            retained: Mutex::default(),
+            last_id: AtomicU64::default(),
+            shut_down: AtomicBool::default(),
+        }
+    }
+
+    /// A number no other connection got, also used to make up client identifiers.
+    pub(crate) fn next_id(&self) -> u64 {
+        self.last_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Client identifier for a client that left it empty in its CONNECT.
+    pub(crate) fn assign_client_id(&self) -> String {
+        format!("auto-{}", self.next_id())
+    }
+
+    /// Attaches connection `id` to the session of the client identifier. The session is
+    /// resumed unless `clean_start` is set or there is none, and a connection still
+    /// attached to it is taken over. The will of the previous connection is published if
+    /// the session ends here or it had no delay, and cancelled otherwise. Nothing is
+    /// attached once the broker is shut down.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn connect(
+        &self,
+        client_id: &str,
+        id: u64,
+        writer: Arc<Writer>,
+        clean_start: bool,
+        expiry_interval: u32,
+        receive_maximum: u16,
+        will: Option<Will>,
+    ) -> Option<Connected> {
+        let now = Instant::now();
+        let mut sessions = lock(&self.sessions);
+        if self.is_shut_down() {
+            return None;
+        }
+        self.expire_sessions(&mut sessions, now);
+        let mut existing = sessions.remove(client_id);
+        let session_ends = clean_start || existing.is_none();
+        let replaced = existing
+            .as_mut()
+            .and_then(|session| session.connection.take());
+        let mut wills = Vec::new();
+        if let Some(will) = replaced.as_ref().and_then(|replaced| replaced.will.clone()) {
+            if session_ends || will::delay_interval(&will) == 0 {
+                wills.push(will);
+            }
+        }
+        if let Some((_, will)) = lock(&self.wills).remove(client_id) {
+            if session_ends {
+                wills.push(will);
+            }
+        }
+        let connection = Connection {
+            id,
+            writer,
+            receive_maximum,
+            will,
+        };
+        let (session, resend) = match existing {
+            Some(mut session) if !clean_start => {
+                session.connection = Some(connection);
+                session.expiry_interval = expiry_interval;
+                session.expires_at = None;
+                let resend = session.resume(now);
+                (session, resend)
+            }
+            existing => {
+                if let Some(session) = existing {
+                    self.unsubscribe_all(client_id, &session);
+                }
+                let session =
+                    ClientSession::new(connection, self.max_queued_messages, expiry_interval);
+                (session, Vec::new())
+            }
+        };
+        sessions.insert(String::from(client_id), session);
+        let deliveries = wills
+            .iter()
+            .flat_map(|will| self.route_will(&mut sessions, client_id, will, now))
+            .collect();
+        Some(Connected {
+            session_present: !session_ends,
+            replaced: replaced.map(|connection| connection.writer),
+            resend,
+            deliveries,
+        })
+    }
+
+    /// Stops attaching connections and returns those still attached, which are to be
+    /// closed.
+    pub(crate) fn shutdown(&self) -> Vec<Arc<Writer>> {
+        let sessions = lock(&self.sessions);
+        self.shut_down.store(true, Ordering::Relaxed);
+        // with the wills locked the timer is either waiting or yet to see the flag
+        let _wills = lock(&self.wills);
+        self.wills_changed.notify_all();
+        sessions
+            .values()
+            .filter_map(|session| session.connection.as_ref())
+            .map(|connection| Arc::clone(&connection.writer))
+            .collect()
+    }
+
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::Relaxed)
+    }
+
+    /// Changes the session expiry interval on DISCONNECT. A session that was to end with
+    /// the connection cannot be kept after all.
+    pub(crate) fn set_session_expiry_interval(
+        &self,
+        client_id: &str,
+        id: u64,
+        expiry_interval: u32,
+    ) -> Result<(), Error> {
+        let mut sessions = lock(&self.sessions);
+        if let Some(session) = attached(&mut sessions, client_id, id) {
+            if session.expiry_interval == 0 && expiry_interval != 0 {
+                return Err(Error::ProtocolError {
+                    packet_type: Some(PacketType::DISCONNECT),
+                    offset: None,
+                    message: "session expiry interval set after connecting with 0",
+                });
+            }
+            session.expiry_interval = expiry_interval;
+        }
+        Ok(())
+    }
+
+    /// Detaches the connection from its session unless a newer connection took the session
+    /// over. The session ends right away with an expiry interval of 0, or expires later.
+    /// With `publish_will` the will of the connection is published after the will delay,
+    /// but no later than the session ends: right away, with the deliveries returned, or by
+    /// the will timer unless the session resumes first.
+    pub(crate) fn disconnect(&self, client_id: &str, id: u64, publish_will: bool) -> Vec<Delivery> {
+        let now = Instant::now();
+        let mut sessions = lock(&self.sessions);
+        let mut deliveries = Vec::new();
+        if let Some(session) = attached(&mut sessions, client_id, id) {
+            let will = session
+                .connection
+                .take()
+                .and_then(|connection| connection.will);
+            let expiry_interval = session.expiry_interval;
+            match expiry_interval {
+                0 => {
+                    if let Some(session) = sessions.remove(client_id) {
+                        self.unsubscribe_all(client_id, &session);
+                    }
+                }
+                NEVER_EXPIRES => {}
+                expiry_interval => {
+                    let expiry_interval = Duration::from_secs(u64::from(expiry_interval));
+                    session.expires_at = Some(now + expiry_interval);
+                }
+            }
+            if let Some(will) = will.filter(|_| publish_will) {
+                match will::delay_interval(&will).min(expiry_interval) {
+                    0 => deliveries = self.route_will(&mut sessions, client_id, &will, now),
+                    delay => {
+                        let publish_at = now + Duration::from_secs(u64::from(delay));
+                        lock(&self.wills).insert(String::from(client_id), (publish_at, will));
+                        self.wills_changed.notify_all();
+                    }
+                }
+            }
+        }
+        self.expire_sessions(&mut sessions, now);
+        deliveries
+    }
+
+    /// Starts the thread that publishes the delayed wills, unless it is running already.
+    pub(crate) fn start_will_timer(self: &Arc<Self>) {
+        if !self.will_timer_started.swap(true, Ordering::Relaxed) {
+            let broker = Arc::clone(self);
+            thread::spawn(move || broker.run_will_timer());
+        }
+    }
+
+    // publishes each will as it falls due, until the broker shuts down
+    fn run_will_timer(&self) {
+        let mut wills = lock(&self.wills);
+        while !self.is_shut_down() {
+            let now = Instant::now();
+            match wills.values().map(|(publish_at, _)| *publish_at).min() {
+                Some(publish_at) if publish_at <= now => {
+                    // the sessions have to be locked first
+                    drop(wills);
+                    for delivery in self.publish_due_wills(now) {
+                        delivery.send();
+                    }
+                    wills = lock(&self.wills);
+                }
+                Some(publish_at) => {
+                    wills = self
+                        .wills_changed
+                        .wait_timeout(wills, publish_at - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+                None => {
+                    wills = self
+                        .wills_changed
+                        .wait(wills)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    // publishes the wills whose delay is over at `now`
+    fn publish_due_wills(&self, now: Instant) -> Vec<Delivery> {
+        let mut sessions = lock(&self.sessions);
+        let due: Vec<(String, Will)> = {
+            let mut wills = lock(&self.wills);
+            let client_ids: Vec<String> = wills
+                .iter()
+                .filter(|(_, (publish_at, _))| *publish_at <= now)
+                .map(|(client_id, _)| client_id.clone())
+                .collect();
+            client_ids
+                .into_iter()
+                .filter_map(|client_id| {
+                    let (_, will) = wills.remove(&client_id)?;
+                    Some((client_id, will))
+                })
+                .collect()
+        };
+        due.iter()
+            .flat_map(|(client_id, will)| self.route_will(&mut sessions, client_id, will, now))
+            .collect()
+    }
+
+    fn route_will(
+        &self,
+        sessions: &mut HashMap<String, ClientSession>,
+        client_id: &str,
+        will: &Will,
+        now: Instant,
+    ) -> Vec<Delivery> {
+        let publish = will::publish(will);
+        if publish.retain() {
+            self.retain(&publish);
+        }
+        self.route_in(sessions, client_id, &publish, now).1
+    }
+
+    // ends the sessions nobody came back for in time
+    fn expire_sessions(&self, sessions: &mut HashMap<String, ClientSession>, now: Instant) {
+        let expired: Vec<String> = sessions
+            .iter()
+            .filter(|(_, session)| session.is_expired(now))
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        for client_id in expired {
+            if let Some(session) = sessions.remove(&client_id) {
+                self.unsubscribe_all(&client_id, &session);
+            }
+        }
+    }
+
+    fn unsubscribe_all(&self, client_id: &str, session: &ClientSession) {
+        let mut subscriptions = lock(&self.subscriptions);
+        for topic_filter in &session.subscriptions {
+            subscriptions.remove(topic_filter, client_id);
+        }
+    }
+
+    /// Adds or replaces a subscription of the connection, returning the SUBACK reason code
+    /// and the retained messages to send after the SUBACK. Those are sent for every
+    /// subscription with Retain Handling 0, only for a new one with 1, and never with 2.
+    pub(crate) fn subscribe(
+        &self,
+        client_id: &str,
+        id: u64,
+        subscription: &Subscription,
+    ) -> (ReasonCode, Vec<Delivery>) {
+        let topic_filter = subscription.topic_filter();
+        if topic_filter.starts_with(SHARED_SUBSCRIPTION_PREFIX) {
+            return (ReasonCode::SharedSubscriptionsNotSupported, Vec::new());
+        }
+        if !is_valid_topic_filter(topic_filter) {
+            return (ReasonCode::TopicFilterInvalid, Vec::new());
+        }
+        let mut sessions = lock(&self.sessions);
+        let session = match attached(&mut sessions, client_id, id) {
+            Some(session) => session,
+            None => return (ReasonCode::UnspecifiedError, Vec::new()),
+        };
+        let options = *subscription.options();
+        let qos = options.qos();
+        let replaced = lock(&self.subscriptions).insert(topic_filter, client_id, options);
+        if replaced.is_none() {
+            session.subscriptions.push(String::from(topic_filter));
+        }
+        let reason_code = match qos {
+            0 => ReasonCode::Success,
+            1 => ReasonCode::GrantedQoS1,
+            _ => ReasonCode::GrantedQoS2,
+        };
+        let send_retained = match options.retain_handling() {
+            0 => true,
+            1 => replaced.is_none(),
+            _ => false,
+        };
+        if !send_retained {
+            return (reason_code, Vec::new());
+        }
+        // retained messages always go out with the retain flag, whatever Retain As Published
+        let now = Instant::now();
+        let deliveries = lock(&self.retained)
+            .matching(topic_filter, now)
+            .into_iter()
+            .filter_map(|publish| {
+                let delivered = Publish::new(
+                    publish.topic_name(),
+                    None,
+                    Vec::from(publish.payload()),
+                    qos.min(publish.qos()),
+                    true,
+                    publish.properties().clone(),
+                );
+                session.deliver(delivered, now)
+            })
+            .collect();
+        (reason_code, deliveries)
+    }
+
+    /// Removes a subscription of the connection, returning the UNSUBACK reason code.
+    pub(crate) fn unsubscribe(&self, client_id: &str, id: u64, topic_filter: &str) -> ReasonCode {
+        let mut sessions = lock(&self.sessions);
+        let session = match attached(&mut sessions, client_id, id) {
+            Some(session) => session,
+            None => return ReasonCode::UnspecifiedError,
+        };
+        if lock(&self.subscriptions)
+            .remove(topic_filter, client_id)
+            .is_none()
+        {
+            return ReasonCode::NoSubscriptionExisted;
+        }
+        session
+            .subscriptions
+            .retain(|subscribed| subscribed != topic_filter);
+        ReasonCode::Success
+    }
+
+    /// Finds the subscribers of a message published by `client_id`, returning whether
+    /// there were any and the deliveries to send. Each subscriber gets the message once, at
+    /// the highest QoS of its matching subscriptions but never above the QoS it was
+    /// published with. Subscribers that are offline get QoS 1 and 2 messages queued
+    /// instead of a delivery.
+    pub(crate) fn route(&self, client_id: &str, publish: &Publish) -> (bool, Vec<Delivery>) {
+        let mut sessions = lock(&self.sessions);
+        self.route_in(&mut sessions, client_id, publish, Instant::now())
+    }
+
+    fn route_in(
+        &self,
+        sessions: &mut HashMap<String, ClientSession>,
+        client_id: &str,
+        publish: &Publish,
+        now: Instant,
+    ) -> (bool, Vec<Delivery>) {
+        let topic_name = publish.topic_name();
+        debug_assert!(is_valid_topic_name(topic_name));
+        let subscriptions = lock(&self.subscriptions);
+        // the subscription options that apply to each subscriber
+        let mut subscribers: HashMap<&str, (u8, bool)> = HashMap::new();
+        for (subscriber, options) in subscriptions.matches(topic_name) {
+            if options.no_local() && subscriber == client_id {
+                continue;
+            }
+            let entry = subscribers.entry(subscriber).or_insert((0, false));
+            entry.0 = entry.0.max(options.qos());
+            entry.1 |= options.retain_as_published();
+        }
+        let properties = forwarded_properties(publish.properties());
+        let mut matched = false;
+        let mut deliveries = Vec::new();
+        for (subscriber, (qos, retain_as_published)) in subscribers {
+            let session = match sessions.get_mut(subscriber) {
+                Some(session) if !session.is_expired(now) => session,
+                _ => continue,
+            };
+            matched = true;
+            let delivered = Publish::new(
+                topic_name,
+                None,
+                Vec::from(publish.payload()),
+                qos.min(publish.qos()),
+                retain_as_published && publish.retain(),
+                properties.clone(),
+            );
+            deliveries.extend(session.deliver(delivered, now));
+        }
+        (matched, deliveries)
+    }
+
+    /// Keeps a PUBLISH with the retain flag for later subscriptions to its topic, or
+    /// deletes the retained message of the topic if the payload is empty.
+    pub(crate) fn retain(&self, publish: &Publish) {
+        let retained = Publish::new(
+            publish.topic_name(),
+            None,
+            Vec::from(publish.payload()),
+            publish.qos(),
+            true,
+            forwarded_properties(publish.properties()),
+        );
+        lock(&self.retained).retain(retained, Instant::now());
+    }
+
+    /// Passes a PUBACK, PUBREC or PUBCOMP from the client to its session. A finished
+    /// delivery makes room for the queued messages returned with it.
+    pub(crate) fn acknowledge(
+        &self,
+        client_id: &str,
+        id: u64,
+        packet: &Packet,
+    ) -> (Acknowledgement, Vec<Delivery>) {
+        let mut sessions = lock(&self.sessions);
+        let session = match attached(&mut sessions, client_id, id) {
+            Some(session) => session,
+            None => return (Acknowledgement::Unknown, Vec::new()),
+        };
+        let acknowledgement = session.session.acknowledge(packet);
+        let deliveries = match (&acknowledgement, &session.connection) {
+            (Acknowledgement::Complete(..), Some(connection)) => {
+                let writer = Arc::clone(&connection.writer);
+                session
+                    .dequeue(Instant::now())
+                    .into_iter()
+                    .map(|publish| Delivery {
+                        writer: Arc::clone(&writer),
+                        publish,
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        (acknowledgement, deliveries)
+    }
+
+    /// Records an incoming QoS 2 message; false if the client is resending it.
+    pub(crate) fn receive(&self, client_id: &str, id: u64, packet_identifier: u16) -> bool {
+        attached(&mut lock(&self.sessions), client_id, id)
+            .is_some_and(|session| session.session.receive(packet_identifier))
+    }
+
+    /// Ends an incoming QoS 2 exchange; false if nothing was waiting for the PUBREL.
+    pub(crate) fn release(&self, client_id: &str, id: u64, packet_identifier: u16) -> bool {
+        attached(&mut lock(&self.sessions), client_id, id)
+            .is_some_and(|session| session.session.release(packet_identifier))
+    }
+
+    /// Client identifiers of the connected clients.
+    pub(crate) fn client_ids(&self) -> Vec<String> {
+        lock(&self.sessions)
+            .iter()
+            .filter(|(_, session)| session.connection.is_some())
+            .map(|(client_id, _)| client_id.clone())
+            .collect()
+    }
+}
+
+// The topic alias belongs to the publisher's connection and the subscription identifiers
+// are not supported, everything else goes to the subscribers unchanged.
+fn forwarded_properties(properties: &Properties) -> Properties {
+    Properties::new(
+        properties
+            .values()
+            .iter()
+            .filter(|property| {
+                !matches!(
+                    property,
+                    Property::TopicAlias(_) | Property::SubscriptionIdentifier(_)
+                )
+            })
+            .cloned()
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::FourByteInt;
+    use crate::control_packet::puback::Puback;
+    use crate::control_packet::publish::Publish;
+    use crate::control_packet::{parse_packet_bytes, Packet};
+    use crate::payload::connect::Will;
+    use crate::payload::subscribe::{Subscription, SubscriptionOptions};
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+    use crate::server::broker::Broker;
+    use crate::server::connection::Writer;
+    use crate::session::Acknowledgement;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn writer() -> Arc<Writer> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        Arc::new(Writer::new(stream))
+    }
+
+    fn publish(topic_name: &str, qos: u8) -> Publish {
+        Publish::new(topic_name, None, vec![1], qos, false, Properties::default())
+    }
+
+    #[test]
+    fn test_connect_disconnect() {
+        let broker = Broker::default();
+        let first = broker.next_id();
+        let connected = broker
+            .connect("foo", first, writer(), true, 0, u16::MAX, None)
+            .unwrap();
+        assert!(!connected.session_present());
+        assert!(connected.replaced().is_none());
+        let second = broker.next_id();
+        let connected = broker
+            .connect("foo", second, writer(), false, 0, u16::MAX, None)
+            .unwrap();
+        assert!(connected.session_present());
+        assert!(connected.replaced().is_some());
+        // the first connection closing leaves the second attached
+        broker.disconnect("foo", first, false);
+        assert_eq!(broker.client_ids(), vec![String::from("foo")]);
+        broker.disconnect("foo", second, false);
+        assert!(broker.client_ids().is_empty());
+        // with an expiry interval of 0 the session is gone
+        let connected = broker
+            .connect("foo", broker.next_id(), writer(), false, 0, u16::MAX, None)
+            .unwrap();
+        assert!(!connected.session_present());
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let broker = Broker::default();
+        broker.connect("foo", broker.next_id(), writer(), true, 0, u16::MAX, None);
+        let id = broker.next_id();
+        broker.connect("bar", id, writer(), true, 60, u16::MAX, None);
+        broker.disconnect("bar", id, false);
+        // only the attached connection is left to close
+        assert_eq!(broker.shutdown().len(), 1);
+        assert!(broker.is_shut_down());
+        assert!(broker
+            .connect("baz", broker.next_id(), writer(), true, 0, u16::MAX, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_assign_client_id() {
+        let broker = Broker::default();
+        assert_ne!(broker.assign_client_id(), broker.assign_client_id());
+    }
+
+    #[test]
+    fn test_session_keeps_subscriptions_and_queues() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 60, u16::MAX, None);
+        let options = SubscriptionOptions::new(2);
+        broker.subscribe("foo", id, &Subscription::new("sport/#", options));
+        let (_, in_flight) = broker.route("bar", &publish("sport/tennis", 1));
+        assert_eq!(in_flight.len(), 1);
+        broker.disconnect("foo", id, false);
+        assert!(broker.client_ids().is_empty());
+        // queued for the offline client, except at QoS 0
+        assert_eq!(broker.route("bar", &publish("sport/golf", 2)).1.len(), 0);
+        let (matched, deliveries) = broker.route("bar", &publish("sport/golf", 0));
+        assert!(matched && deliveries.is_empty());
+        let id = broker.next_id();
+        let connected = broker
+            .connect("foo", id, writer(), false, 60, u16::MAX, None)
+            .unwrap();
+        assert!(connected.session_present());
+        let resend: Vec<Packet> = connected
+            .resend()
+            .iter()
+            .map(|bytes| parse_packet_bytes(bytes).unwrap())
+            .collect();
+        assert_eq!(
+            resend,
+            vec![
+                Packet::Publish(
+                    publish("sport/tennis", 1)
+                        .with_packet_identifier(Some(1))
+                        .with_dup(true)
+                ),
+                Packet::Publish(publish("sport/golf", 2).with_packet_identifier(Some(2))),
+            ]
+        );
+        // the subscription is still there
+        assert_eq!(broker.route("bar", &publish("sport", 1)).1.len(), 1);
+        let puback = Puback::new(1, ReasonCode::Success, Properties::default());
+        assert_eq!(
+            broker.acknowledge("foo", id, &Packet::Puback(puback)).0,
+            Acknowledgement::Complete(1, ReasonCode::Success)
+        );
+    }
+
+    #[test]
+    fn test_queue_drops_oldest() {
+        let broker = Broker::new(2);
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 60, u16::MAX, None);
+        let options = SubscriptionOptions::new(1);
+        broker.subscribe("foo", id, &Subscription::new("sport/#", options));
+        broker.disconnect("foo", id, false);
+        for topic_name in ["sport/tennis", "sport/golf", "sport/chess"] {
+            broker.route("bar", &publish(topic_name, 1));
+        }
+        let connected = broker
+            .connect("foo", broker.next_id(), writer(), false, 60, u16::MAX, None)
+            .unwrap();
+        let resend: Vec<Packet> = connected
+            .resend()
+            .iter()
+            .map(|bytes| parse_packet_bytes(bytes).unwrap())
+            .collect();
+        assert_eq!(
+            resend,
+            vec![
+                Packet::Publish(publish("sport/golf", 1).with_packet_identifier(Some(1))),
+                Packet::Publish(publish("sport/chess", 1).with_packet_identifier(Some(2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_receive_maximum() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, 1, None);
+        let options = SubscriptionOptions::new(1);
+        broker.subscribe("foo", id, &Subscription::new("sport/#", options));
+        assert_eq!(broker.route("bar", &publish("sport/tennis", 1)).1.len(), 1);
+        // the client takes one message at a time, the others wait in order
+        let (matched, deliveries) = broker.route("bar", &publish("sport/golf", 1));
+        assert!(matched && deliveries.is_empty());
+        assert!(broker.route("bar", &publish("sport/chess", 1)).1.is_empty());
+        // QoS 0 messages are not held back
+        assert_eq!(broker.route("bar", &publish("sport/darts", 0)).1.len(), 1);
+        let puback = Puback::new(1, ReasonCode::Success, Properties::default());
+        let (_, deliveries) = broker.acknowledge("foo", id, &Packet::Puback(puback));
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(
+            deliveries[0].publish,
+            publish("sport/golf", 1).with_packet_identifier(Some(2))
+        );
+        let puback = Puback::new(2, ReasonCode::Success, Properties::default());
+        let (_, deliveries) = broker.acknowledge("foo", id, &Packet::Puback(puback));
+        assert_eq!(
+            deliveries[0].publish,
+            publish("sport/chess", 1).with_packet_identifier(Some(3))
+        );
+    }
+
+    #[test]
+    fn test_clean_start_discards_session() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 60, u16::MAX, None);
+        let options = SubscriptionOptions::new(1);
+        broker.subscribe("foo", id, &Subscription::new("foo", options));
+        broker.disconnect("foo", id, false);
+        broker.route("bar", &publish("foo", 1));
+        let connected = broker
+            .connect("foo", broker.next_id(), writer(), true, 60, u16::MAX, None)
+            .unwrap();
+        assert!(!connected.session_present());
+        assert!(connected.resend().is_empty());
+        assert!(!broker.route("bar", &publish("foo", 1)).0);
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 1, u16::MAX, None);
+        broker.disconnect("foo", id, false);
+        let connected = broker
+            .connect("foo", broker.next_id(), writer(), false, 1, u16::MAX, None)
+            .unwrap();
+        assert!(connected.session_present());
+        let id = broker.next_id();
+        broker.connect("bar", id, writer(), true, 1, u16::MAX, None);
+        broker.disconnect("bar", id, false);
+        thread::sleep(Duration::from_millis(1100));
+        let connected = broker
+            .connect("bar", broker.next_id(), writer(), false, 1, u16::MAX, None)
+            .unwrap();
+        assert!(!connected.session_present());
+    }
+
+    #[test]
+    fn test_set_session_expiry_interval() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, u16::MAX, None);
+        assert!(broker.set_session_expiry_interval("foo", id, 10).is_err());
+        let id = broker.next_id();
+        broker.connect("bar", id, writer(), true, 10, u16::MAX, None);
+        broker.set_session_expiry_interval("bar", id, 0).unwrap();
+        broker.disconnect("bar", id, false);
+        let connected = broker
+            .connect("bar", broker.next_id(), writer(), false, 10, u16::MAX, None)
+            .unwrap();
+        assert!(!connected.session_present());
+    }
+
+    #[test]
+    fn test_subscribe_retain_handling() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, u16::MAX, None);
+        broker.retain(&Publish::new(
+            "sport/tennis",
+            Some(1),
+            vec![1],
+            2,
+            true,
+            Properties::default(),
+        ));
+        let subscribe = |retain_handling: u8| {
+            let options = SubscriptionOptions::from_byte((retain_handling << 4) | 1).unwrap();
+            broker.subscribe("foo", id, &Subscription::new("sport/#", options))
+        };
+        let (reason_code, deliveries) = subscribe(1);
+        assert_eq!(reason_code, ReasonCode::GrantedQoS1);
+        let publish = &deliveries[0].publish;
+        assert_eq!(publish.qos(), 1);
+        assert!(publish.retain());
+        assert!(publish.packet_identifier().is_some());
+        // 1 only sends them for a new subscription
+        assert!(subscribe(1).1.is_empty());
+        assert_eq!(subscribe(0).1.len(), 1);
+        assert!(subscribe(2).1.is_empty());
+        // an empty payload deletes the retained message
+        broker.retain(&Publish::new(
+            "sport/tennis",
+            None,
+            Vec::new(),
+            0,
+            true,
+            Properties::default(),
+        ));
+        assert!(subscribe(0).1.is_empty());
+    }
+
+    #[test]
+    fn test_route_retain_as_published() {
+        let broker = Broker::default();
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, u16::MAX, None);
+        let options = SubscriptionOptions::new(0);
+        broker.subscribe("foo", id, &Subscription::new("plain", options));
+        // retain as published
+        let options = SubscriptionOptions::from_byte(0b1000).unwrap();
+        broker.subscribe("foo", id, &Subscription::new("published", options));
+        for (topic_name, retain) in [("plain", false), ("published", true)] {
+            let publish = Publish::new(topic_name, None, vec![1], 0, true, Properties::default());
+            let (_, deliveries) = broker.route("bar", &publish);
+            assert_eq!(deliveries[0].publish.retain(), retain);
+        }
+    }
+
+    fn will(delay_interval: u32) -> Option<Will> {
+        let properties = Properties::new(vec![Property::WillDelayInterval(FourByteInt::new(
+            delay_interval,
+        ))]);
+        Some(Will::new("status", vec![0], 1, false, properties))
+    }
+
+    // connects a client subscribed to the will topic
+    fn subscriber(broker: &Broker) {
+        let id = broker.next_id();
+        broker.connect("subscriber", id, writer(), true, 0, u16::MAX, None);
+        let options = SubscriptionOptions::new(1);
+        broker.subscribe("subscriber", id, &Subscription::new("status", options));
+    }
+
+    #[test]
+    fn test_publish_will() {
+        let broker = Broker::default();
+        subscriber(&broker);
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, u16::MAX, will(0));
+        let deliveries = broker.disconnect("foo", id, true);
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].publish.topic_name(), "status");
+        // only once
+        assert!(broker.publish_due_wills(Instant::now()).is_empty());
+        // discarded on a normal disconnect
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 0, u16::MAX, will(0));
+        assert!(broker.disconnect("foo", id, false).is_empty());
+        assert!(broker.publish_due_wills(Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn test_will_delay() {
+        let broker = Broker::default();
+        subscriber(&broker);
+        let id = broker.next_id();
+        broker.connect("foo", id, writer(), true, 60, u16::MAX, will(10));
+        assert!(broker.disconnect("foo", id, true).is_empty());
+        let now = Instant::now();
+        assert!(broker.publish_due_wills(now).is_empty());
+        // the session resuming cancels the will
+        let connected = broker
+            .connect(
+                "foo",
+                broker.next_id(),
+                writer(),
+                false,
+                2,
+                u16::MAX,
+                will(10),
+            )
+            .unwrap();
+        assert!(connected.deliveries().is_empty());
+        assert!(broker
+            .publish_due_wills(now + Duration::from_secs(10))
+            .is_empty());
+        // no later than the session ends
+        let id = broker.next_id();
+        broker.connect("bar", id, writer(), true, 2, u16::MAX, will(10));
+        assert!(broker.disconnect("bar", id, true).is_empty());
+        let now = Instant::now();
+        assert_eq!(
+            broker.publish_due_wills(now + Duration::from_secs(2)).len(),
+            1
+        );
+        // the session ending publishes it right away
+        let id = broker.next_id();
+        broker.connect("bar", id, writer(), true, 2, u16::MAX, will(10));
+        broker.disconnect("bar", id, true);
+        let connected = broker
+            .connect("bar", broker.next_id(), writer(), true, 0, u16::MAX, None)
+            .unwrap();
+        assert_eq!(connected.deliveries().len(), 1);
+        assert!(broker
+            .publish_due_wills(now + Duration::from_secs(10))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_takeover_publishes_will() {
+        let broker = Broker::default();
+        subscriber(&broker);
+        broker.connect(
+            "foo",
+            broker.next_id(),
+            writer(),
+            true,
+            60,
+            u16::MAX,
+            will(10),
+        );
+        // resumed, and the delay has not passed
+        let connected = broker
+            .connect(
+                "foo",
+                broker.next_id(),
+                writer(),
+                false,
+                60,
+                u16::MAX,
+                will(0),
+            )
+            .unwrap();
+        assert!(connected.deliveries().is_empty());
+        // without a delay
+        let connected = broker
+            .connect("foo", broker.next_id(), writer(), false, 60, u16::MAX, None)
+            .unwrap();
+        assert_eq!(connected.deliveries().len(), 1);
+    }
+}
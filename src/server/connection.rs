@@ -0,0 +1,410 @@
+use crate::common::Bytes;
+use crate::common::{FourByteInt, TwoByteInt, UTF8String};
+use crate::control_packet::connack::Connack;
+use crate::control_packet::connect::Connect;
+use crate::control_packet::disconnect::Disconnect;
+use crate::control_packet::pingresp::Pingresp;
+use crate::control_packet::puback::Puback;
+use crate::control_packet::pubcomp::Pubcomp;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::pubrec::Pubrec;
+use crate::control_packet::pubrel::Pubrel;
+use crate::control_packet::suback::Suback;
+use crate::control_packet::unsuback::Unsuback;
+use crate::control_packet::{ControlPacket, Packet, PacketType};
+use crate::decoder::Decoder;
+use crate::error::Error;
+use crate::property::{Properties, Property};
+use crate::reason_code::ReasonCode;
+use crate::server::auth::{Authentication, Authenticator};
+use crate::server::broker::{lock, Broker};
+use crate::server::options::ServerOptions;
+use crate::session::Acknowledgement;
+use crate::topic::is_valid_topic_name;
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// The write half of a connection, shared with the threads that deliver messages to it.
+pub(crate) struct Writer {
+    stream: Mutex<TcpStream>,
+}
+
+impl Writer {
+    pub(crate) fn new(stream: TcpStream) -> Self {
+        Writer {
+            stream: Mutex::new(stream),
+        }
+    }
+
+    /// A write that fails or times out closes the connection, as part of the packet may
+    /// have gone out already.
+    pub(crate) fn send(&self, packet: &impl ControlPacket) -> Result<(), Error> {
+        let mut stream = lock(&self.stream);
+        if let Err(error) = stream.write_all(&packet.as_bytes()) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Holds the connection, so nothing else is written until the guard is dropped.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, TcpStream> {
+        lock(&self.stream)
+    }
+
+    /// Closes both directions, which also ends the thread reading from the connection.
+    pub(crate) fn close(&self) {
+        let _ = lock(&self.stream).shutdown(Shutdown::Both);
+    }
+}
+
+/// Serves one TCP connection from its CONNECT until it closes.
+pub(crate) fn run(
+    broker: &Broker,
+    options: &ServerOptions,
+    authenticator: Option<&dyn Authenticator>,
+    mut stream: TcpStream,
+) {
+    // a client that stops reading must not hold up the threads delivering to it
+    if stream
+        .set_write_timeout(Some(options.write_timeout()))
+        .is_err()
+    {
+        return;
+    }
+    let mut decoder = Decoder::default();
+    let connect = match read_connect(&mut stream, &mut decoder, options) {
+        Ok(connect) => connect,
+        Err(error) => {
+            // a broken CONNECT is answered, anything else is just closed
+            refuse(&mut stream, connack_reason_code(&error));
+            return;
+        }
+    };
+    if connect
+        .will()
+        .is_some_and(|will| !is_valid_topic_name(will.topic()))
+    {
+        refuse(&mut stream, Some(ReasonCode::TopicNameInvalid));
+        return;
+    }
+    // there is no AUTH exchange, so no authentication method is supported
+    if connect
+        .properties()
+        .get(Property::AuthenticationMethod)
+        .is_some()
+    {
+        refuse(&mut stream, Some(ReasonCode::BadAuthenticationMethod));
+        return;
+    }
+    if let Some(authenticator) = authenticator {
+        let peer_addr = match stream.peer_addr() {
+            Ok(peer_addr) => peer_addr,
+            Err(_) => return,
+        };
+        let authentication = authenticator.authenticate(
+            connect.client_id(),
+            connect.user_name(),
+            connect.password(),
+            peer_addr,
+        );
+        if authentication != Authentication::Accepted {
+            refuse(&mut stream, Some(authentication.reason_code()));
+            return;
+        }
+    }
+    let writer = match stream.try_clone() {
+        Ok(write_stream) => Arc::new(Writer::new(write_stream)),
+        Err(_) => return,
+    };
+    let id = broker.next_id();
+    // neither shared subscriptions nor subscription identifiers are supported
+    let mut properties = Properties::new(vec![
+        Property::SharedSubscriptionAvailable(0),
+        Property::SubscriptionIdentifierAvailable(0),
+    ]);
+    let keep_alive = match options.server_keep_alive() {
+        Some(server_keep_alive) => {
+            if server_keep_alive != connect.keep_alive() {
+                properties.push(Property::ServerKeepAlive(TwoByteInt::new(
+                    server_keep_alive,
+                )));
+            }
+            server_keep_alive
+        }
+        None => connect.keep_alive(),
+    };
+    let client_id = if connect.client_id().is_empty() {
+        let client_id = broker.assign_client_id();
+        properties.push(Property::AssignedClientIdentifier(UTF8String::new(
+            &client_id,
+        )));
+        client_id
+    } else {
+        String::from(connect.client_id())
+    };
+    // nothing routed to the session may go out before the CONNACK
+    let mut write_stream = writer.lock();
+    let connected = match broker.connect(
+        &client_id,
+        id,
+        Arc::clone(&writer),
+        connect.clean_start(),
+        connect
+            .properties()
+            .get(Property::SessionExpiryInterval)
+            .map_or(0, FourByteInt::value),
+        connect
+            .properties()
+            .get(Property::ReceiveMaximum)
+            .map_or(u16::MAX, TwoByteInt::value),
+        connect.will().cloned(),
+    ) {
+        Some(connected) => connected,
+        None => {
+            drop(write_stream);
+            refuse(&mut stream, Some(ReasonCode::ServerUnavailable));
+            return;
+        }
+    };
+    if let Some(replaced) = connected.replaced() {
+        let disconnect = Disconnect::new(ReasonCode::SessionTakenOver, Properties::default());
+        let _ = replaced.send(&disconnect);
+        replaced.close();
+    }
+    let connack = Connack::new(connected.session_present(), ReasonCode::Success, properties);
+    let sent = write_connack(&mut write_stream, &connack, connected.resend());
+    drop(write_stream);
+    for delivery in connected.deliveries() {
+        delivery.send();
+    }
+    let result = sent
+        .and_then(|_| {
+            stream
+                .set_read_timeout(keep_alive_timeout(keep_alive))
+                .map_err(Error::from)
+        })
+        .and_then(|_| serve(&mut stream, &mut decoder, broker, &client_id, id, &writer));
+    // the will is only discarded when the client disconnects normally
+    let publish_will = match &result {
+        Ok(reason_code) => *reason_code == ReasonCode::DisconnectWithWillMessage,
+        Err(error) => {
+            // a client that went quiet is told it took too long
+            if !matches!(error, Error::Io(_)) {
+                let disconnect = Disconnect::new(error.reason_code(), Properties::default());
+                let _ = writer.send(&disconnect);
+            }
+            true
+        }
+    };
+    writer.close();
+    for delivery in broker.disconnect(&client_id, id, publish_will) {
+        delivery.send();
+    }
+}
+
+// A client has to send something within one and a half times its keep alive, or it is
+// considered gone. Every packet counts, as each one is a new read.
+fn keep_alive_timeout(keep_alive: u16) -> Option<Duration> {
+    if keep_alive == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(u64::from(keep_alive)).mul_f64(1.5))
+}
+
+// answers a CONNECT that is not accepted with a CONNACK if there is a reason code for it,
+// and closes the connection
+fn refuse(stream: &mut TcpStream, reason_code: Option<ReasonCode>) {
+    if let Some(reason_code) = reason_code {
+        let connack = Connack::new(false, reason_code, Properties::default());
+        let _ = stream.write_all(&connack.as_bytes());
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// sends the CONNACK followed by what the resumed session still has to deliver
+fn write_connack(stream: &mut TcpStream, connack: &Connack, resend: &[Bytes]) -> Result<(), Error> {
+    stream.write_all(&connack.as_bytes())?;
+    for bytes in resend {
+        stream.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+// The first packet has to be a CONNECT, and it has to arrive within the connect timeout.
+fn read_connect(
+    stream: &mut TcpStream,
+    decoder: &mut Decoder,
+    options: &ServerOptions,
+) -> Result<Connect, Error> {
+    let deadline = Instant::now() + options.connect_timeout();
+    let packet = decoder.read_packet_before(stream, deadline);
+    stream.set_read_timeout(None)?;
+    match packet? {
+        Packet::Connect(connect) => Ok(connect),
+        packet => Err(Error::ProtocolError {
+            packet_type: Some(packet.packet_type()),
+            offset: None,
+            message: "first packet must be CONNECT",
+        }),
+    }
+}
+
+// the CONNACK reason code for a CONNECT that could not be accepted
+fn connack_reason_code(error: &Error) -> Option<ReasonCode> {
+    match error {
+        Error::MalformedPacket {
+            packet_type: Some(PacketType::CONNECT),
+            ..
+        }
+        | Error::ProtocolError {
+            packet_type: Some(PacketType::CONNECT),
+            ..
+        }
+        | Error::UnsupportedProtocolVersion(_)
+        | Error::PacketTooLarge { .. } => Some(error.reason_code()),
+        _ => None,
+    }
+}
+
+// Handles the packets after the CONNECT until the client disconnects, returning the reason
+// code of its DISCONNECT, or until it breaks the protocol.
+fn serve(
+    stream: &mut TcpStream,
+    decoder: &mut Decoder,
+    broker: &Broker,
+    client_id: &str,
+    id: u64,
+    writer: &Writer,
+) -> Result<ReasonCode, Error> {
+    loop {
+        match decoder.read_packet(stream)? {
+            Packet::Publish(publish) => receive_publish(broker, client_id, id, writer, &publish)?,
+            Packet::Pubrel(pubrel) => {
+                let packet_identifier = pubrel.packet_identifier();
+                let reason_code = if broker.release(client_id, id, packet_identifier) {
+                    ReasonCode::Success
+                } else {
+                    ReasonCode::PacketIdentifierNotFound
+                };
+                writer.send(&Pubcomp::new(
+                    packet_identifier,
+                    reason_code,
+                    Properties::default(),
+                ))?
+            }
+            packet @ (Packet::Puback(_) | Packet::Pubrec(_) | Packet::Pubcomp(_)) => {
+                let (acknowledgement, deliveries) = broker.acknowledge(client_id, id, &packet);
+                if let Acknowledgement::Release(packet_identifier) = acknowledgement {
+                    writer.send(&Pubrel::new(
+                        packet_identifier,
+                        ReasonCode::Success,
+                        Properties::default(),
+                    ))?
+                }
+                for delivery in deliveries {
+                    delivery.send();
+                }
+            }
+            Packet::Subscribe(subscribe) => {
+                let mut reason_codes = Vec::new();
+                let mut retained = Vec::new();
+                for subscription in subscribe.subscriptions() {
+                    let (reason_code, deliveries) = broker.subscribe(client_id, id, subscription);
+                    reason_codes.push(reason_code);
+                    retained.extend(deliveries);
+                }
+                writer.send(&Suback::new(
+                    subscribe.packet_identifier(),
+                    reason_codes,
+                    Properties::default(),
+                ))?;
+                for delivery in retained {
+                    delivery.send();
+                }
+            }
+            Packet::Unsubscribe(unsubscribe) => {
+                let reason_codes = unsubscribe
+                    .topic_filters()
+                    .into_iter()
+                    .map(|topic_filter| broker.unsubscribe(client_id, id, topic_filter))
+                    .collect();
+                writer.send(&Unsuback::new(
+                    unsubscribe.packet_identifier(),
+                    reason_codes,
+                    Properties::default(),
+                ))?
+            }
+            Packet::Pingreq(_) => writer.send(&Pingresp::new())?,
+            Packet::Disconnect(disconnect) => {
+                if let Some(interval) = disconnect.properties().get(Property::SessionExpiryInterval)
+                {
+                    broker.set_session_expiry_interval(client_id, id, interval.value())?;
+                }
+                return Ok(disconnect.reason_code());
+            }
+            packet @ Packet::Connect(_) => {
+                return Err(Error::ProtocolError {
+                    packet_type: Some(packet.packet_type()),
+                    offset: None,
+                    message: "second CONNECT",
+                })
+            }
+            _ => {}
+        }
+    }
+}
+
+// Routes a message from the client to the subscribers and acknowledges it. A resent QoS 2
+// message is only acknowledged again, as it was already routed.
+fn receive_publish(
+    broker: &Broker,
+    client_id: &str,
+    id: u64,
+    writer: &Writer,
+    publish: &Publish,
+) -> Result<(), Error> {
+    // no topic alias maximum is sent in the CONNACK, so the topic name is never empty
+    if !is_valid_topic_name(publish.topic_name()) {
+        return Err(Error::ProtocolError {
+            packet_type: Some(PacketType::PUBLISH),
+            offset: None,
+            message: "invalid topic name",
+        });
+    }
+    let packet_identifier = publish.packet_identifier();
+    let routed = match packet_identifier {
+        Some(packet_identifier) if publish.qos() == 2 => {
+            broker.receive(client_id, id, packet_identifier)
+        }
+        _ => true,
+    };
+    let mut reason_code = ReasonCode::Success;
+    if routed {
+        if publish.retain() {
+            broker.retain(publish);
+        }
+        let (matched, deliveries) = broker.route(client_id, publish);
+        if !matched {
+            reason_code = ReasonCode::NoMatchingSubscribers;
+        }
+        for delivery in deliveries {
+            delivery.send();
+        }
+    }
+    match (publish.qos(), packet_identifier) {
+        (1, Some(packet_identifier)) => writer.send(&Puback::new(
+            packet_identifier,
+            reason_code,
+            Properties::default(),
+        )),
+        (2, Some(packet_identifier)) => writer.send(&Pubrec::new(
+            packet_identifier,
+            reason_code,
+            Properties::default(),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_QUEUED_MESSAGES: usize = 1000;
+const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the server treats the connections it accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    connect_timeout: Duration,
+    server_keep_alive: Option<u16>,
+    max_queued_messages: usize,
+    write_timeout: Duration,
+}
+
+impl ServerOptions {
+    pub fn new() -> Self {
+        ServerOptions {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            server_keep_alive: None,
+            max_queued_messages: DEFAULT_MAX_QUEUED_MESSAGES,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+        }
+    }
+
+    /// How long a new connection has to send its CONNECT before it is closed.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Keep alive in seconds that replaces the one each client asks for in its CONNECT and
+    /// is sent back in the CONNACK; 0 turns keep alive off.
+    pub fn with_server_keep_alive(mut self, server_keep_alive: u16) -> Self {
+        self.server_keep_alive = Some(server_keep_alive);
+        self
+    }
+
+    /// How many QoS 1 and 2 messages a session keeps for a client that is offline or has
+    /// as many messages in flight as its Receive Maximum allows. The oldest message is
+    /// dropped to make room for a new one.
+    pub fn with_max_queued_messages(mut self, max_queued_messages: usize) -> Self {
+        self.max_queued_messages = max_queued_messages;
+        self
+    }
+
+    /// How long a client may keep the server from writing to it, by not reading what it
+    /// was sent, before its connection is closed. A zero timeout counts as a millisecond.
+    pub fn with_write_timeout(mut self, write_timeout: Duration) -> Self {
+        self.write_timeout = write_timeout.max(Duration::from_millis(1));
+        self
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn server_keep_alive(&self) -> Option<u16> {
+        self.server_keep_alive
+    }
+
+    pub fn max_queued_messages(&self) -> usize {
+        self.max_queued_messages
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        self.write_timeout
+    }
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions::new()
+    }
+}
@@ -0,0 +1,113 @@
+use crate::control_packet::publish::Publish;
+use crate::server::stored::StoredMessage;
+use crate::topic;
+use std::collections::HashMap;
+use std::time::Instant;
+
+// The last PUBLISH with the retain flag set on each topic, which is sent to every new
+// subscription that matches the topic.
+#[derive(Debug, Default)]
+pub(crate) struct RetainedMessages {
+    messages: HashMap<String, StoredMessage>,
+}
+
+impl RetainedMessages {
+    /// Keeps the message as the one retained for its topic, or deletes the retained
+    /// message of the topic if the payload is empty.
+    pub(crate) fn retain(&mut self, publish: Publish, now: Instant) {
+        let topic_name = String::from(publish.topic_name());
+        if publish.payload().is_empty() {
+            self.messages.remove(&topic_name);
+            return;
+        }
+        self.messages
+            .insert(topic_name, StoredMessage::new(&publish, now));
+    }
+
+    /// The unexpired messages on topics matching the filter. Their Message Expiry Interval
+    /// is lowered to the time they have left, and expired messages are dropped for good.
+    pub(crate) fn matching(&mut self, topic_filter: &str, now: Instant) -> Vec<Publish> {
+        self.messages.retain(|_, message| !message.is_expired(now));
+        self.messages
+            .values()
+            .filter(|message| topic::matches(topic_filter, message.topic_name()))
+            .map(|message| message.publish(now))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::FourByteInt;
+    use crate::control_packet::publish::Publish;
+    use crate::property::{Properties, Property};
+    use crate::server::retained::RetainedMessages;
+    use std::time::{Duration, Instant};
+
+    fn publish(topic_name: &str, payload: &[u8], properties: Properties) -> Publish {
+        Publish::new(topic_name, Some(7), Vec::from(payload), 1, true, properties)
+    }
+
+    #[test]
+    fn test_retain_replaces_and_deletes() {
+        let mut retained = RetainedMessages::default();
+        let now = Instant::now();
+        retained.retain(publish("sport/tennis", &[1], Properties::default()), now);
+        retained.retain(publish("sport/tennis", &[2], Properties::default()), now);
+        retained.retain(publish("sport/golf", &[3], Properties::default()), now);
+        let mut payloads: Vec<Vec<u8>> = retained
+            .matching("sport/+", now)
+            .iter()
+            .map(|publish| Vec::from(publish.payload()))
+            .collect();
+        payloads.sort();
+        assert_eq!(payloads, vec![vec![2], vec![3]]);
+        // an empty payload deletes the retained message
+        retained.retain(publish("sport/tennis", &[], Properties::default()), now);
+        let matching = retained.matching("sport/tennis", now);
+        assert!(matching.is_empty());
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut retained = RetainedMessages::default();
+        let now = Instant::now();
+        retained.retain(publish("$SYS/uptime", &[1], Properties::default()), now);
+        retained.retain(publish("sport", &[2], Properties::default()), now);
+        assert!(retained
+            .matching("#", now)
+            .iter()
+            .all(|publish| publish.topic_name() == "sport"));
+        let matching = retained.matching("$SYS/#", now);
+        assert_eq!(
+            matching,
+            vec![Publish::new(
+                "$SYS/uptime",
+                None,
+                vec![1],
+                1,
+                true,
+                Properties::default()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_message_expiry() {
+        let mut retained = RetainedMessages::default();
+        let now = Instant::now();
+        let properties =
+            Properties::new(vec![Property::MessageExpiryInterval(FourByteInt::new(10))]);
+        retained.retain(publish("foo", &[1], properties), now);
+        let matching = retained.matching("foo", now + Duration::from_millis(3500));
+        assert_eq!(
+            matching[0].properties(),
+            &Properties::new(vec![Property::MessageExpiryInterval(FourByteInt::new(7))])
+        );
+        assert!(retained
+            .matching("foo", now + Duration::from_secs(10))
+            .is_empty());
+        // dropped, not just hidden
+        assert!(retained.messages.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::common::FourByteInt;
+use crate::control_packet::publish::Publish;
+use crate::property::{Properties, Property};
+use std::time::{Duration, Instant};
+
+// A message the broker keeps to send later, either retained or queued for an offline
+// client. It is dropped once its Message Expiry Interval has passed.
+#[derive(Debug)]
+pub(crate) struct StoredMessage {
+    publish: Publish,
+    expires_at: Option<Instant>,
+}
+
+impl StoredMessage {
+    /// Stores a message received at `now`, without its packet identifier.
+    pub(crate) fn new(publish: &Publish, now: Instant) -> Self {
+        let expires_at = publish
+            .properties()
+            .get(Property::MessageExpiryInterval)
+            .map(|interval| now + Duration::from_secs(u64::from(interval.value())));
+        StoredMessage {
+            publish: Publish::new(
+                publish.topic_name(),
+                None,
+                Vec::from(publish.payload()),
+                publish.qos(),
+                publish.retain(),
+                publish.properties().clone(),
+            ),
+            expires_at,
+        }
+    }
+
+    pub(crate) fn topic_name(&self) -> &str {
+        self.publish.topic_name()
+    }
+
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// The message as it goes out at `now`, with its Message Expiry Interval lowered to
+    /// the time it has left.
+    pub(crate) fn publish(&self, now: Instant) -> Publish {
+        let expires_at = match self.expires_at {
+            Some(expires_at) => expires_at,
+            None => return self.publish.clone(),
+        };
+        let publish = &self.publish;
+        Publish::new(
+            publish.topic_name(),
+            None,
+            Vec::from(publish.payload()),
+            publish.qos(),
+            publish.retain(),
+            with_message_expiry_interval(
+                publish.properties(),
+                expires_at.saturating_duration_since(now),
+            ),
+        )
+    }
+}
+
+// replaces the Message Expiry Interval, rounding up so a message with time left never
+// goes out as already expired
+fn with_message_expiry_interval(properties: &Properties, remaining: Duration) -> Properties {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    let interval = u32::try_from(seconds).unwrap_or(u32::MAX);
+    Properties::new(
+        properties
+            .values()
+            .iter()
+            .map(|property| match property {
+                Property::MessageExpiryInterval(_) => {
+                    Property::MessageExpiryInterval(FourByteInt::new(interval))
+                }
+                property => property.clone(),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::FourByteInt;
+    use crate::control_packet::publish::Publish;
+    use crate::property::{Properties, Property};
+    use crate::server::stored::StoredMessage;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_message_expiry() {
+        let now = Instant::now();
+        let properties =
+            Properties::new(vec![Property::MessageExpiryInterval(FourByteInt::new(10))]);
+        let publish = Publish::new("foo", Some(7), vec![1], 1, true, properties);
+        let stored = StoredMessage::new(&publish, now);
+        let later = now + Duration::from_millis(3500);
+        assert!(!stored.is_expired(later));
+        let expected = Properties::new(vec![Property::MessageExpiryInterval(FourByteInt::new(7))]);
+        assert_eq!(
+            stored.publish(later),
+            Publish::new("foo", None, vec![1], 1, true, expected)
+        );
+        assert!(stored.is_expired(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_no_message_expiry() {
+        let now = Instant::now();
+        let publish = Publish::new("foo", None, vec![1], 0, false, Properties::default());
+        let stored = StoredMessage::new(&publish, now);
+        assert!(!stored.is_expired(now + Duration::from_secs(u64::from(u32::MAX))));
+        assert_eq!(stored.publish(now), publish);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::topic::{MULTI_LEVEL_WILDCARD, SEPARATOR, SINGLE_LEVEL_WILDCARD};
+use std::collections::HashMap;
+
+// one topic level, with the subscribers whose filter ends here
+#[derive(Debug)]
+struct Node<V> {
+    children: HashMap<String, Node<V>>,
+    subscribers: HashMap<String, V>,
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Node {
+            children: HashMap::new(),
+            subscribers: HashMap::new(),
+        }
+    }
+}
+
+impl<V> Node<V> {
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.subscribers.is_empty()
+    }
+}
+
+// Subscriptions indexed by topic filter level, so a PUBLISH only visits the branches its
+// topic name can match instead of every subscription. Each subscriber, keyed by client
+// identifier, has at most one value per topic filter.
+#[derive(Debug)]
+pub(crate) struct TopicTree<V> {
+    root: Node<V>,
+}
+
+impl<V> Default for TopicTree<V> {
+    fn default() -> Self {
+        TopicTree {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<V> TopicTree<V> {
+    /// Subscribes `client_id` to a valid topic filter, returning the value it replaced.
+    pub(crate) fn insert(&mut self, topic_filter: &str, client_id: &str, value: V) -> Option<V> {
+        let mut node = &mut self.root;
+        for level in topic_filter.split(SEPARATOR) {
+            node = node.children.entry(String::from(level)).or_default();
+        }
+        node.subscribers.insert(String::from(client_id), value)
+    }
+
+    /// Unsubscribes `client_id` from the topic filter, returning the value it had.
+    pub(crate) fn remove(&mut self, topic_filter: &str, client_id: &str) -> Option<V> {
+        let levels: Vec<&str> = topic_filter.split(SEPARATOR).collect();
+        remove(&mut self.root, &levels, client_id)
+    }
+
+    /// Every subscriber with a topic filter matching the topic name, once per matching
+    /// filter. Filters starting with a wildcard do not match topic names starting with `$`.
+    pub(crate) fn matches(&self, topic_name: &str) -> Vec<(&str, &V)> {
+        let levels: Vec<&str> = topic_name.split(SEPARATOR).collect();
+        let mut matches = Vec::new();
+        let root_wildcards = !topic_name.starts_with('$');
+        collect(&self.root, &levels, root_wildcards, &mut matches);
+        matches
+    }
+}
+
+// removes the subscription below `node` and prunes the branches left empty
+fn remove<V>(node: &mut Node<V>, levels: &[&str], client_id: &str) -> Option<V> {
+    let (level, rest) = match levels.split_first() {
+        Some(split) => split,
+        None => return node.subscribers.remove(client_id),
+    };
+    let child = node.children.get_mut(*level)?;
+    let removed = remove(child, rest, client_id);
+    if child.is_empty() {
+        node.children.remove(*level);
+    }
+    removed
+}
+
+fn collect<'a, V>(
+    node: &'a Node<V>,
+    levels: &[&str],
+    wildcards: bool,
+    matches: &mut Vec<(&'a str, &'a V)>,
+) {
+    // "#" also matches the parent level, so "sport/#" matches "sport"
+    if wildcards {
+        if let Some(child) = node.children.get(MULTI_LEVEL_WILDCARD) {
+            push_subscribers(child, matches);
+        }
+    }
+    let (level, rest) = match levels.split_first() {
+        Some(split) => split,
+        None => {
+            push_subscribers(node, matches);
+            return;
+        }
+    };
+    if let Some(child) = node.children.get(*level) {
+        collect(child, rest, true, matches);
+    }
+    if wildcards {
+        if let Some(child) = node.children.get(SINGLE_LEVEL_WILDCARD) {
+            collect(child, rest, true, matches);
+        }
+    }
+}
+
+fn push_subscribers<'a, V>(node: &'a Node<V>, matches: &mut Vec<(&'a str, &'a V)>) {
+    matches.extend(
+        node.subscribers
+            .iter()
+            .map(|(client_id, value)| (client_id.as_str(), value)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::topic_tree::TopicTree;
+
+    fn matching(tree: &TopicTree<u8>, topic_name: &str) -> Vec<(String, u8)> {
+        let mut matches: Vec<(String, u8)> = tree
+            .matches(topic_name)
+            .into_iter()
+            .map(|(client_id, value)| (String::from(client_id), *value))
+            .collect();
+        matches.sort();
+        matches
+    }
+
+    #[test]
+    fn test_matches_wildcards() {
+        let mut tree = TopicTree::default();
+        tree.insert("sport/tennis/player1", "exact", 0);
+        tree.insert("sport/+/player1", "single", 1);
+        tree.insert("sport/#", "multi", 2);
+        tree.insert("+/+", "two_levels", 0);
+        tree.insert("#", "everything", 1);
+        assert_eq!(
+            matching(&tree, "sport/tennis/player1"),
+            vec![
+                (String::from("everything"), 1),
+                (String::from("exact"), 0),
+                (String::from("multi"), 2),
+                (String::from("single"), 1),
+            ]
+        );
+        assert_eq!(
+            matching(&tree, "sport"),
+            vec![(String::from("everything"), 1), (String::from("multi"), 2)]
+        );
+        assert_eq!(
+            matching(&tree, "/finance"),
+            vec![
+                (String::from("everything"), 1),
+                (String::from("two_levels"), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matches_dollar_topics() {
+        let mut tree = TopicTree::default();
+        tree.insert("#", "everything", 0);
+        tree.insert("+/uptime", "uptime", 0);
+        tree.insert("$SYS/#", "system", 0);
+        tree.insert("$SYS/+", "system_level", 0);
+        assert_eq!(
+            matching(&tree, "$SYS/uptime"),
+            vec![
+                (String::from("system"), 0),
+                (String::from("system_level"), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let mut tree = TopicTree::default();
+        assert_eq!(tree.insert("foo", "client", 0), None);
+        assert_eq!(tree.insert("foo", "client", 2), Some(0));
+        assert_eq!(matching(&tree, "foo"), vec![(String::from("client"), 2)]);
+    }
+
+    #[test]
+    fn test_remove_prunes() {
+        let mut tree = TopicTree::default();
+        tree.insert("sport/tennis/+", "first", 0);
+        tree.insert("sport/tennis/+", "second", 1);
+        assert_eq!(tree.remove("sport/tennis/+", "first"), Some(0));
+        assert_eq!(tree.remove("sport/tennis/+", "first"), None);
+        assert_eq!(tree.remove("sport/#", "second"), None);
+        assert_eq!(tree.remove("sport/tennis/+", "second"), Some(1));
+        assert!(tree.root.is_empty());
+    }
+}
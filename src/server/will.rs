@@ -0,0 +1,69 @@
+use crate::common::FourByteInt;
+use crate::control_packet::publish::Publish;
+use crate::payload::connect::Will;
+use crate::property::{Properties, Property};
+
+/// Seconds to wait after the connection closes before the will is published.
+pub(crate) fn delay_interval(will: &Will) -> u32 {
+    will.properties()
+        .get(Property::WillDelayInterval)
+        .map_or(0, FourByteInt::value)
+}
+
+/// The will as it is published, with the Will Properties that also apply to a PUBLISH.
+pub(crate) fn publish(will: &Will) -> Publish {
+    let properties = will
+        .properties()
+        .values()
+        .iter()
+        .filter(|property| !matches!(property, Property::WillDelayInterval(_)))
+        .cloned()
+        .collect();
+    Publish::new(
+        will.topic(),
+        None,
+        Vec::from(will.payload()),
+        will.qos(),
+        will.retain(),
+        Properties::new(properties),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{FourByteInt, UTF8String};
+    use crate::control_packet::publish::Publish;
+    use crate::payload::connect::Will;
+    use crate::property::{Properties, Property};
+    use crate::server::will::{delay_interval, publish};
+
+    #[test]
+    fn test_delay_interval() {
+        let will = Will::new("foo", vec![1], 0, false, Properties::default());
+        assert_eq!(delay_interval(&will), 0);
+        let properties = Properties::new(vec![Property::WillDelayInterval(FourByteInt::new(5))]);
+        let will = Will::new("foo", vec![1], 0, false, properties);
+        assert_eq!(delay_interval(&will), 5);
+    }
+
+    #[test]
+    fn test_publish() {
+        let content_type = Property::ContentType(UTF8String::new("text/plain"));
+        let properties = Properties::new(vec![
+            Property::WillDelayInterval(FourByteInt::new(5)),
+            content_type.clone(),
+        ]);
+        let will = Will::new("foo/status", b"offline".to_vec(), 1, true, properties);
+        assert_eq!(
+            publish(&will),
+            Publish::new(
+                "foo/status",
+                None,
+                b"offline".to_vec(),
+                1,
+                true,
+                Properties::new(vec![content_type])
+            )
+        );
+    }
+}
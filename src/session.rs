@@ -0,0 +1,244 @@
+use crate::common::Bytes;
+use crate::control_packet::publish::Publish;
+use crate::control_packet::pubrel::Pubrel;
+use crate::control_packet::{ControlPacket, Packet};
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use std::collections::{BTreeMap, BTreeSet};
+
+// outgoing QoS 1 and 2 messages the other side has not finished acknowledging
+#[derive(Debug)]
+enum InFlight {
+    // waiting for PUBACK (QoS 1) or PUBREC (QoS 2)
+    Publish(Publish),
+    // PUBREL sent, waiting for PUBCOMP
+    Pubrel,
+}
+
+/// What to do with an acknowledgement received from the other side.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Acknowledgement {
+    /// Delivery of the message with this packet identifier is finished.
+    Complete(u16, ReasonCode),
+    /// PUBREC accepted, a PUBREL has to be sent.
+    Release(u16),
+    /// Not an acknowledgement of anything in flight.
+    Unknown,
+}
+
+// The QoS 1 and 2 state of one side of a session: the client keeps it for the server and
+// the server for each client. It is kept across connections so unacknowledged messages can
+// be resent with DUP set when the session is resumed.
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    last_packet_identifier: u16,
+    in_flight: BTreeMap<u16, InFlight>,
+    // incoming QoS 2 messages delivered to the application but not yet released
+    awaiting_release: BTreeSet<u16>,
+}
+
+impl Session {
+    /// The next identifier not used by a message in flight.
+    pub(crate) fn next_packet_identifier(&mut self) -> Option<u16> {
+        for _ in 0..u16::MAX {
+            self.last_packet_identifier = self.last_packet_identifier.checked_add(1).unwrap_or(1);
+            if !self.in_flight.contains_key(&self.last_packet_identifier) {
+                return Some(self.last_packet_identifier);
+            }
+        }
+        None
+    }
+
+    /// How many messages have not finished being acknowledged.
+    pub(crate) fn in_flight_len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Tracks a QoS 1 or 2 publish until it is acknowledged.
+    pub(crate) fn insert(&mut self, publish: Publish) {
+        if let Some(packet_identifier) = publish.packet_identifier() {
+            self.in_flight
+                .insert(packet_identifier, InFlight::Publish(publish));
+        }
+    }
+
+    pub(crate) fn acknowledge(&mut self, packet: &Packet) -> Acknowledgement {
+        match packet {
+            Packet::Puback(puback) => {
+                let packet_identifier = puback.packet_identifier();
+                match self.in_flight.get(&packet_identifier) {
+                    Some(InFlight::Publish(publish)) if publish.qos() == 1 => {
+                        self.in_flight.remove(&packet_identifier);
+                        Acknowledgement::Complete(packet_identifier, puback.reason_code())
+                    }
+                    _ => Acknowledgement::Unknown,
+                }
+            }
+            Packet::Pubrec(pubrec) => {
+                let packet_identifier = pubrec.packet_identifier();
+                match self.in_flight.get(&packet_identifier) {
+                    Some(InFlight::Publish(publish)) if publish.qos() == 2 => {
+                        // a failed PUBREC ends the exchange without PUBREL
+                        if pubrec.reason_code().is_failure() {
+                            self.in_flight.remove(&packet_identifier);
+                            return Acknowledgement::Complete(
+                                packet_identifier,
+                                pubrec.reason_code(),
+                            );
+                        }
+                        self.in_flight.insert(packet_identifier, InFlight::Pubrel);
+                        Acknowledgement::Release(packet_identifier)
+                    }
+                    // a resent PUBREC for a message already released
+                    Some(InFlight::Pubrel) => Acknowledgement::Release(packet_identifier),
+                    _ => Acknowledgement::Unknown,
+                }
+            }
+            Packet::Pubcomp(pubcomp) => {
+                let packet_identifier = pubcomp.packet_identifier();
+                match self.in_flight.get(&packet_identifier) {
+                    Some(InFlight::Pubrel) => {
+                        self.in_flight.remove(&packet_identifier);
+                        Acknowledgement::Complete(packet_identifier, pubcomp.reason_code())
+                    }
+                    _ => Acknowledgement::Unknown,
+                }
+            }
+            _ => Acknowledgement::Unknown,
+        }
+    }
+
+    /// Records an incoming QoS 2 message; false if it was already delivered and this is
+    /// the other side resending it.
+    pub(crate) fn receive(&mut self, packet_identifier: u16) -> bool {
+        self.awaiting_release.insert(packet_identifier)
+    }
+
+    /// Ends the incoming QoS 2 exchange on PUBREL; false if nothing was waiting for it.
+    pub(crate) fn release(&mut self, packet_identifier: u16) -> bool {
+        self.awaiting_release.remove(&packet_identifier)
+    }
+
+    /// Everything in flight, in the order it was first sent, ready to go out again on a
+    /// resumed session.
+    pub(crate) fn retransmissions(&self) -> Vec<Bytes> {
+        self.in_flight
+            .iter()
+            .map(|(packet_identifier, in_flight)| match in_flight {
+                InFlight::Publish(publish) => publish.clone().with_dup(true).as_bytes(),
+                InFlight::Pubrel => Pubrel::new(
+                    *packet_identifier,
+                    ReasonCode::Success,
+                    Properties::default(),
+                )
+                .as_bytes(),
+            })
+            .collect()
+    }
+
+    /// Drops all state, as required when the session was not kept.
+    pub(crate) fn clear(&mut self) {
+        self.in_flight.clear();
+        self.awaiting_release.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::puback::Puback;
+    use crate::control_packet::pubcomp::Pubcomp;
+    use crate::control_packet::publish::Publish;
+    use crate::control_packet::pubrec::Pubrec;
+    use crate::control_packet::{ControlPacket, Packet};
+    use crate::property::Properties;
+    use crate::reason_code::ReasonCode;
+    use crate::session::{Acknowledgement, Session};
+
+    fn publish(packet_identifier: u16, qos: u8) -> Publish {
+        Publish::new(
+            "foo",
+            Some(packet_identifier),
+            vec![1],
+            qos,
+            false,
+            Properties::default(),
+        )
+    }
+
+    #[test]
+    fn test_next_packet_identifier() {
+        let mut session = Session::default();
+        assert_eq!(session.next_packet_identifier(), Some(1));
+        session.insert(publish(2, 1));
+        assert_eq!(session.next_packet_identifier(), Some(3));
+        session.last_packet_identifier = u16::MAX;
+        assert_eq!(session.next_packet_identifier(), Some(1));
+    }
+
+    #[test]
+    fn test_acknowledge_qos_1() {
+        let mut session = Session::default();
+        session.insert(publish(1, 1));
+        let pubrec = Packet::Pubrec(Pubrec::new(1, ReasonCode::Success, Properties::default()));
+        assert_eq!(session.acknowledge(&pubrec), Acknowledgement::Unknown);
+        let puback = Puback::new(1, ReasonCode::QuotaExceeded, Properties::default());
+        assert_eq!(
+            session.acknowledge(&Packet::Puback(puback)),
+            Acknowledgement::Complete(1, ReasonCode::QuotaExceeded)
+        );
+        assert_eq!(session.in_flight_len(), 0);
+    }
+
+    #[test]
+    fn test_acknowledge_qos_2() {
+        let mut session = Session::default();
+        session.insert(publish(1, 2));
+        let pubrec = Pubrec::new(1, ReasonCode::Success, Properties::default());
+        assert_eq!(
+            session.acknowledge(&Packet::Pubrec(pubrec)),
+            Acknowledgement::Release(1)
+        );
+        let pubcomp = Pubcomp::new(1, ReasonCode::Success, Properties::default());
+        assert_eq!(
+            session.acknowledge(&Packet::Pubcomp(pubcomp)),
+            Acknowledgement::Complete(1, ReasonCode::Success)
+        );
+        assert_eq!(session.in_flight_len(), 0);
+    }
+
+    #[test]
+    fn test_acknowledge_qos_2_failed_pubrec() {
+        let mut session = Session::default();
+        session.insert(publish(1, 2));
+        let pubrec = Pubrec::new(1, ReasonCode::TopicNameInvalid, Properties::default());
+        assert_eq!(
+            session.acknowledge(&Packet::Pubrec(pubrec)),
+            Acknowledgement::Complete(1, ReasonCode::TopicNameInvalid)
+        );
+    }
+
+    #[test]
+    fn test_receive_release() {
+        let mut session = Session::default();
+        assert!(session.receive(4));
+        assert!(!session.receive(4));
+        assert!(session.release(4));
+        assert!(!session.release(4));
+    }
+
+    #[test]
+    fn test_retransmissions() {
+        let mut session = Session::default();
+        session.insert(publish(1, 1));
+        session.insert(publish(2, 2));
+        let pubrec = Pubrec::new(2, ReasonCode::Success, Properties::default());
+        session.acknowledge(&Packet::Pubrec(pubrec));
+        let retransmissions = session.retransmissions();
+        assert_eq!(retransmissions.len(), 2);
+        let resent = Publish::from_bytes(&retransmissions[0]).unwrap();
+        assert!(resent.dup());
+        assert_eq!(retransmissions[1], vec![0x62, 2, 0, 2]);
+        session.clear();
+        assert!(session.retransmissions().is_empty());
+    }
+}
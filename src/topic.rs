@@ -0,0 +1,105 @@
+use crate::common::MAX_ENCODED_LEN;
+
+pub(crate) const SEPARATOR: char = '/';
+pub(crate) const SINGLE_LEVEL_WILDCARD: &str = "+";
+pub(crate) const MULTI_LEVEL_WILDCARD: &str = "#";
+
+/// Topic names are what PUBLISH packets carry: at least one character and no more than fit
+/// in a UTF-8 string, no wildcards and no null character.
+pub(crate) fn is_valid_topic_name(topic_name: &str) -> bool {
+    !topic_name.is_empty()
+        && topic_name.len() <= MAX_ENCODED_LEN
+        && !topic_name.contains(['+', '#', '\0'])
+}
+
+/// Topic filters may use `+` for a whole level and `#` for a whole last level, but no null
+/// character. They are as long as a topic name may be.
+pub(crate) fn is_valid_topic_filter(topic_filter: &str) -> bool {
+    if topic_filter.is_empty()
+        || topic_filter.len() > MAX_ENCODED_LEN
+        || topic_filter.contains('\0')
+    {
+        return false;
+    }
+    let levels: Vec<&str> = topic_filter.split(SEPARATOR).collect();
+    let last = levels.len() - 1;
+    levels.iter().enumerate().all(|(idx, level)| {
+        if level.contains(['+', '#']) {
+            *level == SINGLE_LEVEL_WILDCARD || (*level == MULTI_LEVEL_WILDCARD && idx == last)
+        } else {
+            true
+        }
+    })
+}
+
+/// Whether a valid topic filter matches a topic name. Filters starting with a wildcard do not
+/// match topics starting with `$`, which are reserved for the server.
+pub(crate) fn matches(topic_filter: &str, topic_name: &str) -> bool {
+    if topic_name.starts_with('$')
+        && (topic_filter.starts_with(SINGLE_LEVEL_WILDCARD)
+            || topic_filter.starts_with(MULTI_LEVEL_WILDCARD))
+    {
+        return false;
+    }
+    let mut filter_levels = topic_filter.split(SEPARATOR);
+    let mut name_levels = topic_name.split(SEPARATOR);
+    loop {
+        match (filter_levels.next(), name_levels.next()) {
+            // "#" also matches the parent level, so "sport/#" matches "sport"
+            (Some(MULTI_LEVEL_WILDCARD), _) => return true,
+            (Some(SINGLE_LEVEL_WILDCARD), Some(_)) => {}
+            (Some(filter_level), Some(name_level)) if filter_level == name_level => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::topic::{is_valid_topic_filter, is_valid_topic_name, matches};
+
+    #[test]
+    fn test_is_valid_topic_name() {
+        assert!(is_valid_topic_name("sport/tennis"));
+        assert!(is_valid_topic_name("/"));
+        assert!(!is_valid_topic_name(""));
+        assert!(!is_valid_topic_name("sport/+"));
+        assert!(!is_valid_topic_name("sport/#"));
+        assert!(!is_valid_topic_name("sport\0"));
+        assert!(is_valid_topic_name(&"a".repeat(65_535)));
+        assert!(!is_valid_topic_name(&"a".repeat(65_536)));
+    }
+
+    #[test]
+    fn test_is_valid_topic_filter() {
+        assert!(is_valid_topic_filter("sport/tennis/#"));
+        assert!(is_valid_topic_filter("#"));
+        assert!(is_valid_topic_filter("+/tennis/+"));
+        assert!(!is_valid_topic_filter(""));
+        assert!(!is_valid_topic_filter("sport/tennis#"));
+        assert!(!is_valid_topic_filter("sport/#/ranking"));
+        assert!(!is_valid_topic_filter("sport+"));
+        assert!(!is_valid_topic_filter("sport/\0/#"));
+        assert!(!is_valid_topic_filter(&"a".repeat(65_536)));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("sport/tennis/#", "sport/tennis/player1/ranking"));
+        assert!(matches("sport/#", "sport"));
+        assert!(matches("sport/+", "sport/"));
+        assert!(matches("+/+", "/finance"));
+        assert!(matches("sport/tennis", "sport/tennis"));
+        assert!(!matches("sport/+", "sport"));
+        assert!(!matches("sport/+", "sport/tennis/player1"));
+        assert!(!matches("sport/tennis", "sport/tennis/player1"));
+    }
+
+    #[test]
+    fn test_matches_dollar_topics() {
+        assert!(!matches("#", "$SYS/uptime"));
+        assert!(!matches("+/uptime", "$SYS/uptime"));
+        assert!(matches("$SYS/#", "$SYS/uptime"));
+    }
+}
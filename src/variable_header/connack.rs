@@ -0,0 +1,94 @@
+use crate::common::{Byte, Bytes, ParseError, Parseable};
+use crate::control_packet::PacketType;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ConnackVariableHeader {
+    session_present: bool,
+    reason_code: ReasonCode,
+    properties: Properties,
+}
+
+impl ConnackVariableHeader {
+    fn acknowledge_flags_bytes(&self) -> Bytes {
+        vec![self.session_present as Byte]
+    }
+
+    pub(crate) fn new(
+        session_present: bool,
+        reason_code: ReasonCode,
+        properties: Properties,
+    ) -> Self {
+        ConnackVariableHeader {
+            session_present,
+            reason_code,
+            properties,
+        }
+    }
+
+    pub(crate) fn session_present(&self) -> bool {
+        self.session_present
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.reason_code
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (acknowledge_flags, af_leftover) = byte_slice.parse_byte()?;
+        if acknowledge_flags & 0b1111_1110 != 0 {
+            return Err(
+                ParseError::new("reserved connect acknowledge flags set").at(byte_slice.len())
+            );
+        }
+        let (reason_code, rc_leftover) = af_leftover.parse_byte()?;
+        let reason_code = ReasonCode::from_value_for(reason_code, &PacketType::CONNACK)
+            .map_err(|error| error.at(af_leftover.len()))?;
+        let (properties, leftover) =
+            Properties::from_bytes(Vec::from(rc_leftover), &PacketType::CONNACK)?;
+        let variable_header =
+            ConnackVariableHeader::new(acknowledge_flags == 1, reason_code, properties);
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for ConnackVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.acknowledge_flags_bytes();
+        bytes.push(self.reason_code.value());
+        bytes.append(&mut self.properties.as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::property::Properties;
+    use crate::reason_code::ReasonCode;
+    use crate::variable_header::connack::ConnackVariableHeader;
+    use crate::variable_header::VariableHeader;
+
+    #[test]
+    fn test_as_bytes() {
+        let variable_header =
+            ConnackVariableHeader::new(true, ReasonCode::UnspecifiedError, Properties::default());
+        assert_eq!(variable_header.as_bytes(), vec![1, 0x80, 0]);
+    }
+
+    #[test]
+    fn test_from_bytes_reserved_flags() {
+        assert!(ConnackVariableHeader::from_bytes(vec![2, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_reason_code() {
+        assert!(ConnackVariableHeader::from_bytes(vec![0, 0x8E, 0]).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::common::{Byte, Bytes, ParseError, Parseable, Serializable, TwoByteInt, UTF8String};
+use crate::control_packet::PacketType;
+use crate::payload::connect::Will;
+use crate::property::Properties;
+use crate::variable_header::VariableHeader;
+
+const PROTOCOL_NAME: &str = "MQTT";
+const PROTOCOL_VERSION: u8 = 5;
+
+const CLEAN_START: Byte = 0b0000_0010;
+const WILL_FLAG: Byte = 0b0000_0100;
+const WILL_QOS: Byte = 0b0001_1000;
+const WILL_RETAIN: Byte = 0b0010_0000;
+const PASSWORD_FLAG: Byte = 0b0100_0000;
+const USER_NAME_FLAG: Byte = 0b1000_0000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct ConnectFlags {
+    clean_start: bool,
+    will: bool,
+    will_qos: u8,
+    will_retain: bool,
+    user_name: bool,
+    password: bool,
+}
+
+impl ConnectFlags {
+    pub(crate) fn new(
+        clean_start: bool,
+        will: Option<&Will>,
+        user_name: bool,
+        password: bool,
+    ) -> Self {
+        ConnectFlags {
+            clean_start,
+            will: will.is_some(),
+            will_qos: will.map_or(0, |will| will.qos()),
+            will_retain: will.is_some_and(|will| will.retain()),
+            user_name,
+            password,
+        }
+    }
+
+    pub(crate) fn clean_start(&self) -> bool {
+        self.clean_start
+    }
+
+    pub(crate) fn will(&self) -> bool {
+        self.will
+    }
+
+    pub(crate) fn will_qos(&self) -> u8 {
+        self.will_qos
+    }
+
+    pub(crate) fn will_retain(&self) -> bool {
+        self.will_retain
+    }
+
+    pub(crate) fn user_name(&self) -> bool {
+        self.user_name
+    }
+
+    pub(crate) fn password(&self) -> bool {
+        self.password
+    }
+
+    pub(crate) fn from_byte(byte: Byte) -> Result<Self, ParseError> {
+        if byte & 0b0000_0001 != 0 {
+            return Err(ParseError::protocol("reserved connect flag set"));
+        }
+        let will = byte & WILL_FLAG != 0;
+        let will_qos = (byte & WILL_QOS) >> 3;
+        let will_retain = byte & WILL_RETAIN != 0;
+        if will_qos > 2 {
+            return Err(ParseError::new("will qos of 3"));
+        }
+        if !will && (will_qos != 0 || will_retain) {
+            return Err(ParseError::protocol(
+                "will qos or retain set without will flag",
+            ));
+        }
+        Ok(ConnectFlags {
+            clean_start: byte & CLEAN_START != 0,
+            will,
+            will_qos,
+            will_retain,
+            user_name: byte & USER_NAME_FLAG != 0,
+            password: byte & PASSWORD_FLAG != 0,
+        })
+    }
+
+    pub(crate) fn as_byte(&self) -> Byte {
+        let mut byte: Byte = 0;
+        if self.clean_start {
+            byte |= CLEAN_START;
+        }
+        if self.will {
+            byte |= WILL_FLAG;
+        }
+        byte |= (self.will_qos << 3) & WILL_QOS;
+        if self.will_retain {
+            byte |= WILL_RETAIN;
+        }
+        if self.password {
+            byte |= PASSWORD_FLAG;
+        }
+        if self.user_name {
+            byte |= USER_NAME_FLAG;
+        }
+        byte
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ConnectVariableHeader {
+    flags: ConnectFlags,
+    keep_alive: u16,
+    properties: Properties,
+}
+
+impl ConnectVariableHeader {
+    fn protocol_bytes(&self) -> Bytes {
+        let protocol_name = UTF8String::new(PROTOCOL_NAME);
+        let mut bytes = protocol_name.as_bytes();
+        bytes.push(PROTOCOL_VERSION);
+        bytes
+    }
+    fn flag_bytes(&self) -> Bytes {
+        vec![self.flags.as_byte()]
+    }
+    fn keep_alive_bytes(&self) -> Bytes {
+        TwoByteInt::new(self.keep_alive).as_bytes()
+    }
+    fn property_bytes(&self) -> Bytes {
+        self.properties.as_bytes()
+    }
+
+    pub(crate) fn new(flags: ConnectFlags, keep_alive: u16, properties: Properties) -> Self {
+        ConnectVariableHeader {
+            flags,
+            keep_alive,
+            properties,
+        }
+    }
+
+    pub(crate) fn flags(&self) -> &ConnectFlags {
+        &self.flags
+    }
+
+    pub(crate) fn keep_alive(&self) -> u16 {
+        self.keep_alive
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    pub(crate) fn from_bytes(bytes: Bytes) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (protocol_name, pn_leftover) = byte_slice.parse_utf8_string()?;
+        if protocol_name.value() != PROTOCOL_NAME {
+            return Err(ParseError::protocol("invalid protocol name").at(byte_slice.len()));
+        }
+        let (protocol_version, pv_leftover) = pn_leftover.parse_byte()?;
+        if protocol_version != PROTOCOL_VERSION {
+            let error = ParseError::unsupported_protocol_version(protocol_version);
+            return Err(error.at(pn_leftover.len()));
+        }
+        let (flag_byte, f_leftover) = pv_leftover.parse_byte()?;
+        let flags =
+            ConnectFlags::from_byte(flag_byte).map_err(|error| error.at(pv_leftover.len()))?;
+        let (keep_alive, ka_leftover) = f_leftover.parse_two_byte_int()?;
+        let (properties, leftover) =
+            Properties::from_bytes(Vec::from(ka_leftover), &PacketType::CONNECT)?;
+        let variable_header = ConnectVariableHeader::new(flags, keep_alive.value(), properties);
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for ConnectVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.protocol_bytes();
+        bytes.append(&mut self.flag_bytes());
+        bytes.append(&mut self.keep_alive_bytes());
+        bytes.append(&mut self.property_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{FourByteInt, ParseErrorKind, UTF8StringPair};
+    use crate::payload::connect::Will;
+    use crate::property::{Properties, Property};
+    use crate::variable_header::connect::{ConnectFlags, ConnectVariableHeader};
+    use crate::variable_header::VariableHeader;
+
+    const KEEP_ALIVE: u16 = 3;
+
+    #[test]
+    fn test_as_bytes() {
+        let variable_header =
+            ConnectVariableHeader::new(ConnectFlags::default(), KEEP_ALIVE, Properties::default());
+        let bytes = variable_header.as_bytes();
+        assert_eq!(bytes, vec![0, 4, 77, 81, 84, 84, 5, 0, 0, 3, 0]);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let bytes = vec![0, 4, 77, 81, 84, 84, 5, 0, 0, 3, 0, 2, 3];
+        let (parsed_variable_header, leftover) = ConnectVariableHeader::from_bytes(bytes).unwrap();
+        let variable_header =
+            ConnectVariableHeader::new(ConnectFlags::default(), KEEP_ALIVE, Properties::default());
+        assert_eq!(parsed_variable_header, variable_header);
+        assert_eq!(leftover, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let variable_header =
+            ConnectVariableHeader::new(ConnectFlags::default(), KEEP_ALIVE, Properties::default());
+        let bytes = variable_header.as_bytes();
+        let (parsed_variable_header, _leftover) = ConnectVariableHeader::from_bytes(bytes).unwrap();
+        assert_eq!(parsed_variable_header, variable_header);
+    }
+
+    #[test]
+    fn test_len() {
+        let variable_header =
+            ConnectVariableHeader::new(ConnectFlags::default(), KEEP_ALIVE, Properties::default());
+        let len = variable_header.len();
+        assert_eq!(len, 11);
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes_with_properties() {
+        let properties = Properties::new(vec![
+            Property::SessionExpiryInterval(FourByteInt::new(120)),
+            Property::UserProperty(UTF8StringPair::new("foo", "bar")),
+        ]);
+        let variable_header =
+            ConnectVariableHeader::new(ConnectFlags::default(), KEEP_ALIVE, properties);
+        let bytes = variable_header.as_bytes();
+        assert_eq!(bytes[10], 16);
+        let (parsed_variable_header, leftover) = ConnectVariableHeader::from_bytes(bytes).unwrap();
+        assert_eq!(parsed_variable_header, variable_header);
+        assert!(leftover.is_empty());
+    }
+
+    #[test]
+    fn test_from_bytes_disallowed_property() {
+        let bytes = vec![0, 4, 77, 81, 84, 84, 5, 0, 0, 3, 3, 0x23, 0, 1];
+        assert!(ConnectVariableHeader::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn test_flags_as_byte_from_byte() {
+        let will = Will::new("foo", vec![], 2, true, Properties::default());
+        let flags = ConnectFlags::new(true, Some(&will), true, false);
+        let byte = flags.as_byte();
+        assert_eq!(byte, 0b1011_0110);
+        assert_eq!(ConnectFlags::from_byte(byte).unwrap(), flags);
+    }
+
+    #[test]
+    fn test_flags_from_byte_reserved() {
+        assert!(ConnectFlags::from_byte(0b0000_0011).is_err());
+    }
+
+    #[test]
+    fn test_flags_from_byte_will_qos() {
+        assert!(ConnectFlags::from_byte(0b0001_1100).is_err());
+        assert!(ConnectFlags::from_byte(0b0010_0000).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_unsupported_protocol_version() {
+        let bytes = vec![0, 4, 77, 81, 84, 84, 4, 0, 0, 3, 0];
+        let error = ConnectVariableHeader::from_bytes(bytes).unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::UnsupportedProtocolVersion(4));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::common::{Bytes, ParseError, Parseable, Serializable, TwoByteInt};
+use crate::control_packet::PacketType;
+use crate::property::Properties;
+use crate::variable_header::VariableHeader;
+
+// shared by SUBSCRIBE, SUBACK, UNSUBSCRIBE and UNSUBACK
+#[derive(Debug, PartialEq)]
+pub(crate) struct PacketIdentifierVariableHeader {
+    packet_identifier: TwoByteInt,
+    properties: Properties,
+}
+
+impl PacketIdentifierVariableHeader {
+    pub(crate) fn new(packet_identifier: u16, properties: Properties) -> Self {
+        PacketIdentifierVariableHeader {
+            packet_identifier: TwoByteInt::new(packet_identifier),
+            properties,
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.packet_identifier.value()
+    }
+
+    pub(crate) fn from_bytes(
+        bytes: Bytes,
+        packet_type: &PacketType,
+    ) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (packet_identifier, pi_leftover) = byte_slice.parse_two_byte_int()?;
+        let (properties, leftover) = Properties::from_bytes(Vec::from(pi_leftover), packet_type)?;
+        let variable_header = PacketIdentifierVariableHeader {
+            packet_identifier,
+            properties,
+        };
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for PacketIdentifierVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.packet_identifier.as_bytes();
+        bytes.append(&mut self.properties.as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::PacketType;
+    use crate::property::Properties;
+    use crate::variable_header::packet_identifier::PacketIdentifierVariableHeader;
+    use crate::variable_header::VariableHeader;
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let variable_header = PacketIdentifierVariableHeader::new(258, Properties::default());
+        let bytes = variable_header.as_bytes();
+        assert_eq!(bytes, vec![1, 2, 0]);
+        let (parsed_variable_header, leftover) =
+            PacketIdentifierVariableHeader::from_bytes(bytes, &PacketType::SUBACK).unwrap();
+        assert_eq!(parsed_variable_header, variable_header);
+        assert!(leftover.is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::common::{Bytes, ParseError, Parseable, Serializable, TwoByteInt};
+use crate::control_packet::PacketType;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::reason_code::ReasonCodeVariableHeader;
+use crate::variable_header::VariableHeader;
+
+// shared by PUBACK, PUBREC, PUBREL and PUBCOMP
+#[derive(Debug, PartialEq)]
+pub(crate) struct PubResponseVariableHeader {
+    packet_identifier: TwoByteInt,
+    reason_code: ReasonCodeVariableHeader,
+}
+
+impl PubResponseVariableHeader {
+    pub(crate) fn new(
+        packet_identifier: u16,
+        reason_code: ReasonCode,
+        properties: Properties,
+    ) -> Self {
+        PubResponseVariableHeader {
+            packet_identifier: TwoByteInt::new(packet_identifier),
+            reason_code: ReasonCodeVariableHeader::new(reason_code, properties),
+        }
+    }
+
+    pub(crate) fn packet_identifier(&self) -> u16 {
+        self.packet_identifier.value()
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.reason_code.reason_code()
+    }
+
+    pub(crate) fn from_bytes(
+        bytes: Bytes,
+        packet_type: &PacketType,
+    ) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (packet_identifier, pi_leftover) = byte_slice.parse_two_byte_int()?;
+        let (reason_code, leftover) =
+            ReasonCodeVariableHeader::from_bytes(Vec::from(pi_leftover), packet_type)?;
+        let variable_header = PubResponseVariableHeader {
+            packet_identifier,
+            reason_code,
+        };
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for PubResponseVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.packet_identifier.as_bytes();
+        bytes.append(&mut self.reason_code.as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_packet::PacketType;
+    use crate::property::Properties;
+    use crate::reason_code::ReasonCode;
+    use crate::variable_header::pub_response::PubResponseVariableHeader;
+    use crate::variable_header::VariableHeader;
+
+    #[test]
+    fn test_as_bytes() {
+        let variable_header =
+            PubResponseVariableHeader::new(7, ReasonCode::Success, Properties::default());
+        assert_eq!(variable_header.as_bytes(), vec![0, 7]);
+        let variable_header = PubResponseVariableHeader::new(
+            7,
+            ReasonCode::NoMatchingSubscribers,
+            Properties::default(),
+        );
+        assert_eq!(variable_header.as_bytes(), vec![0, 7, 0x10]);
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let variable_header = PubResponseVariableHeader::new(
+            7,
+            ReasonCode::NoMatchingSubscribers,
+            Properties::default(),
+        );
+        let bytes = variable_header.as_bytes();
+        let (parsed_variable_header, leftover) =
+            PubResponseVariableHeader::from_bytes(bytes, &PacketType::PUBACK).unwrap();
+        assert_eq!(parsed_variable_header, variable_header);
+        assert!(leftover.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::common::{Bytes, ParseError, Parseable, Serializable, TwoByteInt, UTF8String};
+use crate::control_packet::PacketType;
+use crate::property::Properties;
+use crate::variable_header::VariableHeader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PublishVariableHeader {
+    topic_name: UTF8String,
+    packet_identifier: Option<TwoByteInt>,
+    properties: Properties,
+}
+
+impl PublishVariableHeader {
+    pub(crate) fn new(
+        topic_name: &str,
+        packet_identifier: Option<u16>,
+        properties: Properties,
+    ) -> Self {
+        PublishVariableHeader {
+            topic_name: UTF8String::new(topic_name),
+            packet_identifier: packet_identifier.map(TwoByteInt::new),
+            properties,
+        }
+    }
+
+    pub(crate) fn topic_name(&self) -> &str {
+        self.topic_name.value()
+    }
+
+    pub(crate) fn packet_identifier(&self) -> Option<u16> {
+        self.packet_identifier.as_ref().map(|id| id.value())
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    // the packet identifier is only present in QoS 1 and QoS 2 PUBLISH packets
+    pub(crate) fn from_bytes(bytes: Bytes, qos: u8) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        let (topic_name, tn_leftover) = byte_slice.parse_utf8_string()?;
+        let (packet_identifier, pi_leftover) = if qos > 0 {
+            let (packet_identifier, pi_leftover) = tn_leftover.parse_two_byte_int()?;
+            (Some(packet_identifier), pi_leftover)
+        } else {
+            (None, tn_leftover)
+        };
+        let (properties, leftover) =
+            Properties::from_bytes(Vec::from(pi_leftover), &PacketType::PUBLISH)?;
+        let variable_header = PublishVariableHeader {
+            topic_name,
+            packet_identifier,
+            properties,
+        };
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for PublishVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        let mut bytes = self.topic_name.as_bytes();
+        if let Some(packet_identifier) = &self.packet_identifier {
+            bytes.append(&mut packet_identifier.as_bytes());
+        }
+        bytes.append(&mut self.properties.as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::property::Properties;
+    use crate::variable_header::publish::PublishVariableHeader;
+    use crate::variable_header::VariableHeader;
+
+    #[test]
+    fn test_as_bytes() {
+        let variable_header = PublishVariableHeader::new("a/b", Some(5), Properties::default());
+        assert_eq!(variable_header.as_bytes(), vec![0, 3, 97, 47, 98, 0, 5, 0]);
+    }
+
+    #[test]
+    fn test_from_bytes_qos_0() {
+        let bytes = vec![0, 3, 97, 47, 98, 0, 1, 2];
+        let (variable_header, leftover) = PublishVariableHeader::from_bytes(bytes, 0).unwrap();
+        assert_eq!(variable_header.packet_identifier(), None);
+        assert_eq!(leftover, vec![1, 2]);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::common::{Bytes, ParseError, Parseable};
+use crate::control_packet::PacketType;
+use crate::property::Properties;
+use crate::reason_code::ReasonCode;
+use crate::variable_header::VariableHeader;
+
+// used by DISCONNECT and AUTH, and as the tail of the PUBACK/PUBREC/PUBREL/PUBCOMP header
+#[derive(Debug, PartialEq)]
+pub(crate) struct ReasonCodeVariableHeader {
+    reason_code: ReasonCode,
+    properties: Properties,
+}
+
+impl ReasonCodeVariableHeader {
+    pub(crate) fn new(reason_code: ReasonCode, properties: Properties) -> Self {
+        ReasonCodeVariableHeader {
+            reason_code,
+            properties,
+        }
+    }
+
+    pub(crate) fn reason_code(&self) -> ReasonCode {
+        self.reason_code
+    }
+
+    pub(crate) fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    // the reason code and properties may be omitted, which means Success with no properties
+    pub(crate) fn from_bytes(
+        bytes: Bytes,
+        packet_type: &PacketType,
+    ) -> Result<(Self, Bytes), ParseError> {
+        let byte_slice = &bytes[..];
+        if byte_slice.is_empty() {
+            return Ok((
+                ReasonCodeVariableHeader::new(ReasonCode::Success, Properties::default()),
+                vec![],
+            ));
+        }
+        let (reason_code, rc_leftover) = byte_slice.parse_byte()?;
+        let reason_code = ReasonCode::from_value_for(reason_code, packet_type)
+            .map_err(|error| error.at(byte_slice.len()))?;
+        if rc_leftover.is_empty() {
+            let variable_header = ReasonCodeVariableHeader::new(reason_code, Properties::default());
+            return Ok((variable_header, vec![]));
+        }
+        let (properties, leftover) = Properties::from_bytes(Vec::from(rc_leftover), packet_type)?;
+        let variable_header = ReasonCodeVariableHeader::new(reason_code, properties);
+        Ok((variable_header, leftover))
+    }
+}
+
+impl VariableHeader for ReasonCodeVariableHeader {
+    fn as_bytes(&self) -> Bytes {
+        if self.reason_code == ReasonCode::Success && self.properties.is_empty() {
+            return vec![];
+        }
+        let mut bytes = vec![self.reason_code.value()];
+        if !self.properties.is_empty() {
+            bytes.append(&mut self.properties.as_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::UTF8String;
+    use crate::control_packet::PacketType;
+    use crate::property::{Properties, Property};
+    use crate::reason_code::ReasonCode;
+    use crate::variable_header::reason_code::ReasonCodeVariableHeader;
+    use crate::variable_header::VariableHeader;
+
+    #[test]
+    fn test_as_bytes() {
+        let variable_header =
+            ReasonCodeVariableHeader::new(ReasonCode::Success, Properties::default());
+        assert_eq!(variable_header.as_bytes(), vec![]);
+        let variable_header = ReasonCodeVariableHeader::new(
+            ReasonCode::DisconnectWithWillMessage,
+            Properties::default(),
+        );
+        assert_eq!(variable_header.as_bytes(), vec![0x04]);
+    }
+
+    #[test]
+    fn test_as_bytes_from_bytes() {
+        let properties = Properties::new(vec![Property::ReasonString(UTF8String::new("foo"))]);
+        let variable_header =
+            ReasonCodeVariableHeader::new(ReasonCode::SessionTakenOver, properties);
+        let bytes = variable_header.as_bytes();
+        let (parsed_variable_header, leftover) =
+            ReasonCodeVariableHeader::from_bytes(bytes, &PacketType::DISCONNECT).unwrap();
+        assert_eq!(parsed_variable_header, variable_header);
+        assert!(leftover.is_empty());
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_reason_code() {
+        assert!(ReasonCodeVariableHeader::from_bytes(vec![0x8E], &PacketType::AUTH).is_err());
+        assert!(ReasonCodeVariableHeader::from_bytes(vec![0x03], &PacketType::DISCONNECT).is_err());
+    }
+}